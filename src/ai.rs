@@ -0,0 +1,219 @@
+use crate::{
+    bitboard::Board,
+    pieces::{Piece, PieceColor, PieceType},
+    rules::{BoardShape, GameResult, Move, Position},
+};
+
+/// Search depth of the built-in engine when none is given
+pub const DEFAULT_DEPTH: u32 = 3;
+
+const MATE_SCORE: i32 = 100_000;
+
+pub fn piece_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::King => 0,
+        PieceType::Queen => 900,
+        PieceType::Rook => 500,
+        PieceType::Bishop => 330,
+        PieceType::Knight => 320,
+        PieceType::Unicorn => 250,
+        PieceType::Archbishop => 850,
+        PieceType::Chancellor => 880,
+        PieceType::Amazon => 1250,
+        PieceType::Pawn => 100,
+    }
+}
+
+/// Small bonus for pieces that stand well, seen from the side the piece belongs to
+fn square_bonus(piece: &Piece, board: BoardShape) -> i32 {
+    let last_row = board.rows() as i32 - 1;
+    // The ranks of all levels one after the other, so climbing a level counts as moving on
+    let row = piece.z as i32 * board.ranks as i32 + piece.x as i32;
+    // Ranks counted from the piece's own side of the board
+    let rank = match piece.color {
+        PieceColor::Light => row,
+        PieceColor::Dark => last_row - row,
+        PieceColor::Blue => piece.y as i32,
+        PieceColor::Green => board.files as i32 - 1 - piece.y as i32,
+    };
+    // 0 on the edge files up to 3 on the center files of the usual board
+    let center = |coordinate: u8, size: u8| {
+        let last = size as i32 - 1;
+        (last - (2 * coordinate as i32 - last).abs()) / 2
+    };
+    let file_center = center(piece.y, board.files);
+    let rank_center = center(piece.x, board.ranks);
+    match piece.piece_type {
+        PieceType::Pawn => rank * 8 + file_center * 4,
+        PieceType::Knight
+        | PieceType::Bishop
+        | PieceType::Unicorn
+        | PieceType::Archbishop
+        | PieceType::Chancellor => (file_center + rank_center) * 8,
+        PieceType::Queen | PieceType::Amazon => (file_center + rank_center) * 2,
+        PieceType::Rook => {
+            if rank == last_row - 1 {
+                20
+            } else {
+                0
+            }
+        }
+        // Stay behind the pawns until the endgame
+        PieceType::King => {
+            if rank == 0 {
+                10
+            } else {
+                -10 * rank
+            }
+        }
+    }
+}
+
+/// What the search needs from a position, so standard games can be searched on bitboards
+trait Searchable: Sized {
+    fn legal_moves(&self) -> Vec<Move>;
+
+    fn outcome(&self, legal_moves: &[Move]) -> Option<GameResult>;
+
+    fn turn(&self) -> PieceColor;
+
+    fn piece_at(&self, square: (u8, u8, u8)) -> Option<Piece>;
+
+    /// The position after `mv`
+    fn play(&self, mv: &Move) -> Self;
+
+    /// Score of the position for the side to move
+    fn evaluate(&self) -> i32;
+}
+
+/// Material and square bonuses of `pieces` for the side `turn`
+fn material(pieces: impl Iterator<Item = Piece>, turn: PieceColor, board: BoardShape) -> i32 {
+    pieces
+        .map(|piece| {
+            let value = piece_value(piece.piece_type) + square_bonus(&piece, board);
+            if piece.color == turn {
+                value
+            } else {
+                -value
+            }
+        })
+        .sum()
+}
+
+impl Searchable for Position {
+    fn legal_moves(&self) -> Vec<Move> {
+        Position::legal_moves(self)
+    }
+
+    fn outcome(&self, legal_moves: &[Move]) -> Option<GameResult> {
+        self.variant.outcome(self, legal_moves)
+    }
+
+    fn turn(&self) -> PieceColor {
+        self.turn
+    }
+
+    fn piece_at(&self, square: (u8, u8, u8)) -> Option<Piece> {
+        Position::piece_at(self, square).copied()
+    }
+
+    fn play(&self, mv: &Move) -> Self {
+        let mut after = self.clone();
+        after.make_move(mv);
+        after
+    }
+
+    fn evaluate(&self) -> i32 {
+        let pieces = self.pieces.iter().copied();
+        material(pieces, self.turn, self.variant.board()) + self.variant.evaluate(self)
+    }
+}
+
+impl Searchable for Board {
+    fn legal_moves(&self) -> Vec<Move> {
+        Board::legal_moves(self)
+    }
+
+    fn outcome(&self, legal_moves: &[Move]) -> Option<GameResult> {
+        Board::outcome(self, legal_moves)
+    }
+
+    fn turn(&self) -> PieceColor {
+        self.turn
+    }
+
+    fn piece_at(&self, square: (u8, u8, u8)) -> Option<Piece> {
+        Board::piece_at(self, square)
+    }
+
+    fn play(&self, mv: &Move) -> Self {
+        let mut after = *self;
+        after.make_move(mv);
+        after
+    }
+
+    fn evaluate(&self) -> i32 {
+        material(self.pieces(), self.turn, BoardShape::STANDARD)
+    }
+}
+
+/// Search `depth` plies deep with alpha-beta pruning, returns the score for the side to move
+fn negamax<P: Searchable>(position: &P, depth: u32, mut alpha: i32, beta: i32, ply: i32) -> i32 {
+    let moves = ordered_moves(position);
+    if let Some(result) = position.outcome(&moves) {
+        // Prefer the quickest win and the slowest loss
+        return match result.winner {
+            Some(winner) if winner == position.turn() => MATE_SCORE - ply,
+            Some(_) => -MATE_SCORE + ply,
+            None => 0,
+        };
+    }
+    if depth == 0 {
+        return position.evaluate();
+    }
+
+    for mv in moves {
+        let score = -negamax(&position.play(&mv), depth - 1, -beta, -alpha, ply + 1);
+        if score >= beta {
+            return beta;
+        }
+        alpha = alpha.max(score);
+    }
+    alpha
+}
+
+/// Legal moves with captures and promotions first, so alpha-beta can cut more of the tree
+fn ordered_moves<P: Searchable>(position: &P) -> Vec<Move> {
+    let mut moves = position.legal_moves();
+    moves.sort_by_key(|mv| {
+        let captured = position
+            .piece_at(mv.to)
+            .filter(|piece| piece.color != position.turn())
+            .map_or(0, |piece| piece_value(piece.piece_type));
+        let promotion = mv.promotion.map_or(0, piece_value);
+        -(captured + promotion)
+    });
+    moves
+}
+
+/// Find the best move for the side to move, `None` if the game is over
+pub fn best_move(position: &Position, depth: u32) -> Option<Move> {
+    match Board::from_position(position) {
+        Some(board) => search(&board, depth),
+        None => search(position, depth),
+    }
+}
+
+fn search<P: Searchable>(position: &P, depth: u32) -> Option<Move> {
+    let depth = depth.max(1);
+    let mut best = None;
+    let mut alpha = -MATE_SCORE - 1;
+    for mv in ordered_moves(position) {
+        let score = -negamax(&position.play(&mv), depth - 1, -MATE_SCORE - 1, -alpha, 1);
+        if best.is_none() || score > alpha {
+            alpha = score;
+            best = Some(mv);
+        }
+    }
+    best
+}
@@ -0,0 +1,447 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    board::{square_translation, LEVEL_HEIGHT},
+    game::{Game, Taken},
+    pieces::{Piece, PieceColor, PieceType},
+    rules::BoardShape,
+};
+
+pub struct AnimationPlugin;
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MoveAnimation>()
+            .init_resource::<CaptureAnimation>()
+            .init_resource::<CaptureTray>()
+            .init_resource::<SparkAssets>()
+            .add_system(start_piece_tweens.label(AnimationSystem::StartTweens))
+            .add_system(tween_pieces.after(AnimationSystem::StartTweens))
+            .add_system(start_capture_tweens.label(AnimationSystem::StartCaptures))
+            .add_system(animate_captures.after(AnimationSystem::StartCaptures))
+            .add_system(animate_sparks);
+    }
+}
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+enum AnimationSystem {
+    StartTweens,
+    StartCaptures,
+}
+
+/// Easing curves, mapping the linear progress `t` in `0..=1` to the eased progress
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Easing {
+    Linear,
+    QuadInOut,
+    CubicInOut,
+    SineInOut,
+    BackOut,
+}
+
+impl Easing {
+    pub fn apply(self, t: f32) -> f32 {
+        use std::f32::consts::PI;
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::QuadInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(2) / 2.0
+                }
+            }
+            Easing::CubicInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            Easing::SineInOut => -((PI * t).cos() - 1.0) / 2.0,
+            Easing::BackOut => {
+                // Overshoots the target slightly before settling
+                const C1: f32 = 1.70158;
+                const C3: f32 = C1 + 1.0;
+                1.0 + C3 * (t - 1.0).powi(3) + C1 * (t - 1.0).powi(2)
+            }
+        }
+    }
+}
+
+/// How pieces travel between squares
+pub struct MoveAnimation {
+    /// Seconds a move takes, no matter how far the piece goes
+    pub duration: f32,
+    pub easing: Easing,
+    /// How high knights jump over the pieces in their way
+    pub knight_hop_height: f32,
+}
+
+impl Default for MoveAnimation {
+    fn default() -> Self {
+        Self {
+            duration: 0.4,
+            easing: Easing::CubicInOut,
+            knight_hop_height: 0.8,
+        }
+    }
+}
+
+/// Moves an entity from `start` to `end` in a fixed time, removed once it arrives
+#[derive(Component)]
+pub struct Tween {
+    start: Vec3,
+    end: Vec3,
+    hop_height: f32,
+    elapsed: f32,
+    duration: f32,
+    easing: Easing,
+}
+
+impl Tween {
+    pub fn new(start: Vec3, end: Vec3, duration: f32, easing: Easing) -> Self {
+        Self {
+            start,
+            end,
+            hop_height: 0.0,
+            elapsed: 0.0,
+            duration,
+            easing,
+        }
+    }
+
+    /// Arc over the straight line, peaking halfway at `height`
+    pub fn with_hop(mut self, height: f32) -> Self {
+        self.hop_height = height;
+        self
+    }
+
+    fn progress(&self) -> f32 {
+        if self.duration <= 0.0 {
+            1.0
+        } else {
+            self.easing.apply(self.elapsed / self.duration)
+        }
+    }
+
+    fn translation(&self) -> Vec3 {
+        let progress = self.progress();
+        let mut translation = self.start.lerp(self.end, progress);
+        translation.y += self.hop_height * 4.0 * progress * (1.0 - progress);
+        translation
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+/// Start a tween for every piece that was moved to a new square
+fn start_piece_tweens(
+    mut commands: Commands,
+    animation: Res<MoveAnimation>,
+    game: Res<Game>,
+    query: Query<(Entity, &Piece, &Transform, Option<&Tween>), Changed<Piece>>,
+) {
+    let board = game.position.variant.board();
+    for (entity, piece, transform, tween) in query.iter() {
+        let target = square_translation(board, piece.square());
+        let already_there = match tween {
+            Some(tween) => tween.end == target,
+            None => transform.translation == target,
+        };
+        if already_there {
+            continue;
+        }
+
+        let mut tween = Tween::new(
+            transform.translation,
+            target,
+            animation.duration,
+            animation.easing,
+        );
+        if piece.piece_type == PieceType::Knight {
+            tween = tween.with_hop(animation.knight_hop_height);
+        }
+        commands.entity(entity).insert(tween);
+    }
+}
+
+fn tween_pieces(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Transform, &mut Tween)>,
+) {
+    for (entity, mut transform, mut tween) in query.iter_mut() {
+        tween.elapsed += time.delta_seconds();
+        if tween.is_finished() {
+            // Snap exactly onto the target
+            transform.translation = tween.end;
+            commands.entity(entity).remove::<Tween>();
+        } else {
+            transform.translation = tween.translation();
+        }
+    }
+}
+
+/// What happens to a captured piece once the attacker reaches it
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum CaptureEffect {
+    /// Fall over onto the board
+    Topple,
+    /// Sink into the board
+    Sink,
+    /// Fly off to the tray beside the board
+    FlyToTray,
+    /// Swell and burst, for pieces blown up in atomic chess whatever the setting is
+    Explode,
+}
+
+pub struct CaptureAnimation {
+    pub effect: CaptureEffect,
+    pub duration: f32,
+}
+
+impl Default for CaptureAnimation {
+    fn default() -> Self {
+        Self {
+            effect: CaptureEffect::Topple,
+            duration: 0.6,
+        }
+    }
+}
+
+/// Number of pieces of each color sent to the tray so far
+#[derive(Default)]
+struct CaptureTray {
+    counts: [u8; 4],
+}
+
+impl CaptureTray {
+    /// Next free spot in the tray, light pieces line up along the a-file side and dark ones
+    /// along the last file, blue pieces below the first rank and green ones above the last
+    fn next_slot(&mut self, color: PieceColor, board: BoardShape) -> Vec3 {
+        let ranks = board.ranks as f32;
+        let files = board.files as f32;
+        let (start, step) = match color {
+            PieceColor::Light => (Vec3::new(ranks - 1.0, 0.0, -1.5), Vec3::new(-0.5, 0.0, 0.0)),
+            PieceColor::Dark => (Vec3::new(0.0, 0.0, files + 0.5), Vec3::new(0.5, 0.0, 0.0)),
+            PieceColor::Blue => (Vec3::new(-1.5, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.5)),
+            PieceColor::Green => (
+                Vec3::new(ranks + 0.5, 0.0, files - 1.0),
+                Vec3::new(0.0, 0.0, -0.5),
+            ),
+        };
+        let count = &mut self.counts[color.index()];
+        let slot = start + step * *count as f32;
+        *count += 1;
+        slot
+    }
+}
+
+/// Plays the capture effect on a taken piece, the piece is despawned once it is finished
+#[derive(Component)]
+pub struct CaptureTween {
+    effect: CaptureEffect,
+    color: PieceColor,
+    start: Transform,
+    tray_slot: Vec3,
+    elapsed: f32,
+    duration: f32,
+}
+
+impl CaptureTween {
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    fn transform(&self) -> Transform {
+        let mut transform = self.start;
+        let t = if self.duration <= 0.0 {
+            1.0
+        } else {
+            self.elapsed / self.duration
+        };
+        match self.effect {
+            CaptureEffect::Topple => {
+                let progress = Easing::QuadInOut.apply(t);
+                // Fall towards the own side of the board
+                let (dx, dz) = self.color.forward();
+                let (dx, dz) = (dx as f32, dz as f32);
+                let angle = std::f32::consts::FRAC_PI_2 * progress;
+                transform.rotation *=
+                    Quat::from_rotation_z(dx * angle) * Quat::from_rotation_x(-dz * angle);
+                transform.translation.x -= dx * 0.4 * progress;
+                transform.translation.z -= dz * 0.4 * progress;
+            }
+            CaptureEffect::Sink => {
+                transform.translation.y -= 1.2 * Easing::SineInOut.apply(t);
+            }
+            CaptureEffect::FlyToTray => {
+                let progress = Easing::CubicInOut.apply(t);
+                transform.translation = self.start.translation.lerp(self.tray_slot, progress);
+                transform.translation.y += 2.0 * 4.0 * progress * (1.0 - progress);
+            }
+            CaptureEffect::Explode => {
+                // Swell for the first quarter, then shrink away to nothing
+                let scale = if t < 0.25 {
+                    1.0 + 0.4 * Easing::QuadInOut.apply(t * 4.0)
+                } else {
+                    1.4 * (1.0 - Easing::QuadInOut.apply((t - 0.25) / 0.75))
+                };
+                transform.scale = self.start.scale * scale;
+                transform.translation.y += 0.3 * t;
+            }
+        }
+        transform
+    }
+}
+
+/// Start the capture effect as soon as the attacking piece has reached the square it captures on
+fn start_capture_tweens(
+    mut commands: Commands,
+    capture_animation: Res<CaptureAnimation>,
+    mut tray: ResMut<CaptureTray>,
+    sparks: Res<SparkAssets>,
+    game: Res<Game>,
+    taken: Query<(Entity, &Piece, &Transform, &Taken), Without<CaptureTween>>,
+    attackers: Query<(&Transform, Option<&Tween>)>,
+) {
+    let board = game.position.variant.board();
+    for (entity, piece, transform, taken) in taken.iter() {
+        let square = square_translation(board, taken.square);
+        let arrived = attackers
+            .get(taken.attacker)
+            .map(|(attacker, tween)| {
+                let offset = attacker.translation - square;
+                // A capturing piece that blows up has to finish its move first
+                let moving = entity == taken.attacker && tween.is_some();
+                // Hopping knights are above the square, the height only has to match the level
+                Vec2::new(offset.x, offset.z).length() < 0.5
+                    && offset.y.abs() < LEVEL_HEIGHT / 2.0
+                    && !moving
+            })
+            // The attacker is gone, nothing to wait for
+            .unwrap_or(true);
+        if !arrived {
+            continue;
+        }
+
+        let effect = if taken.exploded {
+            CaptureEffect::Explode
+        } else {
+            capture_animation.effect
+        };
+        // One burst of sparks per explosion, the capturing piece is always part of it
+        if taken.exploded && entity == taken.attacker {
+            sparks.spawn(&mut commands, square);
+        }
+        let tray_slot = if effect == CaptureEffect::FlyToTray {
+            tray.next_slot(piece.color, board)
+        } else {
+            transform.translation
+        };
+        commands.entity(entity).insert(CaptureTween {
+            effect,
+            color: piece.color,
+            start: *transform,
+            tray_slot,
+            elapsed: 0.0,
+            duration: capture_animation.duration,
+        });
+    }
+}
+
+fn animate_captures(time: Res<Time>, mut query: Query<(&mut Transform, &mut CaptureTween)>) {
+    for (mut transform, mut capture) in query.iter_mut() {
+        if capture.is_finished() {
+            continue;
+        }
+        capture.elapsed = (capture.elapsed + time.delta_seconds()).min(capture.duration);
+        *transform = capture.transform();
+    }
+}
+
+/// How many sparks fly off an explosion
+const SPARK_COUNT: usize = 24;
+const SPARK_DURATION: f32 = 0.8;
+const GRAVITY: f32 = 9.8;
+
+/// Mesh and material shared by all sparks
+struct SparkAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for SparkAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mesh = world
+            .get_resource_mut::<Assets<Mesh>>()
+            .unwrap()
+            .add(Mesh::from(shape::Icosphere {
+                radius: 0.05,
+                subdivisions: 1,
+            }));
+        let material = world
+            .get_resource_mut::<Assets<StandardMaterial>>()
+            .unwrap()
+            .add(StandardMaterial {
+                base_color: Color::rgb(1.0, 0.6, 0.1),
+                emissive: Color::rgb(1.0, 0.4, 0.0),
+                unlit: true,
+                ..Default::default()
+            });
+        Self { mesh, material }
+    }
+}
+
+impl SparkAssets {
+    /// Throw sparks in all directions from `center`
+    fn spawn(&self, commands: &mut Commands, center: Vec3) {
+        for index in 0..SPARK_COUNT {
+            // Spread the directions evenly with the golden angle, all pointing upwards
+            let angle = index as f32 * 2.4;
+            let rise = 0.3 + 0.7 * (index as f32 + 0.5) / SPARK_COUNT as f32;
+            let spread = (1.0 - rise * rise).sqrt();
+            let direction = Vec3::new(angle.cos() * spread, rise, angle.sin() * spread);
+            commands
+                .spawn_bundle(PbrBundle {
+                    mesh: self.mesh.clone(),
+                    material: self.material.clone(),
+                    transform: Transform::from_translation(center + Vec3::Y * 0.3),
+                    ..Default::default()
+                })
+                .insert(Spark {
+                    velocity: direction * (2.5 + (index % 3) as f32),
+                    elapsed: 0.0,
+                });
+        }
+    }
+}
+
+/// A glowing bit flying off an explosion, despawned when it burns out
+#[derive(Component)]
+struct Spark {
+    velocity: Vec3,
+    elapsed: f32,
+}
+
+fn animate_sparks(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Transform, &mut Spark)>,
+) {
+    let delta = time.delta_seconds();
+    for (entity, mut transform, mut spark) in query.iter_mut() {
+        spark.elapsed += delta;
+        if spark.elapsed >= SPARK_DURATION {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        spark.velocity.y -= GRAVITY * delta;
+        transform.translation += spark.velocity * delta;
+        transform.scale = Vec3::splat(1.0 - spark.elapsed / SPARK_DURATION);
+    }
+}
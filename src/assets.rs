@@ -0,0 +1,19 @@
+use std::path::PathBuf;
+
+use bevy::{asset::FileAssetIo, prelude::*};
+use serde::de::DeserializeOwned;
+
+/// Path of a file in the assets folder
+pub fn asset_path(path: &str) -> PathBuf {
+    FileAssetIo::get_root_path().join("assets").join(path)
+}
+
+/// Read a RON file from the assets folder, warns and returns `None` if it is missing or invalid
+pub fn load_ron<T: DeserializeOwned>(path: &str) -> Option<T> {
+    let path = asset_path(path);
+    std::fs::read_to_string(&path)
+        .map_err(|err| err.to_string())
+        .and_then(|ron| ron::from_str(&ron).map_err(|err| err.to_string()))
+        .map_err(|err| warn!("Could not load {}: {}", path.display(), err))
+        .ok()
+}
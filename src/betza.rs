@@ -0,0 +1,171 @@
+//! Piece movement written in Betza notation, like `N` for the knight or `BN` for the archbishop.
+//!
+//! A piece is a list of atoms, each a leap with all its symmetric directions:
+//! `W` `F` `D` `N` `A` `H` `C` `Z` `G` leap one or more squares, `K` `Q` `R` `B` are shorthands
+//! for the usual pieces. A doubled atom like `NN` is a rider that repeats its leap, a number like
+//! `W3` repeats it up to that many times. Lowercase prefixes limit an atom: `m` only moves, `c`
+//! only captures, and `f` `b` `l` `r` `s` `v` keep the forward, backward, left, right, sideways or
+//! vertical directions. Several direction prefixes keep every direction one of them allows.
+
+use std::collections::HashMap;
+
+use crate::pieces::PieceType;
+
+/// One leap of a piece and how it may be repeated
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Atom {
+    /// The leap as `(forward, sideways)`, in one of its directions
+    pub leap: (i8, i8),
+    /// How many times in a row the leap can be made, 0 for as far as the board goes
+    pub range: u8,
+    pub moves: bool,
+    pub captures: bool,
+    /// Directions that are kept, 0 for all of them
+    directions: u8,
+}
+
+const FORWARD: u8 = 1;
+const BACKWARD: u8 = 2;
+const LEFT: u8 = 4;
+const RIGHT: u8 = 8;
+
+impl Atom {
+    /// Every direction of the leap the atom allows, as `(forward, sideways)` seen from the
+    /// piece's own side of the board
+    pub fn steps(&self) -> Vec<(i8, i8)> {
+        let (a, b) = self.leap;
+        let mut steps = Vec::with_capacity(8);
+        for (forward, sideways) in [(a, b), (b, a)] {
+            for forward in [forward, -forward] {
+                for sideways in [sideways, -sideways] {
+                    if !steps.contains(&(forward, sideways)) && self.allows((forward, sideways)) {
+                        steps.push((forward, sideways));
+                    }
+                }
+            }
+        }
+        steps
+    }
+
+    fn allows(&self, (forward, sideways): (i8, i8)) -> bool {
+        self.directions == 0
+            || self.directions & FORWARD != 0 && forward > 0
+            || self.directions & BACKWARD != 0 && forward < 0
+            || self.directions & LEFT != 0 && sideways < 0
+            || self.directions & RIGHT != 0 && sideways > 0
+    }
+}
+
+/// Leaps of the single letter atoms
+fn leap(letter: char) -> Option<(i8, i8)> {
+    Some(match letter {
+        'W' => (1, 0),
+        'F' => (1, 1),
+        'D' => (2, 0),
+        'N' => (2, 1),
+        'A' => (2, 2),
+        'H' => (3, 0),
+        'C' => (3, 1),
+        'Z' => (3, 2),
+        'G' => (3, 3),
+        _ => return None,
+    })
+}
+
+/// Atoms the shorthand letters stand for, with the range they have when written alone
+fn shorthand(letter: char) -> Option<(&'static str, u8)> {
+    Some(match letter {
+        'K' => ("WF", 1),
+        'Q' => ("WF", 0),
+        'R' => ("W", 0),
+        'B' => ("F", 0),
+        _ => return None,
+    })
+}
+
+/// Parse a piece written in Betza notation into its atoms
+pub fn parse(betza: &str) -> Result<Vec<Atom>, String> {
+    let mut atoms = Vec::new();
+    let mut chars = betza.chars().peekable();
+    while chars.peek().is_some() {
+        let (mut moves, mut captures, mut directions) = (false, false, 0);
+        while let Some(&c) = chars.peek().filter(|c| c.is_ascii_lowercase()) {
+            match c {
+                'm' => moves = true,
+                'c' => captures = true,
+                'f' => directions |= FORWARD,
+                'b' => directions |= BACKWARD,
+                'l' => directions |= LEFT,
+                'r' => directions |= RIGHT,
+                's' => directions |= LEFT | RIGHT,
+                'v' => directions |= FORWARD | BACKWARD,
+                _ => return Err(format!("unknown modifier '{}' in {}", c, betza)),
+            }
+            chars.next();
+        }
+        if !moves && !captures {
+            moves = true;
+            captures = true;
+        }
+
+        let letter = chars
+            .next()
+            .ok_or_else(|| format!("modifiers without a piece at the end of {}", betza))?;
+        let (leaps, mut range): (Vec<(i8, i8)>, u8) = match (leap(letter), shorthand(letter)) {
+            (Some(step), _) => (vec![step], 1),
+            (_, Some((letters, range))) => (letters.chars().filter_map(leap).collect(), range),
+            _ => return Err(format!("unknown atom '{}' in {}", letter, betza)),
+        };
+        // A doubled leap rides
+        if chars.peek() == Some(&letter) && leap(letter).is_some() {
+            chars.next();
+            range = 0;
+        }
+        let mut digits = String::new();
+        while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+            digits.push(c);
+            chars.next();
+        }
+        if !digits.is_empty() {
+            range = digits
+                .parse()
+                .map_err(|_| format!("invalid range {} in {}", digits, betza))?;
+        }
+
+        for leap in leaps {
+            atoms.push(Atom {
+                leap,
+                range,
+                moves,
+                captures,
+                directions,
+            });
+        }
+    }
+    Ok(atoms)
+}
+
+/// The atoms of every piece type of a variant, parsed once
+#[derive(Debug)]
+pub struct Movement {
+    atoms: HashMap<PieceType, Vec<Atom>>,
+}
+
+impl Movement {
+    /// Parse the Betza notation `betza` gives each piece type, the ones it gives none move their
+    /// own way
+    pub fn new(betza: impl Fn(PieceType) -> Option<&'static str>) -> Result<Self, String> {
+        let mut atoms = HashMap::new();
+        for piece_type in PieceType::ALL {
+            if let Some(betza) = betza(piece_type) {
+                let parsed = parse(betza).map_err(|err| format!("{:?}: {}", piece_type, err))?;
+                atoms.insert(piece_type, parsed);
+            }
+        }
+        Ok(Movement { atoms })
+    }
+
+    pub fn atoms(&self, piece_type: PieceType) -> &[Atom] {
+        self.atoms.get(&piece_type).map_or(&[], Vec::as_slice)
+    }
+}
@@ -0,0 +1,30 @@
+use std::time::Duration;
+
+use clap::Parser;
+
+use chess::server::{self, ServerConfig};
+
+/// Lobby and referee for network games, clients join with `chess --server ADDRESS`
+#[derive(Parser, Debug)]
+#[clap(about = "Chess game server")]
+struct Args {
+    /// Address to listen on
+    #[clap(long, default_value = "0.0.0.0:7878")]
+    address: String,
+
+    /// Seconds a player who lost their connection has to come back before they lose
+    #[clap(long, default_value_t = 60)]
+    reconnect_seconds: u64,
+}
+
+fn main() {
+    let args = Args::parse();
+    let config = ServerConfig {
+        address: args.address,
+        reconnect_grace: Duration::from_secs(args.reconnect_seconds),
+    };
+    if let Err(err) = server::run(config) {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,670 @@
+//! Move generation on bitboards for chess on the usual board, fast enough for the engine to
+//! search millions of positions a second. Rooks, bishops and queens look up their attacks in
+//! tables indexed with magic numbers, or with the PEXT instruction when the game is built for a
+//! processor that has it, like with `RUSTFLAGS="-C target-cpu=native"`.
+
+use crate::{
+    pieces::{Piece, PieceColor, PieceType},
+    rules::{CastlingRights, CastlingSide, GameResult, Move, Position, PROMOTIONS},
+};
+
+/// One bit per square, a1 is bit 0, h1 bit 7 and h8 bit 63
+pub type Bitboard = u64;
+
+/// Piece types in the order of `Board::pieces`
+const PIECE_TYPES: [PieceType; 6] = [
+    PieceType::Pawn,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+    PieceType::King,
+];
+
+const PAWN: usize = 0;
+const KNIGHT: usize = 1;
+const BISHOP: usize = 2;
+const ROOK: usize = 3;
+const QUEEN: usize = 4;
+const KING: usize = 5;
+
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const KNIGHT_LEAPS: [(i8, i8); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+const KING_STEPS: [(i8, i8); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+/// Multipliers that send every set of blockers of a rook to its own slot in the attack table,
+/// found by trying random sparse numbers until none of them collide
+#[rustfmt::skip]
+const ROOK_MAGICS: [u64; 64] = [
+    0x0980_0080_1140_0020, 0x8340_0044_1000_2000, 0x0880_2000_9000_8268, 0x0080_0800_8010_0004,
+    0x8100_1100_0402_0800, 0x0300_0100_0400_0822, 0x0880_1a00_2900_0080, 0x8100_0500_0120_4882,
+    0x0844_8000_8140_0320, 0x0804_4020_1000_4000, 0x0108_8020_0310_0480, 0x0004_8080_0800_1000,
+    0x0003_0018_0100_1014, 0x0002_0002_0004_1008, 0x0004_0081_0804_2210, 0x0105_0001_0000_9042,
+    0x0400_8080_0040_0021, 0xc100_4040_1000_2000, 0x0060_0080_1000_2088, 0x0400_8080_0800_1000,
+    0x4440_8080_0800_0400, 0x1002_0080_0400_0280, 0x4002_4400_300d_1248, 0x0010_0200_0040_8104,
+    0x0101_0082_0020_4200, 0x8020_0020_4000_5000, 0x4100_1000_8080_2000, 0x4008_006a_8010_0280,
+    0x1020_0800_8004_0080, 0x0004_0100_4002_0040, 0x0018_a124_0008_0290, 0x6140_0042_0000_8104,
+    0x4000_4000_2080_0090, 0x2020_0020_8080_4000, 0x0000_4082_0200_2010, 0x0080_1005_0100_0820,
+    0x0000_8004_0080_0800, 0x000a_2004_0801_4010, 0x0100_8002_0080_0100, 0xa008_0057_0200_008c,
+    0x0080_0040_6000_c010, 0x1040_1000_2800_2000, 0x0048_2001_0011_0040, 0x0068_4902_1003_0020,
+    0x1009_0800_0501_0010, 0x2142_0008_0401_0100, 0x1001_0801_1084_0002, 0x1801_0044_0082_0001,
+    0x0104_4020_8d02_0200, 0x0000_4000_2000_8080, 0x0200_2000_8010_0280, 0x0000_1000_2009_0100,
+    0x0204_0080_0802_0480, 0x8104_0100_4002_0040, 0x7800_0201_b008_0400, 0x0040_8000_5100_2880,
+    0x0050_1080_0100_2041, 0x208a_8011_0061_4003, 0x0006_0020_4208_9082, 0x0011_0900_0420_1001,
+    0x1002_0010_0420_0802, 0x0005_0002_0804_0001, 0x0002_0027_01ac_0822, 0x0000_1025_0184_004a,
+];
+
+/// The same for bishops
+#[rustfmt::skip]
+const BISHOP_MAGICS: [u64; 64] = [
+    0xc0a0_0122_0604_0ea0, 0x8010_2282_0042_0001, 0x0110_0082_2040_0400, 0x0244_5c00_8010_6000,
+    0x0044_0420_0400_8100, 0x0880_9004_2040_8c05, 0x0201_0801_1008_0002, 0x0000_1080_9420_2000,
+    0x0000_0420_0204_0108, 0x0000_6230_2411_0042, 0x0086_1000_9481_1002, 0x0000_0445_0200_2080,
+    0x0100_4602_1140_0040, 0x0008_1090_0420_0004, 0x0202_3200_8484_4000, 0x8040_0424_2104_1009,
+    0x2010_10c0_5102_008c, 0x1020_8882_0802_4080, 0x0108_000c_8029_0200, 0x8048_0004_2042_5203,
+    0x0005_0000_9040_2000, 0x2080_4002_0110_4100, 0x8820_4201_1110_1000, 0x4ac0_3022_0882_1802,
+    0x0004_4000_1002_a840, 0x2002_2000_1004_1080, 0x1012_0802_0100_4400, 0x8440_0400_0241_0120,
+    0x1090_8200_8401_0400, 0x2084_8520_1202_1000, 0x1204_0062_c101_1003, 0x0200_8205_e109_0080,
+    0x088c_1028_0804_2080, 0x0802_1022_0090_4280, 0x8020_2090_0208_0020, 0x2200_0808_0006_0a00,
+    0x20c0_0040_1001_0100, 0x0802_0041_0082_1003, 0x0008_0244_0000_8080, 0x0000_8401_0200_8090,
+    0x0030_a404_2024_4007, 0x0a19_0842_1001_1282, 0x0004_0820_9001_9806, 0x6108_0042_0802_0080,
+    0x0081_2004_1011_0100, 0x1040_8107_0101_0208, 0x0282_0478_3201_2080, 0x0010_0200_9900_0020,
+    0x000e_0104_2240_0840, 0x1020_4208_b008_9090, 0x0810_0444_0c04_8000, 0x88c0_1800_8404_0001,
+    0x3100_0208_0304_0080, 0x8900_70a0_4121_0c00, 0x0020_2001_0101_0a09, 0x0004_1002_4041_0400,
+    0x0006_0044_0208_0200, 0x0801_0624_8404_2000, 0x0001_0002_d744_1004, 0x0810_0800_0020_8800,
+    0x0000_0208_0803_0411, 0x1450_0010_2001_4440, 0x0040_6008_1081_a288, 0x0044_0114_0410_8a00,
+];
+
+/// Where the attacks of a rook or bishop on one square start in `Tables::sliding`, and how the
+/// pieces in the way are turned into an index from there
+struct Magic {
+    /// Squares where a piece can block the slider, the edge of the board never does
+    mask: Bitboard,
+    #[cfg_attr(all(target_arch = "x86_64", target_feature = "bmi2"), allow(dead_code))]
+    magic: u64,
+    #[cfg_attr(all(target_arch = "x86_64", target_feature = "bmi2"), allow(dead_code))]
+    shift: u32,
+    offset: usize,
+}
+
+impl Magic {
+    #[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
+    fn index(&self, occupied: Bitboard) -> usize {
+        // The instruction is there, the build targets processors that have it
+        let blockers = unsafe { std::arch::x86_64::_pext_u64(occupied, self.mask) };
+        self.offset + blockers as usize
+    }
+
+    #[cfg(not(all(target_arch = "x86_64", target_feature = "bmi2")))]
+    fn index(&self, occupied: Bitboard) -> usize {
+        let blockers = (occupied & self.mask).wrapping_mul(self.magic) >> self.shift;
+        self.offset + blockers as usize
+    }
+}
+
+fn bit(square: usize) -> Bitboard {
+    1 << square
+}
+
+fn square_index((x, y, _): (u8, u8, u8)) -> usize {
+    x as usize * 8 + y as usize
+}
+
+fn coordinates(square: usize) -> (u8, u8, u8) {
+    ((square / 8) as u8, (square % 8) as u8, 0)
+}
+
+/// Squares of a bitboard, lowest first
+fn squares(mut bitboard: Bitboard) -> impl Iterator<Item = usize> {
+    std::iter::from_fn(move || {
+        (bitboard != 0).then(|| {
+            let square = bitboard.trailing_zeros() as usize;
+            bitboard &= bitboard - 1;
+            square
+        })
+    })
+}
+
+/// The square a step of `(ranks, files)` away, if it is on the board
+fn offset(square: usize, (dx, dy): (i8, i8)) -> Option<usize> {
+    let x = (square / 8) as i8 + dx;
+    let y = (square % 8) as i8 + dy;
+    ((0..8).contains(&x) && (0..8).contains(&y)).then(|| x as usize * 8 + y as usize)
+}
+
+fn leaps(square: usize, steps: &[(i8, i8)]) -> Bitboard {
+    steps
+        .iter()
+        .filter_map(|&step| offset(square, step))
+        .fold(0, |bitboard, to| bitboard | bit(to))
+}
+
+/// Attacks of a slider found the slow way, up to and including the first occupied square in each
+/// direction
+fn slide(square: usize, occupied: Bitboard, directions: &[(i8, i8)]) -> Bitboard {
+    let mut attacks = 0;
+    for &step in directions {
+        let mut current = square;
+        while let Some(to) = offset(current, step) {
+            attacks |= bit(to);
+            if occupied & bit(to) != 0 {
+                break;
+            }
+            current = to;
+        }
+    }
+    attacks
+}
+
+/// Squares a piece on them could block a slider on, without the last square in each direction
+fn blocker_mask(square: usize, directions: &[(i8, i8)]) -> Bitboard {
+    let mut mask = 0;
+    for &step in directions {
+        let mut current = square;
+        while let Some(to) = offset(current, step) {
+            if offset(to, step).is_none() {
+                break;
+            }
+            mask |= bit(to);
+            current = to;
+        }
+    }
+    mask
+}
+
+/// Attacks of every piece from every square
+struct Tables {
+    knight: [Bitboard; 64],
+    king: [Bitboard; 64],
+    /// Squares a white and a black pawn attack
+    pawn: [[Bitboard; 64]; 2],
+    rook: Vec<Magic>,
+    bishop: Vec<Magic>,
+    /// Attacks of rooks and bishops for every set of pieces in their way
+    sliding: Vec<Bitboard>,
+}
+
+impl Tables {
+    fn new() -> Self {
+        let mut sliding = Vec::new();
+        let mut magics = |directions: &[(i8, i8)], numbers: &[u64; 64]| -> Vec<Magic> {
+            (0..64)
+                .map(|square| {
+                    let mask = blocker_mask(square, directions);
+                    let magic = Magic {
+                        mask,
+                        magic: numbers[square],
+                        shift: 64 - mask.count_ones(),
+                        offset: sliding.len(),
+                    };
+                    sliding.resize(sliding.len() + (1 << mask.count_ones()), 0);
+                    // Go through every subset of the mask
+                    let mut occupied: Bitboard = 0;
+                    loop {
+                        sliding[magic.index(occupied)] = slide(square, occupied, directions);
+                        occupied = occupied.wrapping_sub(mask) & mask;
+                        if occupied == 0 {
+                            break;
+                        }
+                    }
+                    magic
+                })
+                .collect()
+        };
+        let rook = magics(&ROOK_DIRECTIONS, &ROOK_MAGICS);
+        let bishop = magics(&BISHOP_DIRECTIONS, &BISHOP_MAGICS);
+
+        let mut tables = Tables {
+            knight: [0; 64],
+            king: [0; 64],
+            pawn: [[0; 64]; 2],
+            rook,
+            bishop,
+            sliding,
+        };
+        for square in 0..64 {
+            tables.knight[square] = leaps(square, &KNIGHT_LEAPS);
+            tables.king[square] = leaps(square, &KING_STEPS);
+            tables.pawn[0][square] = leaps(square, &[(1, -1), (1, 1)]);
+            tables.pawn[1][square] = leaps(square, &[(-1, -1), (-1, 1)]);
+        }
+        tables
+    }
+
+    fn rook_attacks(&self, square: usize, occupied: Bitboard) -> Bitboard {
+        self.sliding[self.rook[square].index(occupied)]
+    }
+
+    fn bishop_attacks(&self, square: usize, occupied: Bitboard) -> Bitboard {
+        self.sliding[self.bishop[square].index(occupied)]
+    }
+}
+
+thread_local! {
+    /// Built the first time a thread generates moves
+    static TABLES: Tables = Tables::new();
+}
+
+/// A position of standard chess as bitboards, for the rules and the engine to work on
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Board {
+    /// Squares of each piece type, in the order of `PIECE_TYPES`
+    pieces: [Bitboard; 6],
+    /// Squares of the white and of the black pieces
+    colors: [Bitboard; 2],
+    pub turn: PieceColor,
+    castling: CastlingRights,
+    en_passant: Option<usize>,
+    pub halfmove_clock: u32,
+}
+
+impl Board {
+    /// The position as bitboards, `None` if its variant has rules of its own or a piece the
+    /// usual board doesn't have
+    pub fn from_position(position: &Position) -> Option<Board> {
+        if !position.variant.uses_bitboards() {
+            return None;
+        }
+        let mut board = Board {
+            pieces: [0; 6],
+            colors: [0; 2],
+            turn: position.turn,
+            castling: position.castling,
+            en_passant: position.en_passant.map(square_index),
+            halfmove_clock: position.halfmove_clock,
+        };
+        for piece in &position.pieces {
+            let kind = PIECE_TYPES.iter().position(|&t| t == piece.piece_type)?;
+            let color = match piece.color {
+                PieceColor::Light | PieceColor::Dark => piece.color.index(),
+                _ => return None,
+            };
+            let square = bit(square_index(piece.square()));
+            board.pieces[kind] |= square;
+            board.colors[color] |= square;
+        }
+        Some(board)
+    }
+
+    fn occupied(&self) -> Bitboard {
+        self.colors[0] | self.colors[1]
+    }
+
+    fn kind_at(&self, square: usize) -> Option<usize> {
+        self.pieces
+            .iter()
+            .position(|&pieces| pieces & bit(square) != 0)
+    }
+
+    pub fn piece_at(&self, square: (u8, u8, u8)) -> Option<Piece> {
+        let index = square_index(square);
+        let kind = self.kind_at(index)?;
+        let color = if self.colors[0] & bit(index) != 0 {
+            PieceColor::Light
+        } else {
+            PieceColor::Dark
+        };
+        Some(Piece {
+            color,
+            piece_type: PIECE_TYPES[kind],
+            x: square.0,
+            y: square.1,
+            z: square.2,
+        })
+    }
+
+    /// Every piece on the board
+    pub fn pieces(&self) -> impl Iterator<Item = Piece> + '_ {
+        squares(self.occupied()).filter_map(|square| self.piece_at(coordinates(square)))
+    }
+
+    /// Whether any piece of the side `by` attacks the square
+    fn is_attacked(&self, tables: &Tables, square: usize, by: usize) -> bool {
+        let theirs = self.colors[by];
+        let occupied = self.occupied();
+        let queens = self.pieces[QUEEN];
+        // A pawn of the other color on the square would attack the pawns that attack it
+        tables.pawn[1 - by][square] & self.pieces[PAWN] & theirs != 0
+            || tables.knight[square] & self.pieces[KNIGHT] & theirs != 0
+            || tables.king[square] & self.pieces[KING] & theirs != 0
+            || tables.bishop_attacks(square, occupied) & (self.pieces[BISHOP] | queens) & theirs
+                != 0
+            || tables.rook_attacks(square, occupied) & (self.pieces[ROOK] | queens) & theirs != 0
+    }
+
+    fn is_king_attacked(&self, tables: &Tables, color: usize) -> bool {
+        match squares(self.pieces[KING] & self.colors[color]).next() {
+            Some(king) => self.is_attacked(tables, king, 1 - color),
+            None => false,
+        }
+    }
+
+    pub fn is_in_check(&self) -> bool {
+        TABLES.with(|tables| self.is_king_attacked(tables, self.turn.index()))
+    }
+
+    /// Moves that follow the movement rules, castling included, without checking whether they
+    /// leave the king in check
+    fn pseudo_legal_moves(&self, tables: &Tables, moves: &mut Vec<Move>) {
+        let us = self.turn.index();
+        let ours = self.colors[us];
+        let theirs = self.colors[1 - us];
+        let occupied = self.occupied();
+        let mut push = |from: usize, targets: Bitboard| {
+            for to in squares(targets) {
+                moves.push(Move::new(coordinates(from), coordinates(to)));
+            }
+        };
+
+        for from in squares(self.pieces[KNIGHT] & ours) {
+            push(from, tables.knight[from] & !ours);
+        }
+        for from in squares((self.pieces[BISHOP] | self.pieces[QUEEN]) & ours) {
+            push(from, tables.bishop_attacks(from, occupied) & !ours);
+        }
+        for from in squares((self.pieces[ROOK] | self.pieces[QUEEN]) & ours) {
+            push(from, tables.rook_attacks(from, occupied) & !ours);
+        }
+        for from in squares(self.pieces[KING] & ours) {
+            push(from, tables.king[from] & !ours);
+        }
+
+        let en_passant = self.en_passant.map_or(0, bit);
+        let (forward, start_rank, last_rank) = if us == 0 { (8, 1, 7) } else { (-8, 6, 0) };
+        for from in squares(self.pieces[PAWN] & ours) {
+            let mut targets = tables.pawn[us][from] & (theirs | en_passant);
+            // `Position` turns away pawns on their last rank, there is always a square ahead
+            let ahead = (from as isize + forward) as usize;
+            if occupied & bit(ahead) == 0 {
+                targets |= bit(ahead);
+                let two = (ahead as isize + forward) as usize;
+                if from / 8 == start_rank && occupied & bit(two) == 0 {
+                    targets |= bit(two);
+                }
+            }
+            for to in squares(targets) {
+                let mv = Move::new(coordinates(from), coordinates(to));
+                if to / 8 == last_rank {
+                    for promotion in PROMOTIONS {
+                        moves.push(Move {
+                            promotion: Some(promotion),
+                            ..mv
+                        });
+                    }
+                } else {
+                    moves.push(mv);
+                }
+            }
+        }
+
+        self.push_castling_moves(tables, moves);
+    }
+
+    /// Castling as the king moving onto its rook, by the same rules as `Position`
+    fn push_castling_moves(&self, tables: &Tables, moves: &mut Vec<Move>) {
+        let color = self.turn;
+        let us = color.index();
+        let rank = if us == 0 { 0 } else { 7 };
+        let king = match squares(self.pieces[KING] & self.colors[us]).next() {
+            Some(king) if king / 8 == rank => king,
+            _ => return,
+        };
+        let sides = [CastlingSide::Queen, CastlingSide::King];
+        if sides
+            .iter()
+            .all(|&side| self.castling.get(color, side).is_none())
+            || self.is_attacked(tables, king, 1 - us)
+        {
+            return;
+        }
+        let on_rank = |file: u8| rank * 8 + file as usize;
+        // Squares from one file to the other, both included
+        let span = |from: u8, to: u8| {
+            (from.min(to)..=from.max(to)).fold(0, |bitboard, file| bitboard | bit(on_rank(file)))
+        };
+        for side in sides {
+            let rook_file = match self.castling.get(color, side) {
+                Some(file) => file,
+                None => continue,
+            };
+            let rook = on_rank(rook_file);
+            if self.pieces[ROOK] & self.colors[us] & bit(rook) == 0 {
+                continue;
+            }
+            let king_file = (king % 8) as u8;
+            let (king_to, rook_to) = side.destination_files(8);
+            let passed = span(king_file, king_to) | span(rook_file, rook_to);
+            if passed & self.occupied() & !bit(king) & !bit(rook) != 0 {
+                continue;
+            }
+            if squares(span(king_file, king_to))
+                .any(|square| self.is_attacked(tables, square, 1 - us))
+            {
+                continue;
+            }
+            moves.push(Move::new(coordinates(king), coordinates(rook)));
+        }
+    }
+
+    fn legal_moves_with(&self, tables: &Tables) -> Vec<Move> {
+        let mut moves = Vec::with_capacity(64);
+        self.pseudo_legal_moves(tables, &mut moves);
+        let us = self.turn.index();
+        moves.retain(|mv| {
+            let mut after = *self;
+            after.make_move(mv);
+            !after.is_king_attacked(tables, us)
+        });
+        moves
+    }
+
+    pub fn legal_moves(&self) -> Vec<Move> {
+        TABLES.with(|tables| self.legal_moves_with(tables))
+    }
+
+    /// Play a move without checking it
+    pub fn make_move(&mut self, mv: &Move) {
+        let us = self.turn.index();
+        let them = 1 - us;
+        let from = square_index(mv.from);
+        let to = square_index(mv.to);
+        let kind = self.kind_at(from).expect("there is a piece to move");
+        let mut captured = None;
+
+        if kind == KING && self.pieces[ROOK] & self.colors[us] & bit(to) != 0 {
+            // Castling, the king might land where the rook was
+            let side = if to > from {
+                CastlingSide::King
+            } else {
+                CastlingSide::Queen
+            };
+            let (king_to, rook_to) = side.destination_files(8);
+            let rank = from / 8 * 8;
+            self.remove(us, KING, from);
+            self.remove(us, ROOK, to);
+            self.put(us, KING, rank + king_to as usize);
+            self.put(us, ROOK, rank + rook_to as usize);
+        } else {
+            let captured_square = if kind == PAWN && Some(to) == self.en_passant {
+                // The pawn taken en passant is one step behind the square it passed over
+                if us == 0 {
+                    to - 8
+                } else {
+                    to + 8
+                }
+            } else {
+                to
+            };
+            if self.colors[them] & bit(captured_square) != 0 {
+                let captured_kind = self.kind_at(captured_square).unwrap();
+                self.remove(them, captured_kind, captured_square);
+                captured = Some((captured_kind, captured_square));
+            }
+            self.remove(us, kind, from);
+            let promoted = match mv.promotion {
+                Some(promotion) => PIECE_TYPES
+                    .iter()
+                    .position(|&t| t == promotion)
+                    .unwrap_or(QUEEN),
+                None => kind,
+            };
+            self.put(us, promoted, to);
+        }
+
+        // Update castling rights
+        let color = self.turn;
+        let back_rank = |color: usize| if color == 0 { 0 } else { 7 };
+        if kind == KING {
+            self.castling.set(color, CastlingSide::Queen, None);
+            self.castling.set(color, CastlingSide::King, None);
+        } else if kind == ROOK && from / 8 == back_rank(us) {
+            self.castling.remove_rook(color, (from % 8) as u8);
+        }
+        if let Some((ROOK, square)) = captured {
+            if square / 8 == back_rank(them) {
+                self.castling
+                    .remove_rook(color.opponent(), (square % 8) as u8);
+            }
+        }
+
+        self.en_passant =
+            (kind == PAWN && (from / 8).abs_diff(to / 8) == 2).then(|| (from + to) / 2);
+        if kind == PAWN || captured.is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        self.turn = color.opponent();
+    }
+
+    fn put(&mut self, color: usize, kind: usize, square: usize) {
+        self.pieces[kind] |= bit(square);
+        self.colors[color] |= bit(square);
+    }
+
+    fn remove(&mut self, color: usize, kind: usize, square: usize) {
+        self.pieces[kind] &= !bit(square);
+        self.colors[color] &= !bit(square);
+    }
+
+    /// Checkmate, stalemate, the fifty-move rule and bare kings, like the standard rules
+    pub fn outcome(&self, legal_moves: &[Move]) -> Option<GameResult> {
+        if legal_moves.is_empty() {
+            return Some(if self.is_in_check() {
+                GameResult::win(self.turn.opponent(), "checkmate")
+            } else {
+                GameResult::draw("stalemate")
+            });
+        }
+        if self.halfmove_clock >= 100 {
+            return Some(GameResult::draw("the fifty-move rule"));
+        }
+        if self.occupied() == self.pieces[KING] {
+            return Some(GameResult::draw("insufficient material"));
+        }
+        None
+    }
+
+    /// Number of move sequences `depth` plies deep
+    pub fn perft(&self, depth: u32) -> u64 {
+        TABLES.with(|tables| self.perft_with(tables, depth))
+    }
+
+    fn perft_with(&self, tables: &Tables, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.legal_moves_with(tables);
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        moves
+            .iter()
+            .map(|mv| {
+                let mut after = *self;
+                after.make_move(mv);
+                after.perft_with(tables, depth - 1)
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check the number of move sequences of each depth, starting from one ply
+    fn assert_perft(fen: &str, expected: &[u64]) {
+        let position = Position::from_fen(fen).unwrap();
+        let board = Board::from_position(&position).unwrap();
+        for (depth, &nodes) in (1..).zip(expected) {
+            assert_eq!(board.perft(depth), nodes, "{} at depth {}", fen, depth);
+        }
+    }
+
+    #[test]
+    fn starting_position() {
+        assert_perft(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            &[20, 400, 8902, 197_281],
+        );
+    }
+
+    #[test]
+    fn kiwipete() {
+        assert_perft(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            &[48, 2039, 97_862],
+        );
+    }
+
+    #[test]
+    fn en_passant_and_promotions() {
+        // En passant captures that would uncover a check on the king
+        assert_perft(
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            &[14, 191, 2812, 43_238],
+        );
+        assert_perft(
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            &[6, 264, 9467],
+        );
+    }
+
+    #[test]
+    fn chess960_castling() {
+        assert_perft(
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            &[21, 528, 12_189],
+        );
+        assert_perft(
+            "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+            &[21, 807, 18_002],
+        );
+    }
+
+    #[test]
+    fn pawns_on_their_last_rank_are_rejected() {
+        assert!(Position::from_fen("P7/8/8/8/8/8/8/K6k w - - 0 1").is_err());
+        assert!(Position::from_fen("K6k/8/8/8/8/8/8/p7 b - - 0 1").is_err());
+    }
+}
@@ -1,19 +1,28 @@
 use bevy::{
-    app::{AppExit, Events},
     prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
 };
 use bevy_mod_picking::{Hover, PickableBundle, PickingEvent, Selection};
 
-use crate::pieces::{Piece, PieceColor, PieceType};
+use crate::{
+    animation::CaptureTween,
+    camera::OrbitCamera,
+    cursor::SquareCursor,
+    game::{Game, MoveRequest, PlayerTurn, Taken},
+    net::Spectating,
+    pieces::{Piece, PieceType},
+    players::Players,
+    rules::{BoardShape, Move},
+    theme::{Rgb, Theme, Themes},
+};
 
 pub struct BoardPlugin;
 impl Plugin for BoardPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<SelectedPiece>()
-            .init_resource::<PlayerTurn>()
             .init_resource::<SquareMaterials>()
             .add_event::<ResetSelectedEvent>()
-            .add_startup_system(create_board)
+            .add_system(create_board)
             .add_system(color_squares)
             .add_system(move_piece)
             .add_system(select_piece)
@@ -23,37 +32,36 @@ impl Plugin for BoardPlugin {
 }
 
 #[derive(Default)]
-struct SelectedPiece {
-    entity: Option<Entity>,
-}
-
-pub struct PlayerTurn(pub PieceColor);
-
-impl PlayerTurn {
-    fn change(&mut self) {
-        self.0 = self.0.opponent();
-    }
-}
-impl Default for PlayerTurn {
-    fn default() -> Self {
-        Self(PieceColor::Light)
-    }
+pub struct SelectedPiece {
+    pub entity: Option<Entity>,
+    /// Piece picked from the reserve to drop in crazyhouse
+    pub drop: Option<PieceType>,
 }
 
 fn color_squares(
     materials: ResMut<SquareMaterials>,
+    cursor: Res<SquareCursor>,
+    game: Res<Game>,
     mut query: Query<(&Square, &Selection, &Hover, &mut Handle<StandardMaterial>)>,
 ) {
+    let board = game.position.variant.board();
+    let goals = game.position.variant.goal_squares();
     for (square, selection, hover, mut material) in query.iter_mut() {
+        let is_goal = goals.contains(&square.square());
+        let shade = shade(board, square.square());
         // Change the material
         *material = if hover.hovered() {
             materials.highlight_color.clone()
         } else if selection.selected() {
             materials.selected_color.clone()
-        } else if square.is_light() {
-            materials.light_color.clone()
+        } else if cursor.is_on(square) {
+            materials.cursor_color.clone()
+        } else if is_goal && shade == Shade::Dark {
+            materials.dark_goal_color.clone()
+        } else if is_goal {
+            materials.light_goal_color.clone()
         } else {
-            materials.dark_color.clone()
+            materials.square_color(shade)
         };
     }
 }
@@ -62,9 +70,16 @@ fn select_piece(
     mut events: EventReader<PickingEvent>,
     mut selected_piece: ResMut<SelectedPiece>,
     turn: Res<PlayerTurn>,
+    game: Res<Game>,
+    players: Res<Players>,
+    spectating: Option<Res<Spectating>>,
     squares: Query<(&Square, &Selection)>,
-    pieces: Query<(Entity, &Piece)>,
+    pieces: Query<(Entity, &Piece), Without<Taken>>,
 ) {
+    // Only the human whose turn it is can pick up pieces
+    if game.is_over() || !players.is_human(turn.0) || spectating.is_some() {
+        return;
+    }
     if !events
         .iter()
         .any(|e| matches!(e, PickingEvent::Selection(_)))
@@ -83,9 +98,10 @@ fn select_piece(
     if selected_piece.entity.is_none() {
         // Select the piece in the currently selected square
         for (piece_entity, piece) in pieces.iter() {
-            if piece.x == square.x && piece.y == square.y && piece.color == turn.0 {
+            if piece.square() == square.square() && piece.color == turn.0 {
                 // piece_entity is now the entity in the same square
                 selected_piece.entity = Some(piece_entity);
+                selected_piece.drop = None;
                 break;
             }
         }
@@ -93,13 +109,17 @@ fn select_piece(
 }
 
 fn move_piece(
-    mut commands: Commands,
     selected_piece: Res<SelectedPiece>,
-    mut turn: ResMut<PlayerTurn>,
+    game: Res<Game>,
+    spectating: Option<Res<Spectating>>,
     squares: Query<(&Square, &Selection)>,
-    mut pieces: Query<(Entity, &mut Piece)>,
-    mut reset_selected_event: ResMut<Events<ResetSelectedEvent>>,
+    pieces: Query<&Piece, Without<Taken>>,
+    mut move_requests: EventWriter<MoveRequest>,
+    mut reset_selected_event: EventWriter<ResetSelectedEvent>,
 ) {
+    if spectating.is_some() {
+        return;
+    }
     let square = if let Some(square) = squares
         .iter()
         .find_map(|(square, selection)| selection.selected().then(|| square))
@@ -109,41 +129,29 @@ fn move_piece(
         return;
     };
 
-    if let Some(selected_piece_entity) = selected_piece.entity {
-        let pieces_vec = pieces.iter().map(|(_, piece)| *piece).collect();
-        // collect is needed here to satisfy the borrow checker
-        #[allow(clippy::needless_collect)]
-        let pieces_entity_vec = pieces
-            .iter()
-            .map(|(entity, piece)| (entity, *piece))
-            .collect::<Vec<_>>();
-        // Move the selected piece to the selected square
-        let mut piece = if let Ok((_piece_entity, piece)) = pieces.get_mut(selected_piece_entity) {
-            piece
-        } else {
-            return;
-        };
-
-        if piece.is_move_valid((square.x, square.y), pieces_vec) {
-            // Check if a piece of the opposite color exists in this square and mark it for
-            // despawning
-            if let Some((other_entity, _other_piece)) = pieces_entity_vec
-                .into_iter()
-                .find(|(_, target_piece)| target_piece.x == square.x && target_piece.y == square.y)
-            {
-                // Mark the piece as taken
-                commands.entity(other_entity).insert(Taken);
-            }
-
-            // Move piece
-            piece.x = square.x;
-            piece.y = square.y;
-
-            // Change turn
-            turn.change();
-
+    // Drop the piece picked from the reserve onto the selected square
+    if let (None, Some(piece_type)) = (selected_piece.entity, selected_piece.drop) {
+        let mv = Move::drop(piece_type, square.square());
+        if game.position.is_legal(&mv) {
+            move_requests.send(MoveRequest(mv));
             reset_selected_event.send(ResetSelectedEvent);
         }
+        return;
+    }
+
+    let piece = if let Some(piece) = selected_piece
+        .entity
+        .and_then(|entity| pieces.get(entity).ok())
+    {
+        piece
+    } else {
+        return;
+    };
+
+    // Move the selected piece to the selected square, if the rules allow it
+    if let Some(mv) = game.position.move_to(piece.square(), square.square()) {
+        move_requests.send(MoveRequest(mv));
+        reset_selected_event.send(ResetSelectedEvent);
     }
 }
 
@@ -154,93 +162,284 @@ fn reset_selected(
 ) {
     for _event in event_reader.iter() {
         selected_piece.entity = None;
+        selected_piece.drop = None;
         for mut selection in square_selections.iter_mut() {
             selection.set_selected(false);
         }
     }
 }
 
+/// Remove taken pieces once their capture effect has played
 fn despawn_taken_pieces(
     mut commands: Commands,
-    mut app_exit_events: ResMut<Events<AppExit>>,
-    query: Query<(Entity, &Piece), With<Taken>>,
+    query: Query<(Entity, &CaptureTween), With<Taken>>,
 ) {
-    for (entity, piece) in query.iter() {
-        // TODO: detect check and mate
-        // If the king is taken, we should exit
-
-        if piece.piece_type == PieceType::King {
-            println!("{} won! Thanks for playing!", piece.color.opponent());
-            app_exit_events.send(AppExit);
+    for (entity, capture) in query.iter() {
+        if capture.is_finished() {
+            commands.entity(entity).despawn_recursive();
         }
+    }
+}
 
-        commands.entity(entity).despawn_recursive();
+/// Height between the levels of stacked boards
+pub const LEVEL_HEIGHT: f32 = 2.0;
+
+/// Distance between neighbouring files of the hexagonal board, `√3 / 2` cells
+const HEX_FILE_WIDTH: f32 = 0.866_025_4;
+
+/// Where the center of a square is, levels of stacked boards are `LEVEL_HEIGHT` apart
+pub fn square_translation(board: BoardShape, square @ (x, y, z): (u8, u8, u8)) -> Vec3 {
+    if board.hexagonal {
+        // Each file towards the middle starts half a cell lower
+        let (file, up) = board.hex_point(square);
+        return Vec3::new(
+            up as f32 - file as f32 / 2.0,
+            0.0,
+            y as f32 * HEX_FILE_WIDTH,
+        );
     }
+    Vec3::new(x as f32, z as f32 * LEVEL_HEIGHT, y as f32)
 }
 
+/// Spawn the squares of the variant's board, again whenever a game on another board is loaded
 fn create_board(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
-    materials: ResMut<SquareMaterials>,
+    mut square_materials: ResMut<SquareMaterials>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    themes: Res<Themes>,
+    game: Res<Game>,
+    mut built: Local<Option<BoardShape>>,
+    squares: Query<Entity, With<Square>>,
+    mut cameras: Query<(&mut Transform, &mut OrbitCamera)>,
 ) {
-    // Add meshes and materials
-    let mesh = meshes.add(Mesh::from(shape::Plane { size: 1.0 }));
-
-    // Spawn 64 squares {
-    for x in 0..8 {
-        for y in 0..8 {
-            let square = Square { x, y };
-            commands
-                .spawn_bundle(PbrBundle {
-                    mesh: mesh.clone(),
-                    material: if square.is_light() {
-                        materials.light_color.clone()
-                    } else {
-                        materials.dark_color.clone()
-                    },
-                    transform: Transform::from_translation(Vec3::new(x as f32, 0.0, y as f32)),
-                    ..Default::default()
-                })
-                .insert_bundle(PickableBundle::default())
-                .insert(square);
+    let board = game.position.variant.board();
+    if *built == Some(board) {
+        return;
+    }
+    // Look at the middle of the new board, the camera keeps its angle and distance
+    if built.is_some() || board != BoardShape::STANDARD {
+        let translations = board
+            .squares()
+            .map(|square| square_translation(board, square));
+        let (min, max) = translations.fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), translation| (min.min(translation), max.max(translation)),
+        );
+        let center = (min + max) / 2.0;
+        for (mut transform, mut orbit) in cameras.iter_mut() {
+            let offset = transform.translation - orbit.focus;
+            orbit.focus = center;
+            transform.translation = center + offset;
+            transform.look_at(center, Vec3::Y);
         }
     }
+    *built = Some(board);
+
+    for entity in squares.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    // The levels of stacked boards are see-through, so the pieces below stay visible
+    square_materials.translucent = board.levels > 1;
+    square_materials.apply_theme(themes.active(), &mut materials);
+
+    // Add meshes and materials
+    let mesh = if board.hexagonal {
+        meshes.add(hexagon_mesh())
+    } else {
+        meshes.add(Mesh::from(shape::Plane { size: 1.0 }))
+    };
+
+    // Squares in the cut off corners of the four-player board are left out
+    for square @ (x, y, z) in board.squares() {
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: mesh.clone(),
+                material: square_materials.square_color(shade(board, square)),
+                transform: Transform::from_translation(square_translation(board, square)),
+                ..Default::default()
+            })
+            .insert_bundle(PickableBundle::default())
+            .insert(Square { x, y, z });
+    }
+}
+
+/// A flat hexagonal cell, with edges towards the cells above and below it in its file
+fn hexagon_mesh() -> Mesh {
+    // The corners are `1 / √3` from the center, so the edges are half a cell away
+    let radius = 1.0 / 3f32.sqrt();
+    let mut positions = vec![[0.0, 0.0, 0.0]];
+    for corner in 0..6 {
+        let angle = std::f32::consts::FRAC_PI_6 + corner as f32 * std::f32::consts::FRAC_PI_3;
+        positions.push([radius * angle.cos(), 0.0, radius * angle.sin()]);
+    }
+    let normals = vec![[0.0, 1.0, 0.0]; positions.len()];
+    let uvs: Vec<[f32; 2]> = positions
+        .iter()
+        .map(|&[x, _, z]| [x + 0.5, z + 0.5])
+        .collect();
+    // A fan around the center, counterclockwise seen from above
+    let indices = (1..=6u32)
+        .flat_map(|corner| [0, corner % 6 + 1, corner])
+        .collect();
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
 }
 
 #[derive(Component)]
-struct Square {
+pub struct Square {
     pub x: u8,
     pub y: u8,
+    /// Level of stacked boards, always 0 on a flat board
+    pub z: u8,
 }
 
 impl Square {
-    pub fn is_light(&self) -> bool {
-        (self.x + self.y) % 2 == 1
+    pub fn square(&self) -> (u8, u8, u8) {
+        (self.x, self.y, self.z)
     }
 }
 
-struct ResetSelectedEvent;
-#[derive(Component)]
-struct Taken;
+/// Color of a square, the hexagonal board has cells in three
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Shade {
+    Light,
+    Middle,
+    Dark,
+}
 
-struct SquareMaterials {
+fn shade(board: BoardShape, square: (u8, u8, u8)) -> Shade {
+    if !board.hexagonal {
+        // The colors alternate between levels too, like the cells of a cube
+        let (x, y, z) = square;
+        return if (x + y + z) % 2 == 1 {
+            Shade::Light
+        } else {
+            Shade::Dark
+        };
+    }
+    // Cells that share an edge never share a shade, and bishops keep to theirs
+    let (file, up) = board.hex_point(square);
+    match (file + up).rem_euclid(3) {
+        0 => Shade::Dark,
+        1 => Shade::Light,
+        _ => Shade::Middle,
+    }
+}
+
+pub struct ResetSelectedEvent;
+
+pub struct SquareMaterials {
     highlight_color: Handle<StandardMaterial>,
     selected_color: Handle<StandardMaterial>,
+    cursor_color: Handle<StandardMaterial>,
     dark_color: Handle<StandardMaterial>,
     light_color: Handle<StandardMaterial>,
+    /// The third color of the hexagonal board
+    middle_color: Handle<StandardMaterial>,
+    /// Squares a king wins the game on in some variants
+    dark_goal_color: Handle<StandardMaterial>,
+    light_goal_color: Handle<StandardMaterial>,
+    /// Let the squares show what is below them, on stacked boards
+    translucent: bool,
+}
+
+/// Opacity of the squares of stacked boards
+const TRANSLUCENT_ALPHA: f32 = 0.45;
+
+/// Goal squares keep a bit of their own color under this tint
+const GOAL_TINT: Rgb = Rgb(0.9, 0.65, 0.1);
+
+/// Halfway between the light and dark squares of the theme
+fn middle_square(theme: &Theme) -> Rgb {
+    let Rgb(light_red, light_green, light_blue) = theme.light_square;
+    let Rgb(dark_red, dark_green, dark_blue) = theme.dark_square;
+    Rgb(
+        (light_red + dark_red) / 2.0,
+        (light_green + dark_green) / 2.0,
+        (light_blue + dark_blue) / 2.0,
+    )
+}
+
+fn goal_color(square: Rgb) -> Color {
+    let Rgb(red, green, blue) = square;
+    let Rgb(tint_red, tint_green, tint_blue) = GOAL_TINT;
+    Color::rgb(
+        (red + tint_red * 2.0) / 3.0,
+        (green + tint_green * 2.0) / 3.0,
+        (blue + tint_blue * 2.0) / 3.0,
+    )
+}
+
+impl SquareMaterials {
+    /// Recolor the square materials in place, so every square picks up the new theme
+    pub fn apply_theme(&self, theme: &Theme, materials: &mut Assets<StandardMaterial>) {
+        for (handle, color) in [
+            (&self.highlight_color, theme.highlight),
+            (&self.selected_color, theme.selected),
+            (&self.cursor_color, theme.cursor),
+            (&self.dark_color, theme.dark_square),
+            (&self.light_color, theme.light_square),
+            (&self.middle_color, middle_square(theme)),
+        ] {
+            if let Some(material) = materials.get_mut(handle) {
+                *material = self.material(Color::from(color));
+            }
+        }
+        for (handle, square) in [
+            (&self.dark_goal_color, theme.dark_square),
+            (&self.light_goal_color, theme.light_square),
+        ] {
+            if let Some(material) = materials.get_mut(handle) {
+                *material = self.material(goal_color(square));
+            }
+        }
+    }
+
+    fn square_color(&self, shade: Shade) -> Handle<StandardMaterial> {
+        match shade {
+            Shade::Light => self.light_color.clone(),
+            Shade::Middle => self.middle_color.clone(),
+            Shade::Dark => self.dark_color.clone(),
+        }
+    }
+
+    fn material(&self, color: Color) -> StandardMaterial {
+        if self.translucent {
+            StandardMaterial {
+                base_color: *color.clone().set_a(TRANSLUCENT_ALPHA),
+                alpha_mode: AlphaMode::Blend,
+                ..color.into()
+            }
+        } else {
+            color.into()
+        }
+    }
 }
 
 impl FromWorld for SquareMaterials {
     fn from_world(world: &mut World) -> Self {
-
-        let mut materials = world.get_resource_mut::<Assets<StandardMaterial>>().unwrap();
-            //resoucrses.get_mut::<Assets<StandardMaterial>>().unwrap();
+        let theme = world
+            .get_resource_or_insert_with(Themes::load)
+            .active()
+            .clone();
+        let mut materials = world
+            .get_resource_mut::<Assets<StandardMaterial>>()
+            .unwrap();
         SquareMaterials {
-            highlight_color: materials.add(Color::rgb(0.8, 0.3, 0.3).into()),
-            selected_color: materials.add(Color::rgb(0.9, 0.1, 0.1).into()),
-            dark_color: materials.add(Color::rgb(0.0, 0.1, 0.1).into()),
-            light_color: materials.add(Color::rgb(1.0, 0.9, 0.9).into()),
+            highlight_color: materials.add(Color::from(theme.highlight).into()),
+            selected_color: materials.add(Color::from(theme.selected).into()),
+            cursor_color: materials.add(Color::from(theme.cursor).into()),
+            dark_color: materials.add(Color::from(theme.dark_square).into()),
+            light_color: materials.add(Color::from(theme.light_square).into()),
+            middle_color: materials.add(Color::from(middle_square(&theme)).into()),
+            dark_goal_color: materials.add(goal_color(theme.dark_square).into()),
+            light_goal_color: materials.add(goal_color(theme.light_square).into()),
+            translucent: false,
         }
     }
 }
-
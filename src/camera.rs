@@ -0,0 +1,71 @@
+use bevy::{
+    input::mouse::{MouseMotion, MouseWheel},
+    prelude::*,
+};
+
+/// Look at the board from anywhere: drag with the right mouse button to orbit, with the middle
+/// button to pan and scroll to zoom
+pub struct CameraPlugin;
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(orbit_camera);
+    }
+}
+
+/// Radians per pixel of mouse movement
+const ORBIT_SPEED: f32 = 0.005;
+/// Share of the distance to the focus moved per pixel
+const PAN_SPEED: f32 = 0.002;
+/// Share of the distance to the focus zoomed per scroll line
+const ZOOM_SPEED: f32 = 0.1;
+const MIN_DISTANCE: f32 = 4.0;
+const MAX_DISTANCE: f32 = 60.0;
+
+/// A camera that turns around `focus`
+#[derive(Component)]
+pub struct OrbitCamera {
+    pub focus: Vec3,
+}
+
+fn orbit_camera(
+    buttons: Res<Input<MouseButton>>,
+    mut motion: EventReader<MouseMotion>,
+    mut scroll: EventReader<MouseWheel>,
+    mut query: Query<(&mut Transform, &mut OrbitCamera)>,
+) {
+    let delta = motion
+        .iter()
+        .fold(Vec2::ZERO, |delta, event| delta + event.delta);
+    let zoom: f32 = scroll.iter().map(|event| event.y).sum();
+    if delta == Vec2::ZERO && zoom == 0.0 {
+        return;
+    }
+
+    for (mut transform, mut orbit) in query.iter_mut() {
+        let mut offset = transform.translation - orbit.focus;
+        if buttons.pressed(MouseButton::Right) && delta != Vec2::ZERO {
+            let yaw = Quat::from_rotation_y(-delta.x * ORBIT_SPEED);
+            let pitch = Quat::from_axis_angle(transform.local_x(), -delta.y * ORBIT_SPEED);
+            // Stay above the board and don't flip over the top
+            let pitched = pitch * offset;
+            let height = pitched.normalize().y;
+            if height > 0.05 && height < 0.99 {
+                offset = pitched;
+            }
+            offset = yaw * offset;
+        } else if buttons.pressed(MouseButton::Middle) && delta != Vec2::ZERO {
+            let right = transform.local_x();
+            let forward = Vec3::new(-offset.x, 0.0, -offset.z).normalize_or_zero();
+            let distance = offset.length();
+            orbit.focus += (forward * delta.y - right * delta.x) * PAN_SPEED * distance;
+        }
+        if zoom != 0.0 {
+            let distance = offset.length();
+            let zoomed = (distance * (1.0 - zoom * ZOOM_SPEED)).clamp(MIN_DISTANCE, MAX_DISTANCE);
+            offset = offset.normalize() * zoomed;
+        }
+
+        transform.translation = orbit.focus + offset;
+        transform.look_at(orbit.focus, Vec3::Y);
+    }
+}
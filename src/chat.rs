@@ -0,0 +1,319 @@
+use std::marker::PhantomData;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    game::{Game, GameSystem},
+    menu::{spawn_sized_button, text_style},
+    net::{Connection, Spectating},
+    offers::{Action, ChatLog, GameAction, Offer, PendingOffer},
+    pieces::PieceColor,
+    players::Players,
+};
+
+/// Panel with the chat log, a line to type in and buttons to resign or make offers. Without a
+/// network game the chat works as a log for notes.
+pub struct ChatPlugin;
+impl Plugin for ChatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChatInput>()
+            .add_startup_system(init_chat_panel)
+            .add_system(
+                type_message
+                    .label(ChatSystem::Typing)
+                    .before(GameSystem::ApplyMoves),
+            )
+            .add_system(press_buttons.before(GameSystem::ApplyMoves))
+            .add_system(chat_log_text)
+            .add_system(chat_input_text)
+            .add_system(show_offer_prompt);
+    }
+}
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ChatSystem {
+    Typing,
+}
+
+/// How many lines of the log are shown
+const VISIBLE_LINES: usize = 8;
+
+/// The message being typed, typing starts with T and ends with Enter or Escape
+#[derive(Default)]
+pub struct ChatInput {
+    pub typing: bool,
+    text: String,
+}
+
+// Component to mark the text showing the chat log
+#[derive(Component)]
+struct ChatLogText;
+
+// Component to mark the text showing the message being typed
+#[derive(Component)]
+struct ChatInputText;
+
+// Component to mark the node asking to accept or decline an offer
+#[derive(Component)]
+struct OfferPrompt;
+
+// Component to mark the text of the offer prompt
+#[derive(Component)]
+struct OfferPromptText;
+
+#[derive(Component, Clone, Copy)]
+enum ChatButton {
+    Resign,
+    OfferDraw,
+    Takeback,
+    Accept,
+    Decline,
+}
+
+impl ChatButton {
+    fn action(self) -> Action {
+        match self {
+            ChatButton::Resign => Action::Resign,
+            ChatButton::OfferDraw => Action::Offer(Offer::Draw),
+            ChatButton::Takeback => Action::Offer(Offer::Takeback),
+            ChatButton::Accept => Action::Accept,
+            ChatButton::Decline => Action::Decline,
+        }
+    }
+}
+
+fn init_chat_panel(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    spectating: Option<Res<Spectating>>,
+) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let text = |value: &str| TextBundle {
+        style: Style {
+            margin: Rect::all(Val::Px(4.0)),
+            ..Default::default()
+        },
+        text: Text::with_section(value, text_style(font.clone()), Default::default()),
+        ..Default::default()
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(10.0),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                padding: Rect::all(Val::Px(10.0)),
+                ..Default::default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(text("")).insert(ChatLogText);
+            // Spectators only read along
+            if spectating.is_some() {
+                return;
+            }
+            parent
+                .spawn_bundle(text("Press T to chat"))
+                .insert(ChatInputText);
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        display: Display::None,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    color: Color::NONE.into(),
+                    ..Default::default()
+                })
+                .insert(OfferPrompt)
+                .with_children(|parent| {
+                    parent.spawn_bundle(text("")).insert(OfferPromptText);
+                    spawn_sized_button(parent, font.clone(), "Accept", ChatButton::Accept, 100.0);
+                    spawn_sized_button(parent, font.clone(), "Decline", ChatButton::Decline, 100.0);
+                });
+            parent
+                .spawn_bundle(NodeBundle {
+                    color: Color::NONE.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    for (label, button) in [
+                        ("Resign", ChatButton::Resign),
+                        ("Offer draw", ChatButton::OfferDraw),
+                        ("Takeback", ChatButton::Takeback),
+                    ] {
+                        spawn_sized_button(parent, font.clone(), label, button, 140.0);
+                    }
+                });
+        });
+}
+
+/// Who the player at this machine acts for
+#[derive(SystemParam)]
+struct LocalPlayer<'w, 's> {
+    game: Res<'w, Game>,
+    players: Res<'w, Players>,
+    pending: Res<'w, PendingOffer>,
+    connection: Option<Res<'w, Connection>>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+impl LocalPlayer<'_, '_> {
+    fn is_spectating(&self) -> bool {
+        self.connection
+            .as_ref()
+            .map_or(false, |connection| connection.spectating)
+    }
+
+    /// The side doing `action`, `None` for spectators and engine games
+    fn color(&self, action: &Action) -> Option<PieceColor> {
+        if let Some(connection) = &self.connection {
+            return (!connection.spectating).then(|| connection.local_color);
+        }
+        let turn = self.game.position.turn;
+        let humans: Vec<PieceColor> = self
+            .game
+            .position
+            .variant
+            .colors()
+            .iter()
+            .copied()
+            .filter(|&color| self.players.is_human(color))
+            .collect();
+        match humans[..] {
+            [] => None,
+            [color] => Some(color),
+            // Hot-seat, guess who is at the board. Only games of two sides have offers, so the
+            // one answering is the opponent.
+            _ => Some(match action {
+                Action::Offer(Offer::Takeback) => turn.opponent(),
+                Action::Accept | Action::Decline => {
+                    self.pending.0.map_or(turn, |(from, _)| from.opponent())
+                }
+                _ => turn,
+            }),
+        }
+    }
+}
+
+fn press_buttons(
+    local: LocalPlayer,
+    buttons: Query<(&Interaction, &ChatButton), Changed<Interaction>>,
+    mut actions: EventWriter<GameAction>,
+) {
+    for (interaction, button) in buttons.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        let action = button.action();
+        if let Some(color) = local.color(&action) {
+            actions.send(GameAction { color, action });
+        }
+    }
+}
+
+fn type_message(
+    keys: Res<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut input: ResMut<ChatInput>,
+    local: LocalPlayer,
+    mut actions: EventWriter<GameAction>,
+) {
+    if !input.typing {
+        if keys.just_pressed(KeyCode::T) && !local.is_spectating() {
+            input.typing = true;
+            // Don't type the T that opened the chat
+            characters.iter().for_each(drop);
+        }
+        return;
+    }
+    if keys.just_pressed(KeyCode::Escape) {
+        input.typing = false;
+        input.text.clear();
+        return;
+    }
+    if keys.just_pressed(KeyCode::Return) {
+        input.typing = false;
+        let text = std::mem::take(&mut input.text);
+        if text.trim().is_empty() {
+            return;
+        }
+        let action = Action::Chat(text.trim().to_string());
+        if let Some(color) = local.color(&action) {
+            actions.send(GameAction { color, action });
+        }
+        return;
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        input.text.pop();
+    }
+    for character in characters.iter() {
+        if !character.char.is_control() {
+            input.text.push(character.char);
+        }
+    }
+}
+
+fn chat_log_text(log: Res<ChatLog>, mut query: Query<&mut Text, With<ChatLogText>>) {
+    if !log.is_changed() {
+        return;
+    }
+    let start = log.0.len().saturating_sub(VISIBLE_LINES);
+    for mut text in query.iter_mut() {
+        text.sections[0].value = log.0[start..].join("\n");
+    }
+}
+
+fn chat_input_text(input: Res<ChatInput>, mut query: Query<&mut Text, With<ChatInputText>>) {
+    if !input.is_changed() {
+        return;
+    }
+    for mut text in query.iter_mut() {
+        text.sections[0].value = if input.typing {
+            format!("> {}_", input.text)
+        } else {
+            "Press T to chat".to_string()
+        };
+    }
+}
+
+/// Ask for an answer when the opponent made an offer
+fn show_offer_prompt(
+    pending: Res<PendingOffer>,
+    connection: Option<Res<Connection>>,
+    mut prompts: Query<&mut Style, With<OfferPrompt>>,
+    mut texts: Query<&mut Text, With<OfferPromptText>>,
+) {
+    if !pending.is_changed() {
+        return;
+    }
+    // In network games only the player receiving the offer answers it
+    let offer = pending.0.filter(|(from, _)| {
+        connection
+            .as_ref()
+            .map_or(true, |connection| *from != connection.local_color)
+    });
+    for mut style in prompts.iter_mut() {
+        style.display = if offer.is_some() {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+    if let Some((from, offer)) = offer {
+        for mut text in texts.iter_mut() {
+            text.sections[0].value = match offer {
+                Offer::Draw => format!("{} offers a draw", from),
+                Offer::Takeback => format!("{} asks for a takeback", from),
+            };
+        }
+    }
+}
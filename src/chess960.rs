@@ -0,0 +1,142 @@
+//! Chess960 (Fischer Random) starting positions, numbered 0 to 959 the way Scharnagl did, which
+//! makes 518 the classical setup
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+use crate::{pieces::PieceType, rules::Position};
+
+pub const POSITIONS: u16 = 960;
+
+/// Pairs of empty squares the knights go on, after the bishops and the queen were placed
+const KNIGHTS: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+
+/// The back rank of position `index`, from the a-file to the h-file
+pub fn back_rank(index: u16) -> Result<[PieceType; 8], String> {
+    if index >= POSITIONS {
+        return Err(format!(
+            "Chess960 positions are numbered 0 to {}, not {}",
+            POSITIONS - 1,
+            index
+        ));
+    }
+    let mut rank: [Option<PieceType>; 8] = [None; 8];
+    let mut n = index as usize;
+
+    // One bishop on a light square (b, d, f or h) and one on a dark square (a, c, e or g)
+    rank[n % 4 * 2 + 1] = Some(PieceType::Bishop);
+    n /= 4;
+    rank[n % 4 * 2] = Some(PieceType::Bishop);
+    n /= 4;
+
+    let empty = |rank: &[Option<PieceType>; 8]| -> Vec<usize> {
+        (0..8).filter(|&file| rank[file].is_none()).collect()
+    };
+    rank[empty(&rank)[n % 6]] = Some(PieceType::Queen);
+    n /= 6;
+
+    let (first, second) = KNIGHTS[n];
+    let files = empty(&rank);
+    rank[files[first]] = Some(PieceType::Knight);
+    rank[files[second]] = Some(PieceType::Knight);
+
+    // The king always ends up between the rooks
+    for (file, piece_type) in
+        empty(&rank)
+            .into_iter()
+            .zip([PieceType::Rook, PieceType::King, PieceType::Rook])
+    {
+        rank[file] = Some(piece_type);
+    }
+    Ok(rank.map(|piece_type| piece_type.unwrap()))
+}
+
+/// Any of the 960 positions, every `RandomState` is seeded differently
+pub fn random_index() -> u16 {
+    (RandomState::new().build_hasher().finish() % POSITIONS as u64) as u16
+}
+
+impl Position {
+    /// Chess960 starting position number `index`
+    pub fn chess960(index: u16) -> Result<Self, String> {
+        let mut position = Position::with_back_rank(back_rank(index)?);
+        position.chess960 = true;
+        Ok(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pieces::PieceColor,
+        rules::{CastlingSide, BACK_RANK},
+    };
+
+    #[test]
+    fn position_518_is_the_classical_setup() {
+        assert_eq!(back_rank(518), Ok(BACK_RANK));
+        assert_eq!(
+            Position::chess960(518).unwrap().to_fen(),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        );
+        assert!(back_rank(POSITIONS).is_err());
+    }
+
+    #[test]
+    fn shredder_fen_names_the_rook_files() {
+        for (index, fen) in [
+            (
+                0,
+                "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w HFhf - 0 1",
+            ),
+            (
+                959,
+                "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB w CAca - 0 1",
+            ),
+        ] {
+            assert_eq!(Position::chess960(index).unwrap().to_shredder_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn x_fen_and_shredder_fen_round_trip() {
+        for index in 0..POSITIONS {
+            let position = Position::chess960(index).unwrap();
+            for fen in [position.to_fen(), position.to_shredder_fen()] {
+                let read = Position::from_fen(&fen).unwrap();
+                assert_eq!(read.castling, position.castling, "{}", fen);
+                assert_eq!(read.to_fen(), position.to_fen(), "{}", fen);
+            }
+        }
+    }
+
+    #[test]
+    fn x_fen_names_a_rook_that_is_not_the_outermost() {
+        // The e-file rook can castle, the h-file rook behind it can't
+        let fen = "rk6/8/8/8/8/8/8/RK2R2R w E - 0 1";
+        let position = Position::from_fen(fen).unwrap();
+        assert_eq!(
+            position.castling.get(PieceColor::Light, CastlingSide::King),
+            Some(4)
+        );
+        assert_eq!(position.to_fen(), fen);
+        assert_eq!(
+            position.to_shredder_fen(),
+            "rk6/8/8/8/8/8/8/RK2R2R w E - 0 1"
+        );
+    }
+}
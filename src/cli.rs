@@ -0,0 +1,255 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use crate::{
+    ai::DEFAULT_DEPTH,
+    chess960,
+    clock::TimeControl,
+    game::Game,
+    headless::ScriptedMoves,
+    net::LobbyRequest,
+    notation::parse_pgn,
+    pieces::PieceColor,
+    players::{PlayerKind, Players},
+    rules::Position,
+    saves::SavedGame,
+    settings::{Settings, SettingsOverrides},
+    variants::{find_variant, Standard, Variant},
+};
+
+/// Command line options, they override the saved settings for this run only
+#[derive(Parser, Debug)]
+#[clap(about = "3D chess")]
+pub struct Args {
+    /// Start from a position in Forsyth-Edwards Notation
+    #[clap(long, conflicts_with = "pgn")]
+    pub fen: Option<String>,
+
+    /// Continue the game in a PGN file
+    #[clap(long, value_name = "PATH")]
+    pub pgn: Option<PathBuf>,
+
+    /// Play Chess960 from starting position INDEX (0 to 959), or a random one
+    #[clap(
+        long,
+        value_name = "INDEX",
+        min_values = 0,
+        max_values = 1,
+        conflicts_with_all = &["fen", "pgn", "load", "connect", "server"]
+    )]
+    pub chess960: Option<Option<u16>>,
+
+    /// Rules to play by, standard chess unless another variant is named
+    #[clap(
+        long,
+        value_name = "NAME",
+        conflicts_with_all = &["pgn", "load", "chess960", "connect", "server"]
+    )]
+    pub variant: Option<String>,
+
+    /// Resume a saved game, with its players and clocks
+    #[clap(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = &["fen", "pgn", "host", "connect", "server"]
+    )]
+    pub load: Option<PathBuf>,
+
+    /// Who plays white: human, ai or the path to a UCI engine
+    #[clap(long, default_value = "human", value_name = "PLAYER")]
+    pub white: PlayerKind,
+
+    /// Who plays black: human, ai or the path to a UCI engine
+    #[clap(long, default_value = "human", value_name = "PLAYER")]
+    pub black: PlayerKind,
+
+    /// Who plays blue in four-player chess: human, ai or the path to a UCI engine
+    #[clap(long, default_value = "human", value_name = "PLAYER")]
+    pub blue: PlayerKind,
+
+    /// Who plays green in four-player chess: human, ai or the path to a UCI engine
+    #[clap(long, default_value = "human", value_name = "PLAYER")]
+    pub green: PlayerKind,
+
+    /// Time control as minutes+increment in seconds, like 5+3
+    #[clap(long, value_name = "MINUTES+INCREMENT")]
+    pub time: Option<TimeControl>,
+
+    /// How many plies deep the built-in AI searches
+    #[clap(long, default_value_t = DEFAULT_DEPTH)]
+    pub depth: u32,
+
+    /// Count the move sequences this many plies deep from the starting position, then exit
+    #[clap(long, value_name = "DEPTH")]
+    pub perft: Option<u32>,
+
+    /// Name of the board and piece theme
+    #[clap(long)]
+    pub theme: Option<String>,
+
+    /// Start in fullscreen
+    #[clap(long, conflicts_with = "windowed")]
+    pub fullscreen: bool,
+
+    /// Start in a window
+    #[clap(long)]
+    pub windowed: bool,
+
+    /// Run without a window or GPU, the finished game is written as PGN
+    #[clap(long)]
+    pub headless: bool,
+
+    /// File with moves in SAN or UCI notation to play before the players take over
+    #[clap(long, value_name = "PATH", requires = "headless")]
+    pub script: Option<PathBuf>,
+
+    /// Where a headless game is written, it is printed when this is left out
+    #[clap(long, value_name = "PATH", requires = "headless")]
+    pub output: Option<PathBuf>,
+
+    /// Host a network game on ADDRESS, like 0.0.0.0 or 0.0.0.0:7878
+    #[clap(long, value_name = "ADDRESS", conflicts_with_all = &["connect", "headless"])]
+    pub host: Option<String>,
+
+    /// Color the host plays, white or black (default white)
+    #[clap(long, value_name = "COLOR", requires = "host")]
+    pub play_as: Option<PieceColor>,
+
+    /// Join a network game hosted at ADDRESS, the host picks the position and time control
+    #[clap(
+        long,
+        value_name = "ADDRESS",
+        conflicts_with_all = &["headless", "fen", "pgn", "time"]
+    )]
+    pub connect: Option<String>,
+
+    /// Play on the chess-server at ADDRESS, takes up an open seek or posts one
+    #[clap(
+        long,
+        value_name = "ADDRESS",
+        conflicts_with_all = &["host", "connect", "headless", "fen", "pgn"]
+    )]
+    pub server: Option<String>,
+
+    /// Post a seek on the server and wait for someone to take it
+    #[clap(long, requires = "server", conflicts_with_all = &["join", "resume"])]
+    pub seek: bool,
+
+    /// Take up the seek with this id on the server
+    #[clap(
+        long,
+        value_name = "ID",
+        requires = "server",
+        conflicts_with = "resume"
+    )]
+    pub join: Option<u32>,
+
+    /// Get back into a server game with the token printed when it started
+    #[clap(long, value_name = "TOKEN", requires = "server")]
+    pub resume: Option<String>,
+
+    /// Watch the server game with this id without playing
+    #[clap(
+        long,
+        value_name = "ID",
+        requires = "server",
+        conflicts_with_all = &["seek", "join", "resume"]
+    )]
+    pub watch: Option<u32>,
+}
+
+impl Args {
+    /// `--theme`, `--fullscreen` and `--windowed`, laid over the saved settings
+    pub fn settings_overrides(&self) -> SettingsOverrides {
+        let fullscreen = if self.fullscreen {
+            Some(true)
+        } else if self.windowed {
+            Some(false)
+        } else {
+            None
+        };
+        SettingsOverrides {
+            theme: self.theme.clone(),
+            fullscreen,
+        }
+    }
+
+    /// The game from `--load`
+    pub fn saved_game(&self) -> Result<Option<SavedGame>, String> {
+        self.load.as_deref().map(SavedGame::load).transpose()
+    }
+
+    /// The game to start with, from `--fen`, `--pgn`, `--chess960` or `--variant`
+    pub fn game(&self) -> Result<Game, String> {
+        let variant: &'static dyn Variant = match &self.variant {
+            Some(name) => find_variant(name).map_err(|err| format!("--variant: {}", err))?,
+            None => &Standard,
+        };
+        if let Some(index) = self.chess960 {
+            let index = index.unwrap_or_else(chess960::random_index);
+            let position =
+                Position::chess960(index).map_err(|err| format!("--chess960: {}", err))?;
+            println!("Chess960 position {}", index);
+            return Ok(Game::new(position));
+        }
+        if let Some(fen) = &self.fen {
+            let position = Position::from_variant_fen(variant, fen)
+                .map_err(|err| format!("--fen: {}", err))?;
+            return Ok(Game::new(position));
+        }
+        if let Some(path) = &self.pgn {
+            let text = std::fs::read_to_string(path)
+                .map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+            let pgn = parse_pgn(&text).map_err(|err| format!("{}: {}", path.display(), err))?;
+            return Ok(Game::from_moves(pgn.start, &pgn.moves));
+        }
+        Ok(Game::new(Position::starting(variant)))
+    }
+
+    /// Moves from `--script`, checked against the game they are played in
+    pub fn scripted_moves(&self, game: &Game) -> Result<ScriptedMoves, String> {
+        match &self.script {
+            Some(path) => {
+                let text = std::fs::read_to_string(path)
+                    .map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+                ScriptedMoves::parse(&text, game)
+                    .map_err(|err| format!("{}: {}", path.display(), err))
+            }
+            None => Ok(ScriptedMoves::default()),
+        }
+    }
+
+    pub fn players(&self) -> Players {
+        let with_depth = |kind: &PlayerKind| match kind {
+            PlayerKind::Ai { .. } => PlayerKind::Ai { depth: self.depth },
+            other => other.clone(),
+        };
+        Players::new(
+            with_depth(&self.white),
+            with_depth(&self.black),
+            with_depth(&self.blue),
+            with_depth(&self.green),
+        )
+    }
+
+    /// What to ask the server for, from `--seek`, `--join`, `--resume` and `--watch`
+    pub fn lobby_request(&self, time_control: Option<TimeControl>) -> LobbyRequest {
+        if let Some(id) = self.watch {
+            LobbyRequest::Watch(id)
+        } else if let Some(token) = &self.resume {
+            LobbyRequest::Resume(token.clone())
+        } else if let Some(id) = self.join {
+            LobbyRequest::Join(id)
+        } else if self.seek {
+            LobbyRequest::Seek(time_control)
+        } else {
+            LobbyRequest::Any(time_control)
+        }
+    }
+
+    /// Time control from `--time`, or the one in the settings
+    pub fn time_control(&self, settings: &Settings) -> Option<TimeControl> {
+        self.time.or_else(|| settings.time_control.get())
+    }
+}
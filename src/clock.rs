@@ -0,0 +1,129 @@
+use std::str::FromStr;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game::{Game, GameSystem, MoveMade},
+    pieces::PieceColor,
+};
+
+pub struct ClockPlugin;
+impl Plugin for ClockPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Clock>()
+            .add_system(
+                add_increment
+                    .label(ClockSystem::Increment)
+                    .after(GameSystem::ApplyMoves),
+            )
+            .add_system(tick_clock.after(GameSystem::ApplyMoves));
+    }
+}
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ClockSystem {
+    Increment,
+}
+
+/// Starting time and the increment added after every move, written like `5+3`
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TimeControl {
+    pub minutes: u32,
+    pub increment_seconds: u32,
+}
+
+impl FromStr for TimeControl {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (minutes, increment) = text.split_once('+').unwrap_or((text, "0"));
+        let invalid = || {
+            format!(
+                "invalid time control '{}', expected minutes+increment",
+                text
+            )
+        };
+        Ok(Self {
+            minutes: minutes.trim().parse().map_err(|_| invalid())?,
+            increment_seconds: increment.trim().parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl std::fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}+{}", self.minutes, self.increment_seconds)
+    }
+}
+
+/// Time left for every player, the clock of the side to move runs once the first move was played
+#[derive(Default)]
+pub struct Clock {
+    pub time_control: Option<TimeControl>,
+    /// Seconds left, by `PieceColor::index` so four-player games have a clock for each side
+    remaining: [f32; 4],
+}
+
+impl Clock {
+    pub fn new(time_control: Option<TimeControl>) -> Self {
+        let seconds = time_control.map_or(0.0, |time_control| time_control.minutes as f32 * 60.0);
+        Self {
+            time_control,
+            remaining: [seconds; 4],
+        }
+    }
+
+    /// Seconds left for `color`, `None` in untimed games
+    pub fn remaining(&self, color: PieceColor) -> Option<f32> {
+        self.time_control.map(|_| self.remaining[color.index()])
+    }
+
+    /// Take over the time reported by the other side of a network game
+    pub fn set_remaining(&mut self, color: PieceColor, seconds: f32) {
+        self.remaining[color.index()] = seconds;
+    }
+
+    pub fn increment(&self) -> f32 {
+        self.time_control
+            .map_or(0.0, |time_control| time_control.increment_seconds as f32)
+    }
+}
+
+/// Time left written as `m:ss`, with tenths in the last ten seconds
+pub fn format_time(seconds: f32) -> String {
+    let seconds = seconds.max(0.0);
+    if seconds < 10.0 {
+        format!("0:{:04.1}", seconds)
+    } else {
+        let whole = seconds.ceil() as u32;
+        format!("{}:{:02}", whole / 60, whole % 60)
+    }
+}
+
+fn add_increment(mut clock: ResMut<Clock>, mut moves: EventReader<MoveMade>) {
+    for MoveMade { color, .. } in moves.iter() {
+        let increment = clock.increment();
+        if clock.time_control.is_some() {
+            clock.remaining[color.index()] += increment;
+        }
+    }
+}
+
+/// Run the clock of the side to move, running out of time loses the game
+fn tick_clock(time: Res<Time>, mut clock: ResMut<Clock>, mut game: ResMut<Game>) {
+    if clock.time_control.is_none() || game.is_over() || game.moves.is_empty() {
+        return;
+    }
+    let turn = game.position.turn;
+    let remaining = &mut clock.remaining[turn.index()];
+    *remaining -= time.delta_seconds();
+    if *remaining <= 0.0 {
+        *remaining = 0.0;
+        let result = game
+            .position
+            .variant
+            .forfeit(&game.position, turn, "timeout");
+        game.end(result);
+    }
+}
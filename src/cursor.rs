@@ -0,0 +1,127 @@
+use bevy::prelude::*;
+use bevy_mod_picking::{PickingEvent, Selection, SelectionEvent};
+
+use crate::{
+    board::{ResetSelectedEvent, Square},
+    chat::{ChatInput, ChatSystem},
+    game::Game,
+};
+
+pub struct CursorPlugin;
+impl Plugin for CursorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SquareCursor>()
+            // Keys that end typing a chat message are not for the cursor
+            .add_system(move_cursor.before(ChatSystem::Typing))
+            .add_system(confirm_cursor.before(ChatSystem::Typing))
+            .add_system(cancel_selection.before(ChatSystem::Typing));
+    }
+}
+
+/// Square the keyboard cursor is on, only shown once the keyboard has been used
+pub struct SquareCursor {
+    pub x: u8,
+    pub y: u8,
+    pub z: u8,
+    pub visible: bool,
+}
+
+impl SquareCursor {
+    pub fn is_on(&self, square: &Square) -> bool {
+        self.visible && (self.x, self.y, self.z) == square.square()
+    }
+}
+
+impl Default for SquareCursor {
+    fn default() -> Self {
+        // Start on e2, in front of the white king
+        Self {
+            x: 1,
+            y: 4,
+            z: 0,
+            visible: false,
+        }
+    }
+}
+
+/// Move the cursor with the arrow keys, up being towards the dark side of the board. Page up and
+/// page down change the level on stacked boards.
+fn move_cursor(
+    keys: Res<Input<KeyCode>>,
+    chat: Res<ChatInput>,
+    game: Res<Game>,
+    mut cursor: ResMut<SquareCursor>,
+) {
+    // The keys are typing a chat message
+    if chat.typing {
+        return;
+    }
+    let (dx, dy, dz) = if keys.just_pressed(KeyCode::Up) {
+        (1, 0, 0)
+    } else if keys.just_pressed(KeyCode::Down) {
+        (-1, 0, 0)
+    } else if keys.just_pressed(KeyCode::Right) {
+        (0, 1, 0)
+    } else if keys.just_pressed(KeyCode::Left) {
+        (0, -1, 0)
+    } else if keys.just_pressed(KeyCode::PageUp) {
+        (0, 0, 1)
+    } else if keys.just_pressed(KeyCode::PageDown) {
+        (0, 0, -1)
+    } else {
+        return;
+    };
+
+    // The first key press only shows the cursor where it is
+    if cursor.visible {
+        let board = game.position.variant.board();
+        let step =
+            |value: u8, delta: i8, size: u8| (value as i8 + delta).clamp(0, size as i8 - 1) as u8;
+        let square = (
+            step(cursor.x, dx, board.ranks),
+            step(cursor.y, dy, board.files),
+            step(cursor.z, dz, board.levels),
+        );
+        // The cut off corners of the four-player board have no squares to stop on
+        if board.contains(square) {
+            (cursor.x, cursor.y, cursor.z) = square;
+        }
+    }
+    cursor.visible = true;
+}
+
+/// Select the square under the cursor, just like clicking it would
+fn confirm_cursor(
+    keys: Res<Input<KeyCode>>,
+    chat: Res<ChatInput>,
+    mut cursor: ResMut<SquareCursor>,
+    mut picking_events: EventWriter<PickingEvent>,
+    mut squares: Query<(Entity, &Square, &mut Selection)>,
+) {
+    if chat.typing || !(keys.just_pressed(KeyCode::Space) || keys.just_pressed(KeyCode::Return)) {
+        return;
+    }
+    cursor.visible = true;
+
+    for (entity, square, mut selection) in squares.iter_mut() {
+        let on_cursor = (cursor.x, cursor.y, cursor.z) == square.square();
+        if selection.selected() != on_cursor {
+            selection.set_selected(on_cursor);
+            picking_events.send(PickingEvent::Selection(if on_cursor {
+                SelectionEvent::JustSelected(entity)
+            } else {
+                SelectionEvent::JustDeselected(entity)
+            }));
+        }
+    }
+}
+
+fn cancel_selection(
+    keys: Res<Input<KeyCode>>,
+    chat: Res<ChatInput>,
+    mut reset_selected_event: EventWriter<ResetSelectedEvent>,
+) {
+    if !chat.typing && keys.just_pressed(KeyCode::Escape) {
+        reset_selected_event.send(ResetSelectedEvent);
+    }
+}
@@ -0,0 +1,324 @@
+use crate::{
+    pieces::{Piece, PieceColor, PieceType},
+    rules::{BoardShape, CastlingRights, CastlingSide, Position},
+    variants::{Standard, VariantState},
+};
+
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// Letter of a piece in FEN, uppercase for white and lowercase for black. The side armies of
+/// four-player chess use uppercase for blue and lowercase for green, in parentheses.
+pub fn piece_letter(piece_type: PieceType, color: PieceColor) -> char {
+    match color {
+        PieceColor::Light | PieceColor::Blue => piece_type.letter(),
+        PieceColor::Dark | PieceColor::Green => piece_type.letter().to_ascii_lowercase(),
+    }
+}
+
+/// The side to move as written in FEN, the side armies in parentheses like their pieces
+fn turn_text(color: PieceColor) -> &'static str {
+    match color {
+        PieceColor::Light => "w",
+        PieceColor::Dark => "b",
+        PieceColor::Blue => "(w)",
+        PieceColor::Green => "(b)",
+    }
+}
+
+impl Position {
+    /// Parse a standard chess position in Forsyth-Edwards Notation, the move counters are optional
+    pub fn from_fen(fen: &str) -> Result<Self, String> {
+        let position = Position::parse_fen(fen, BoardShape::STANDARD)?;
+        for color in [PieceColor::Light, PieceColor::Dark] {
+            position.check_king(color)?;
+        }
+        position.check_pawns()?;
+        Ok(position)
+    }
+
+    /// Parse the FEN fields every variant shares, without asking for a king of each color. The
+    /// ranks of stacked boards are written from the top level down.
+    pub(crate) fn parse_fen(fen: &str, board: BoardShape) -> Result<Self, String> {
+        let mut fields = fen.split_whitespace();
+        let placement = fields.next().ok_or("empty FEN")?;
+
+        let mut pieces = Vec::new();
+        let ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() != board.rows() as usize {
+            return Err(format!(
+                "expected {} ranks, found {}",
+                board.rows(),
+                ranks.len()
+            ));
+        }
+        for (index, rank) in ranks.iter().enumerate() {
+            let row = board.rows() - 1 - index as u8;
+            let squares = board.row_squares(row);
+            // Index into the squares of the row
+            let mut y: u8 = 0;
+            let mut chars = rank.chars().peekable();
+            while let Some(c) = chars.next() {
+                if let Some(mut empty) = c.to_digit(10) {
+                    // Wider boards have runs of ten or more empty squares
+                    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                        empty = empty * 10 + digit;
+                        chars.next();
+                    }
+                    y = y.saturating_add(empty as u8);
+                    continue;
+                }
+                // A side army piece, like `(N)`
+                let side_army = c == '(';
+                let c = if side_army {
+                    let letter = chars.next().ok_or("unfinished piece in parentheses")?;
+                    if chars.next() != Some(')') {
+                        return Err(format!("expected ')' after '({}'", letter));
+                    }
+                    letter
+                } else {
+                    c
+                };
+                let piece_type =
+                    PieceType::from_letter(c).ok_or_else(|| format!("unknown piece '{}'", c))?;
+                let color = match (side_army, c.is_ascii_uppercase()) {
+                    (false, true) => PieceColor::Light,
+                    (false, false) => PieceColor::Dark,
+                    (true, true) => PieceColor::Blue,
+                    (true, false) => PieceColor::Green,
+                };
+                let square = match squares.get(y as usize) {
+                    Some(&square) => square,
+                    None => return Err(format!("rank {} is too long", row + 1)),
+                };
+                if !board.contains(square) {
+                    return Err(format!("{} is not on the board", board.square_name(square)));
+                }
+                pieces.push(Piece {
+                    color,
+                    piece_type,
+                    x: square.0,
+                    y: square.1,
+                    z: square.2,
+                });
+                y += 1;
+            }
+            if y as usize != squares.len() {
+                return Err(format!(
+                    "rank {} does not have {} squares",
+                    row + 1,
+                    squares.len()
+                ));
+            }
+        }
+
+        let turn_field = fields.next().unwrap_or("w");
+        let turn = [
+            PieceColor::Light,
+            PieceColor::Dark,
+            PieceColor::Blue,
+            PieceColor::Green,
+        ]
+        .into_iter()
+        .find(|&color| turn_text(color) == turn_field)
+        .ok_or_else(|| format!("invalid side to move '{}'", turn_field))?;
+
+        // X-FEN writes K and Q for the outermost rooks and the file of any other rook, Shredder-FEN
+        // always writes files
+        let mut castling = CastlingRights::default();
+        let mut chess960 = false;
+        for c in fields.next().unwrap_or("-").chars() {
+            if c == '-' {
+                continue;
+            }
+            let color = if c.is_ascii_uppercase() {
+                PieceColor::Light
+            } else {
+                PieceColor::Dark
+            };
+            let rank = board.back_rank(color);
+            let king = pieces
+                .iter()
+                .find(|piece| {
+                    piece.color == color && piece.piece_type == PieceType::King && piece.x == rank
+                })
+                .ok_or_else(|| format!("castling right '{}' without a king to castle", c))?
+                .y;
+            let mut rooks = pieces
+                .iter()
+                .filter(|piece| {
+                    piece.color == color && piece.piece_type == PieceType::Rook && piece.x == rank
+                })
+                .map(|piece| piece.y);
+            let (side, file) = match c.to_ascii_lowercase() {
+                'k' => (CastlingSide::King, rooks.filter(|&file| file > king).max()),
+                'q' => (CastlingSide::Queen, rooks.filter(|&file| file < king).min()),
+                letter @ 'a'..='z' if letter as u8 - b'a' < board.files => {
+                    let file = letter as u8 - b'a';
+                    let side = if file > king {
+                        CastlingSide::King
+                    } else {
+                        CastlingSide::Queen
+                    };
+                    (side, rooks.find(|&rook| rook == file))
+                }
+                _ => return Err(format!("invalid castling right '{}'", c)),
+            };
+            let file = file.ok_or_else(|| format!("castling right '{}' without a rook", c))?;
+            // Anything but the king right of the middle and the rooks in the corners is Chess960
+            chess960 |= !"KQkq".contains(c)
+                || king != board.files / 2
+                || file != 0 && file != board.files - 1;
+            castling.set(color, side, Some(file));
+        }
+
+        let en_passant = match fields.next().unwrap_or("-") {
+            "-" => None,
+            square => Some(
+                board
+                    .parse_square(square)
+                    .ok_or_else(|| format!("invalid en passant square '{}'", square))?,
+            ),
+        };
+        let halfmove_clock = fields
+            .next()
+            .map_or(Ok(0), str::parse)
+            .map_err(|_| "invalid halfmove clock")?;
+        let fullmove_number = fields
+            .next()
+            .map_or(Ok(1), str::parse)
+            .map_err(|_| "invalid fullmove number")?;
+
+        Ok(Position {
+            pieces,
+            turn,
+            castling,
+            en_passant,
+            halfmove_clock,
+            fullmove_number,
+            chess960,
+            variant: &Standard,
+            state: VariantState::default(),
+        })
+    }
+
+    pub(crate) fn check_king(&self, color: PieceColor) -> Result<(), String> {
+        match self.king(color) {
+            Some(_) => Ok(()),
+            None => Err(format!("{} has no king", color)),
+        }
+    }
+
+    /// A pawn can't stand where it would have promoted
+    pub(crate) fn check_pawns(&self) -> Result<(), String> {
+        for piece in &self.pieces {
+            let square = piece.square();
+            if piece.piece_type == PieceType::Pawn
+                && self.variant.is_promotion_square(piece.color, square)
+            {
+                return Err(format!(
+                    "the pawn on {} should have promoted",
+                    self.variant.board().square_name(square)
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the position in FEN, castling rights of Chess960 positions are written as in X-FEN
+    pub fn to_fen(&self) -> String {
+        self.fen(false)
+    }
+
+    /// Write the position in Shredder-FEN, which names the file of every rook that can castle
+    pub fn to_shredder_fen(&self) -> String {
+        self.fen(true)
+    }
+
+    fn fen(&self, shredder: bool) -> String {
+        let board = self.variant.board();
+        let mut fen = String::new();
+        for row in (0..board.rows()).rev() {
+            let mut empty = 0;
+            for square in board.row_squares(row) {
+                match self.piece_at(square) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        let letter = piece_letter(piece.piece_type, piece.color);
+                        match piece.color {
+                            PieceColor::Blue | PieceColor::Green => {
+                                fen.push_str(&format!("({})", letter))
+                            }
+                            _ => fen.push(letter),
+                        }
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if row > 0 {
+                fen.push('/');
+            }
+        }
+
+        fen.push(' ');
+        fen.push_str(turn_text(self.turn));
+        fen.push(' ');
+
+        let mut castling = String::new();
+        for color in [PieceColor::Light, PieceColor::Dark] {
+            for side in [CastlingSide::King, CastlingSide::Queen] {
+                let file = match self.castling.get(color, side) {
+                    Some(file) => file,
+                    None => continue,
+                };
+                let letter = if shredder || !self.is_outermost_rook(color, side, file) {
+                    (b'A' + file) as char
+                } else {
+                    match side {
+                        CastlingSide::King => 'K',
+                        CastlingSide::Queen => 'Q',
+                    }
+                };
+                castling.push(match color {
+                    PieceColor::Dark => letter.to_ascii_lowercase(),
+                    _ => letter,
+                });
+            }
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+        fen.push_str(&castling);
+
+        fen.push(' ');
+        match self.en_passant {
+            Some(square) => fen.push_str(&board.square_name(square)),
+            None => fen.push('-'),
+        }
+        fen.push_str(&format!(
+            " {} {}",
+            self.halfmove_clock, self.fullmove_number
+        ));
+        self.variant.write_fen(self, &mut fen);
+        fen
+    }
+
+    /// Whether no other rook of `color` stands further out on the back rank than the one on `file`
+    fn is_outermost_rook(&self, color: PieceColor, side: CastlingSide, file: u8) -> bool {
+        let rank = self.variant.board().back_rank(color);
+        !self.pieces.iter().any(|piece| {
+            piece.color == color
+                && piece.piece_type == PieceType::Rook
+                && piece.x == rank
+                && match side {
+                    CastlingSide::King => piece.y > file,
+                    CastlingSide::Queen => piece.y < file,
+                }
+        })
+    }
+}
@@ -0,0 +1,270 @@
+use bevy::prelude::*;
+
+use crate::{
+    board::square_translation,
+    notation::san,
+    pieces::{Piece, PieceColor, PieceModel},
+    rules::{GameResult, Move, MoveEffects, Position},
+};
+
+pub struct GamePlugin;
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Game>()
+            .init_resource::<PlayerTurn>()
+            .add_event::<MoveRequest>()
+            .add_event::<MoveMade>()
+            .add_event::<ResetBoardEvent>()
+            .add_startup_system(create_pieces)
+            .add_system(reset_board.before(GameSystem::ApplyMoves))
+            .add_system(apply_moves.label(GameSystem::ApplyMoves))
+            .add_system(despawn_taken_pieces);
+    }
+}
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GameSystem {
+    ApplyMoves,
+}
+
+/// A move that has been played, together with how it was written
+#[derive(Clone, Debug)]
+pub struct PlayedMove {
+    pub mv: Move,
+    pub san: String,
+}
+
+/// The game being played, the piece entities mirror `position`
+pub struct Game {
+    pub start: Position,
+    pub position: Position,
+    pub moves: Vec<PlayedMove>,
+    pub result: Option<GameResult>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new(Position::default())
+    }
+}
+
+impl Game {
+    pub fn new(start: Position) -> Self {
+        let result = start.outcome();
+        Self {
+            position: start.clone(),
+            start,
+            moves: Vec::new(),
+            result,
+        }
+    }
+
+    /// Replay `moves` from `start`, they have to be legal
+    pub fn from_moves(start: Position, moves: &[Move]) -> Self {
+        let mut game = Self::new(start);
+        for mv in moves {
+            game.play(mv);
+        }
+        game
+    }
+
+    /// Play a legal move and check whether it ended the game
+    pub fn play(&mut self, mv: &Move) -> MoveEffects {
+        let san = san(&self.position, mv);
+        let effects = self.position.make_move(mv);
+        self.moves.push(PlayedMove { mv: *mv, san });
+        if self.result.is_none() {
+            self.result = self.position.outcome();
+        }
+        effects
+    }
+
+    pub fn end(&mut self, result: GameResult) {
+        if self.result.is_none() {
+            println!("{}! Thanks for playing!", result);
+            self.result = Some(result);
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.result.is_some()
+    }
+
+    /// Position before the move with index `ply` was played
+    pub fn position_before(&self, ply: usize) -> Position {
+        let mut position = self.start.clone();
+        for played in &self.moves[..ply] {
+            position.make_move(&played.mv);
+        }
+        position
+    }
+
+    /// Undo the last `plies` moves, the pieces have to be respawned with `ResetBoardEvent`
+    pub fn take_back(&mut self, plies: usize) {
+        let moves = self.move_list();
+        *self = Self::from_moves(self.start.clone(), &moves[..moves.len() - plies]);
+    }
+
+    pub fn move_list(&self) -> Vec<Move> {
+        self.moves.iter().map(|played| played.mv).collect()
+    }
+}
+
+pub struct PlayerTurn(pub PieceColor);
+
+impl Default for PlayerTurn {
+    fn default() -> Self {
+        Self(PieceColor::Light)
+    }
+}
+
+/// Ask for a move to be played, it is ignored unless it is legal
+pub struct MoveRequest(pub Move);
+
+/// Sent after a move has been played by `color`
+pub struct MoveMade {
+    pub color: PieceColor,
+    pub mv: Move,
+    pub san: String,
+}
+
+/// Respawn all pieces from `Game::position`, after the game has been replaced
+pub struct ResetBoardEvent;
+
+/// Marks a captured piece, it stays on the board until `attacker` reaches `square`
+#[derive(Component)]
+pub struct Taken {
+    pub attacker: Entity,
+    pub square: (u8, u8, u8),
+    /// Blown up by an atomic capture instead of being taken the usual way
+    pub exploded: bool,
+}
+
+fn create_pieces(mut commands: Commands, game: Res<Game>, mut turn: ResMut<PlayerTurn>) {
+    spawn_pieces(&mut commands, &game.position);
+    turn.0 = game.position.turn;
+}
+
+/// Spawn an entity for every piece
+fn spawn_pieces(commands: &mut Commands, position: &Position) {
+    for piece in &position.pieces {
+        spawn_piece(commands, position, piece);
+    }
+}
+
+/// Spawn the entity of a piece, the meshes are added by the pieces plugin
+fn spawn_piece(commands: &mut Commands, position: &Position, piece: &Piece) {
+    let translation = square_translation(position.variant.board(), piece.square());
+    commands
+        .spawn()
+        .insert(*piece)
+        .insert(Transform::from_translation(translation))
+        .insert(GlobalTransform::identity());
+}
+
+fn reset_board(
+    mut commands: Commands,
+    mut events: EventReader<ResetBoardEvent>,
+    game: Res<Game>,
+    mut turn: ResMut<PlayerTurn>,
+    pieces: Query<Entity, With<Piece>>,
+) {
+    if events.iter().count() == 0 {
+        return;
+    }
+    for entity in pieces.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_pieces(&mut commands, &game.position);
+    turn.0 = game.position.turn;
+}
+
+fn apply_moves(
+    mut commands: Commands,
+    mut game: ResMut<Game>,
+    mut turn: ResMut<PlayerTurn>,
+    mut requests: EventReader<MoveRequest>,
+    mut moves_made: EventWriter<MoveMade>,
+    mut pieces: Query<(Entity, &mut Piece), Without<Taken>>,
+) {
+    // Pieces taken in this run, their `Taken` marker is only inserted at the end of the stage
+    let mut taken = Vec::new();
+    for MoveRequest(mv) in requests.iter() {
+        if game.is_over() || !game.position.is_legal(mv) {
+            warn!("Ignoring illegal move {:?}", mv);
+            continue;
+        }
+        let color = game.position.turn;
+        let effects = game.play(mv);
+
+        let entity_at = |pieces: &Query<(Entity, &mut Piece), Without<Taken>>, square| {
+            pieces
+                .iter()
+                .find(|(entity, piece)| piece.square() == square && !taken.contains(entity))
+                .map(|(entity, _)| entity)
+        };
+        // Look up all entities before moving any, castling in Chess960 can swap king and rook
+        let moved: Vec<(Entity, (u8, u8, u8))> = effects
+            .moved
+            .iter()
+            .filter_map(|&(from, to)| entity_at(&pieces, from).map(|entity| (entity, to)))
+            .collect();
+        let mut captured: Vec<Entity> = effects
+            .captured
+            .iter()
+            .chain(&effects.exploded)
+            .filter_map(|piece| entity_at(&pieces, piece.square()))
+            .collect();
+
+        if let Some(&(attacker, square)) = moved.first() {
+            // The capturing piece blows up with the others once it gets there
+            if effects.explosion.is_some() {
+                captured.push(attacker);
+            }
+            for entity in captured {
+                commands.entity(entity).insert(Taken {
+                    attacker,
+                    square,
+                    exploded: effects.explosion.is_some(),
+                });
+                taken.push(entity);
+            }
+        }
+        for (index, (entity, (x, y, z))) in moved.into_iter().enumerate() {
+            if let Ok((_, mut piece)) = pieces.get_mut(entity) {
+                piece.x = x;
+                piece.y = y;
+                piece.z = z;
+                if index == 0 {
+                    if let Some(promotion) = effects.promotion {
+                        piece.piece_type = promotion;
+                    }
+                }
+            }
+        }
+        if let Some(dropped) = &effects.dropped {
+            spawn_piece(&mut commands, &game.position, dropped);
+        }
+
+        turn.0 = game.position.turn;
+        let san = game.moves.last().unwrap().san.clone();
+        moves_made.send(MoveMade {
+            color,
+            mv: *mv,
+            san,
+        });
+        if let Some(result) = &game.result {
+            println!("{}! Thanks for playing!", result);
+        }
+    }
+}
+
+/// Remove taken pieces right away when they have no model to animate, like in headless games
+fn despawn_taken_pieces(
+    mut commands: Commands,
+    query: Query<Entity, (With<Taken>, Without<PieceModel>)>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,116 @@
+use std::{collections::VecDeque, path::PathBuf};
+
+use bevy::{app::AppExit, prelude::*};
+
+use crate::{
+    game::{Game, GameSystem, MoveRequest},
+    notation::{parse_san, parse_uci, pgn_date, write_pgn},
+    pieces::PieceColor,
+    players::Players,
+    rules::Move,
+};
+
+/// Runs games without a window, moves come from the script and the engines. The finished game is
+/// written as PGN and the app exits.
+pub struct HeadlessPlugin;
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScriptedMoves>()
+            .init_resource::<PgnOutput>()
+            .add_system(play_scripted_move.before(GameSystem::ApplyMoves))
+            .add_system(finish_game.after(GameSystem::ApplyMoves));
+    }
+}
+
+/// Moves that are played one per frame before the players take over
+#[derive(Default)]
+pub struct ScriptedMoves(pub VecDeque<Move>);
+
+impl ScriptedMoves {
+    /// Read whitespace separated moves in SAN or UCI notation, lines starting with `#` are
+    /// comments. The moves are checked against the game they will be played in.
+    pub fn parse(text: &str, game: &Game) -> Result<Self, String> {
+        let mut position = game.position.clone();
+        let mut moves = VecDeque::new();
+        for token in text
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .flat_map(str::split_whitespace)
+        {
+            let mv = parse_san(&position, token)
+                .or_else(|_| parse_uci(&position, token))
+                .map_err(|err| format!("move {}: {}", position.fullmove_number, err))?;
+            position.make_move(&mv);
+            moves.push_back(mv);
+        }
+        Ok(Self(moves))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Where the finished game is written, `None` prints it
+#[derive(Default)]
+pub struct PgnOutput(pub Option<PathBuf>);
+
+fn play_scripted_move(
+    mut script: ResMut<ScriptedMoves>,
+    game: Res<Game>,
+    mut requests: EventWriter<MoveRequest>,
+) {
+    if game.is_over() {
+        return;
+    }
+    if let Some(mv) = script.0.pop_front() {
+        requests.send(MoveRequest(mv));
+    }
+}
+
+/// Write the game and exit once it is over, or when the script ended and a human is to move
+fn finish_game(
+    game: Res<Game>,
+    script: Res<ScriptedMoves>,
+    players: Res<Players>,
+    output: Res<PgnOutput>,
+    mut app_exit: EventWriter<AppExit>,
+    mut written: Local<bool>,
+) {
+    if *written {
+        return;
+    }
+    let stuck = script.is_empty() && players.is_human(game.position.turn);
+    if !game.is_over() && !stuck {
+        return;
+    }
+    if !game.is_over() {
+        warn!("The script has no moves left and a human is to move, stopping the game");
+    }
+
+    let tags = vec![
+        ("Event".to_string(), "Headless game".to_string()),
+        ("Site".to_string(), "?".to_string()),
+        ("Date".to_string(), pgn_date()),
+        ("Round".to_string(), "-".to_string()),
+        (
+            "White".to_string(),
+            players.get(PieceColor::Light).to_string(),
+        ),
+        (
+            "Black".to_string(),
+            players.get(PieceColor::Dark).to_string(),
+        ),
+    ];
+    let pgn = write_pgn(&tags, &game.start, &game.move_list(), game.result.as_ref());
+    match &output.0 {
+        Some(path) => {
+            if let Err(err) = std::fs::write(path, pgn) {
+                error!("Could not write {}: {}", path.display(), err);
+            }
+        }
+        None => print!("{}", pgn),
+    }
+    *written = true;
+    app_exit.send(AppExit);
+}
@@ -0,0 +1,34 @@
+//! Game logic and plugins, shared by the game and the `chess-server` binary
+
+pub mod ai;
+pub mod animation;
+pub mod assets;
+pub mod betza;
+pub mod bitboard;
+pub mod board;
+pub mod camera;
+pub mod chat;
+pub mod chess960;
+pub mod cli;
+pub mod clock;
+pub mod cursor;
+pub mod fen;
+pub mod game;
+pub mod headless;
+pub mod menu;
+pub mod net;
+pub mod notation;
+pub mod offers;
+pub mod piece_set;
+pub mod pieces;
+pub mod players;
+pub mod reserve;
+pub mod rules;
+pub mod saves;
+pub mod server;
+pub mod settings;
+pub mod sound;
+pub mod theme;
+pub mod uci;
+pub mod variants;
+pub mod ui;
@@ -1,44 +1,199 @@
-use bevy::prelude::*;
-use bevy_mod_picking::{DebugCursorPickingPlugin, DefaultPickingPlugins, PickingCameraBundle};
+use std::time::{Duration, Instant};
 
-mod board;
-mod pieces;
-mod ui;
+use bevy::{app::ScheduleRunnerSettings, log::LogPlugin, prelude::*};
+use bevy_mod_picking::{DebugCursorPickingPlugin, DefaultPickingPlugins, PickingCameraBundle};
+use clap::Parser;
 
-use board::BoardPlugin;
-use pieces::PiecesPlugin;
-use ui::UiPlugin;
+use chess::{
+    animation::AnimationPlugin,
+    board::BoardPlugin,
+    camera::{CameraPlugin, OrbitCamera},
+    chat::ChatPlugin,
+    cli::Args,
+    clock::{Clock, ClockPlugin},
+    cursor::CursorPlugin,
+    game::GamePlugin,
+    headless::{HeadlessPlugin, PgnOutput},
+    menu::MenuPlugin,
+    net::{Connection, NetPlugin, Spectating},
+    offers::OffersPlugin,
+    pieces::{PieceColor, PiecesPlugin},
+    players::PlayersPlugin,
+    reserve::ReservePlugin,
+    saves::SavesPlugin,
+    settings::{SavedSettings, Settings, SettingsPlugin},
+    sound::SoundPlugin,
+    theme::ThemePlugin,
+    ui::UiPlugin,
+};
 
 fn main() {
-    App::new()
+    let args = Args::parse();
+    let saved_settings = SavedSettings(Settings::load());
+    let mut settings = saved_settings.0.clone();
+    let overrides = args.settings_overrides();
+    overrides.apply(&mut settings);
+    let saved = args
+        .saved_game()
+        .unwrap_or_else(|err| exit_with_error(&err));
+    let mut game = match &saved {
+        Some(saved) => saved.game(),
+        None => args.game(),
+    }
+    .unwrap_or_else(|err| exit_with_error(&err));
+    if let Some(depth) = args.perft {
+        let start = Instant::now();
+        let nodes = game.position.perft(depth);
+        let seconds = start.elapsed().as_secs_f64();
+        println!(
+            "perft {}: {} nodes in {:.3}s, {:.0} nodes/s",
+            depth,
+            nodes,
+            seconds,
+            nodes as f64 / seconds.max(1e-9)
+        );
+        return;
+    }
+    let script = args
+        .scripted_moves(&game)
+        .unwrap_or_else(|err| exit_with_error(&err));
+    let mut players = args.players();
+    let mut time_control = args.time_control(&settings);
+    let mut clock = Clock::new(time_control);
+    // Clocks of a resumed game
+    if let Some(saved) = &saved {
+        players = saved.players();
+        time_control = saved.time_control;
+        clock = saved.clock();
+    }
+    // Network games are made for two sides
+    if game.position.variant.colors().len() > 2 && (args.host.is_some() || args.server.is_some())
+    {
+        exit_with_error(&format!(
+            "{} can't be played over the network",
+            game.position.variant.name()
+        ));
+    }
+
+    // Network games agree on colors, position and time control before the window opens
+    let connection = if let Some(address) = &args.host {
+        let host_color = args.play_as.unwrap_or(PieceColor::Light);
+        let connection = Connection::host(address, host_color, time_control, &game)
+            .unwrap_or_else(|err| exit_with_error(&err));
+        players.set_remote(host_color.opponent());
+        Some(connection)
+    } else if let Some(address) = &args.connect {
+        let (connection, agreement) =
+            Connection::connect(address).unwrap_or_else(|err| exit_with_error(&err));
+        players.set_remote(agreement.local_color.opponent());
+        clock = Clock::new(agreement.time_control);
+        game = agreement.game;
+        Some(connection)
+    } else if let Some(address) = &args.server {
+        let request = args.lobby_request(time_control);
+        let (connection, agreement) =
+            Connection::join_server(address, request).unwrap_or_else(|err| exit_with_error(&err));
+        players.set_remote(agreement.local_color.opponent());
+        if connection.spectating {
+            players.set_remote(agreement.local_color);
+        }
+        clock = Clock::new(agreement.time_control);
+        if let Some([white_ms, black_ms]) = agreement.clock_ms {
+            clock.set_remaining(PieceColor::Light, white_ms as f32 / 1000.0);
+            clock.set_remaining(PieceColor::Dark, black_ms as f32 / 1000.0);
+        }
+        game = agreement.game;
+        Some(connection)
+    } else {
+        None
+    };
+
+    let mut app = App::new();
+    if args.headless {
+        let has_human = game
+            .position
+            .variant
+            .colors()
+            .iter()
+            .any(|&color| players.is_human(color));
+        if has_human && script.is_empty() {
+            exit_with_error("headless games need --script or ai/engine players for every side");
+        }
+        app.insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_millis(1)))
+            .add_plugins(MinimalPlugins)
+            .add_plugin(LogPlugin)
+            .insert_resource(game)
+            .insert_resource(players)
+            .insert_resource(clock)
+            .insert_resource(script)
+            .insert_resource(PgnOutput(args.output.clone()))
+            .add_plugin(GamePlugin)
+            .add_plugin(PlayersPlugin)
+            .add_plugin(ClockPlugin)
+            .add_plugin(HeadlessPlugin)
+            .run();
+        return;
+    }
+
+    // The window is created from the descriptor when the plugins are built
+    app.insert_resource(settings.window_descriptor())
+        .insert_resource(Msaa {
+            samples: settings.display.msaa_samples,
+        })
         .add_plugins(DefaultPlugins)
-        .add_plugin(DefaultPickingPlugins)
-        .add_plugin(DebugCursorPickingPlugin)
+        .add_plugin(DefaultPickingPlugins);
+    if settings.input.debug_cursor {
+        app.add_plugin(DebugCursorPickingPlugin);
+    }
+    app.insert_resource(settings)
+        .insert_resource(saved_settings)
+        .insert_resource(overrides)
+        .insert_resource(game)
+        .insert_resource(players)
+        .insert_resource(clock)
+        .add_plugin(SettingsPlugin)
+        .add_plugin(ThemePlugin)
+        .add_plugin(GamePlugin)
+        .add_plugin(PlayersPlugin)
+        .add_plugin(ClockPlugin)
+        .add_plugin(OffersPlugin)
         .add_plugin(PiecesPlugin)
+        .add_plugin(AnimationPlugin)
         .add_plugin(BoardPlugin)
+        .add_plugin(ReservePlugin)
+        .add_plugin(CursorPlugin)
+        .add_plugin(CameraPlugin)
         .add_plugin(UiPlugin)
-        .insert_resource(Msaa { samples: 4 })
-        .insert_resource(WindowDescriptor {
-            title: "Chess!".to_string(),
-            width: 1600.,
-            height: 1600.,
-            ..Default::default()
-        })
-        .add_startup_system(setup)
-        .run();
+        .add_plugin(MenuPlugin)
+        .add_plugin(ChatPlugin)
+        .add_plugin(SavesPlugin)
+        .add_plugin(SoundPlugin)
+        .add_startup_system(setup);
+    if let Some(connection) = connection {
+        if connection.spectating {
+            app.insert_resource(Spectating);
+        }
+        app.insert_resource(connection).add_plugin(NetPlugin);
+    }
+    app.run();
+}
+
+fn exit_with_error(err: &str) -> ! {
+    eprintln!("error: {}", err);
+    std::process::exit(2);
 }
 
-fn setup(mut commands: Commands) {
+fn setup(mut commands: Commands, settings: Res<Settings>) {
     // Camera
     commands
         .spawn_bundle(PerspectiveCameraBundle {
-            transform: Transform::from_matrix(Mat4::from_rotation_translation(
-                Quat::from_xyzw(-0.3, -0.5, -0.3, 0.5).normalize(),
-                Vec3::new(-7.0, 20.0, 4.0),
-            )),
+            transform: settings.camera_transform(),
             ..Default::default()
         })
-        .insert_bundle(PickingCameraBundle::default());
+        .insert_bundle(PickingCameraBundle::default())
+        .insert(OrbitCamera {
+            focus: settings.camera.look_at.into(),
+        });
     // Light
     commands.spawn_bundle(PointLightBundle {
         transform: Transform::from_translation(Vec3::new(4.0, 8.0, 4.0)),
@@ -0,0 +1,303 @@
+use bevy::prelude::*;
+
+use crate::{animation::CaptureEffect, settings::Settings, theme::Themes};
+
+pub struct MenuPlugin;
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(init_settings_button)
+            .add_system(toggle_settings_menu)
+            .add_system(change_settings)
+            .add_system(animation_speed_text)
+            .add_system(color_buttons);
+    }
+}
+
+const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
+const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
+const ACTIVE_BUTTON: Color = Color::rgb(0.35, 0.55, 0.35);
+
+// Component to mark the button opening the settings menu
+#[derive(Component)]
+struct SettingsButton;
+
+// Component to mark the root node of the settings menu
+#[derive(Component)]
+struct SettingsMenu;
+
+// Component to mark the text showing the animation speed
+#[derive(Component)]
+struct AnimationSpeedText;
+
+/// What a button in the settings menu changes
+#[derive(Component, Clone, Copy, PartialEq)]
+enum SettingButton {
+    Theme(usize),
+    CaptureEffect(CaptureEffect),
+    SlowerAnimations,
+    FasterAnimations,
+    Sound,
+    Fullscreen,
+}
+
+impl SettingButton {
+    /// Whether the button shows the current choice or an enabled toggle
+    fn is_active(&self, settings: &Settings, themes: &Themes) -> bool {
+        match self {
+            SettingButton::Theme(index) => *index == themes.active_index(),
+            SettingButton::CaptureEffect(effect) => *effect == settings.animation.capture_effect,
+            SettingButton::SlowerAnimations | SettingButton::FasterAnimations => false,
+            SettingButton::Sound => settings.sound.enabled,
+            SettingButton::Fullscreen => settings.display.fullscreen,
+        }
+    }
+}
+
+fn init_settings_button(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .with_children(|parent| spawn_button(parent, font, "Settings", SettingsButton));
+}
+
+/// Open or close the settings menu with its button or F1
+fn toggle_settings_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    keys: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
+    themes: Res<Themes>,
+    buttons: Query<&Interaction, (Changed<Interaction>, With<SettingsButton>)>,
+    menus: Query<Entity, With<SettingsMenu>>,
+) {
+    let clicked = buttons
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    if !clicked && !keys.just_pressed(KeyCode::F1) {
+        return;
+    }
+
+    let mut was_open = false;
+    for menu in menus.iter() {
+        commands.entity(menu).despawn_recursive();
+        was_open = true;
+    }
+    if was_open {
+        return;
+    }
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(10.0),
+                    top: Val::Px(70.0),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                padding: Rect::all(Val::Px(10.0)),
+                ..Default::default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.7).into(),
+            ..Default::default()
+        })
+        .insert(SettingsMenu)
+        .with_children(|parent| {
+            spawn_label(parent, font.clone(), "Theme");
+            for (index, theme) in themes.themes.iter().enumerate() {
+                spawn_button(
+                    parent,
+                    font.clone(),
+                    &theme.name,
+                    SettingButton::Theme(index),
+                );
+            }
+
+            spawn_label(parent, font.clone(), "Captured pieces");
+            for (label, effect) in [
+                ("Topple", CaptureEffect::Topple),
+                ("Sink", CaptureEffect::Sink),
+                ("Fly to tray", CaptureEffect::FlyToTray),
+            ] {
+                spawn_button(
+                    parent,
+                    font.clone(),
+                    label,
+                    SettingButton::CaptureEffect(effect),
+                );
+            }
+
+            spawn_label(parent, font.clone(), "Animation speed");
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    color: Color::NONE.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    spawn_small_button(parent, font.clone(), "-", SettingButton::SlowerAnimations);
+                    parent
+                        .spawn_bundle(TextBundle {
+                            style: Style {
+                                margin: Rect::all(Val::Px(8.0)),
+                                ..Default::default()
+                            },
+                            text: Text::with_section(
+                                format!("{:.2}x", settings.animation.speed),
+                                text_style(font.clone()),
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        .insert(AnimationSpeedText);
+                    spawn_small_button(parent, font.clone(), "+", SettingButton::FasterAnimations);
+                });
+
+            spawn_label(parent, font.clone(), "Other");
+            spawn_button(parent, font.clone(), "Sound", SettingButton::Sound);
+            spawn_button(parent, font, "Fullscreen", SettingButton::Fullscreen);
+        });
+}
+
+/// Change the settings with the buttons in the menu, they are written back to disk by the
+/// settings plugin
+fn change_settings(
+    mut settings: ResMut<Settings>,
+    themes: Res<Themes>,
+    buttons: Query<(&Interaction, &SettingButton), Changed<Interaction>>,
+) {
+    for (interaction, button) in buttons.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        match *button {
+            SettingButton::Theme(index) => {
+                settings.theme = themes.themes[index].name.clone();
+            }
+            SettingButton::CaptureEffect(effect) => settings.animation.capture_effect = effect,
+            SettingButton::SlowerAnimations => {
+                settings.animation.speed = (settings.animation.speed - 0.25).max(0.25);
+            }
+            SettingButton::FasterAnimations => {
+                settings.animation.speed = (settings.animation.speed + 0.25).min(4.0);
+            }
+            SettingButton::Sound => settings.sound.enabled = !settings.sound.enabled,
+            SettingButton::Fullscreen => {
+                settings.display.fullscreen = !settings.display.fullscreen;
+            }
+        }
+    }
+}
+
+fn animation_speed_text(
+    settings: Res<Settings>,
+    mut query: Query<&mut Text, With<AnimationSpeedText>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    for mut text in query.iter_mut() {
+        text.sections[0].value = format!("{:.2}x", settings.animation.speed);
+    }
+}
+
+/// Show which buttons are hovered and which choices are active
+fn color_buttons(
+    settings: Res<Settings>,
+    themes: Res<Themes>,
+    mut buttons: Query<(&Interaction, &mut UiColor, Option<&SettingButton>), With<Button>>,
+) {
+    for (interaction, mut color, setting_button) in buttons.iter_mut() {
+        let active = setting_button.map_or(false, |button| button.is_active(&settings, &themes));
+        *color = if active {
+            ACTIVE_BUTTON.into()
+        } else if *interaction == Interaction::None {
+            NORMAL_BUTTON.into()
+        } else {
+            HOVERED_BUTTON.into()
+        };
+    }
+}
+
+pub fn text_style(font: Handle<Font>) -> TextStyle {
+    TextStyle {
+        font,
+        font_size: 24.0,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    }
+}
+
+pub fn spawn_label(parent: &mut ChildBuilder, font: Handle<Font>, label: &str) {
+    parent.spawn_bundle(TextBundle {
+        style: Style {
+            margin: Rect::all(Val::Px(4.0)),
+            ..Default::default()
+        },
+        text: Text::with_section(label, text_style(font), Default::default()),
+        ..Default::default()
+    });
+}
+
+pub fn spawn_button(
+    parent: &mut ChildBuilder,
+    font: Handle<Font>,
+    label: &str,
+    marker: impl Component,
+) {
+    spawn_sized_button(parent, font, label, marker, 220.0);
+}
+
+fn spawn_small_button(
+    parent: &mut ChildBuilder,
+    font: Handle<Font>,
+    label: &str,
+    marker: impl Component,
+) {
+    spawn_sized_button(parent, font, label, marker, 36.0);
+}
+
+pub fn spawn_sized_button(
+    parent: &mut ChildBuilder,
+    font: Handle<Font>,
+    label: &str,
+    marker: impl Component,
+    width: f32,
+) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(width), Val::Px(36.0)),
+                margin: Rect::all(Val::Px(4.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: NORMAL_BUTTON.into(),
+            ..Default::default()
+        })
+        .insert(marker)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(label, text_style(font), Default::default()),
+                ..Default::default()
+            });
+        });
+}
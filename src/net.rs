@@ -0,0 +1,989 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock::{Clock, ClockSystem, TimeControl},
+    game::{Game, GameSystem, MoveMade, MoveRequest},
+    notation::{parse_uci, parse_uci_moves, uci, uci_moves},
+    offers::{Action, GameAction, OfferSystem},
+    pieces::PieceColor,
+    rules::{GameResult, Move, Position},
+    variants::{find_variant, Variant},
+};
+
+/// Bumped whenever `Message` changes, both sides have to speak the same version
+pub const PROTOCOL_VERSION: u32 = 7;
+
+pub const DEFAULT_PORT: u16 = 7878;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a client keeps trying to get back into a game on the server
+const RECONNECT_WINDOW: Duration = Duration::from_secs(60);
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+
+pub struct NetPlugin;
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            receive_messages
+                .after(OfferSystem::ApplyActions)
+                .before(GameSystem::ApplyMoves),
+        )
+        // The mover's time is sent before the increment, both sides add it themselves
+        .add_system(
+            send_local_moves
+                .after(GameSystem::ApplyMoves)
+                .before(ClockSystem::Increment),
+        )
+        .add_system(send_local_actions);
+    }
+}
+
+/// Present while watching someone else's game, the board ignores input
+pub struct Spectating;
+
+/// Everything sent over the connection, one RON value per line
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Message {
+    /// First message of the joining side
+    Hello {
+        version: u32,
+    },
+    /// The host's answer, with the game both sides start from
+    Welcome {
+        version: u32,
+        your_color: PieceColor,
+        time_control: Option<TimeControl>,
+        /// Name of the rules being played
+        variant: String,
+        start_fen: String,
+        /// FEN only tells Chess960 positions apart when castling is unusual
+        chess960: bool,
+        /// Moves already played from `start_fen`, in UCI notation
+        moves: Vec<String>,
+    },
+    Reject {
+        reason: String,
+    },
+    /// A move that was played as move number `ply`, with the position it led to so the other side
+    /// can check that both boards agree
+    Move {
+        ply: usize,
+        uci: String,
+        fen_after: String,
+        /// Time the mover had left after the move, before the increment, in milliseconds
+        clock_ms: Option<u32>,
+    },
+    /// The boards no longer agree, the game can't go on
+    Desync {
+        reason: String,
+    },
+
+    /// Ask the server to pair us with someone playing `time_control`
+    Seek {
+        time_control: Option<TimeControl>,
+    },
+    /// Take up an open seek from the lobby
+    AcceptSeek {
+        id: u32,
+    },
+    /// Get back into a game after the connection dropped
+    Resume {
+        token: String,
+    },
+    /// Follow a game on the server without playing in it
+    Watch {
+        id: u32,
+    },
+    /// Open seeks and running games, sent by the server to everyone in the lobby whenever they
+    /// change
+    Lobby {
+        seeks: Vec<SeekInfo>,
+        games: Vec<GameInfo>,
+    },
+    /// Our seek is open in the lobby
+    Seeking {
+        id: u32,
+    },
+    /// The server paired us or let us back into a game, `token` lets us resume it later
+    GameStarted {
+        your_color: PieceColor,
+        time_control: Option<TimeControl>,
+        /// Name of the rules being played
+        variant: String,
+        start_fen: String,
+        chess960: bool,
+        moves: Vec<String>,
+        /// Time left for white and black, in milliseconds
+        clock_ms: Option<[u32; 2]>,
+        token: String,
+    },
+    /// We are watching a game, its moves follow as they are played
+    Watching {
+        time_control: Option<TimeControl>,
+        variant: String,
+        start_fen: String,
+        chess960: bool,
+        moves: Vec<String>,
+        clock_ms: Option<[u32; 2]>,
+    },
+    /// The server's clocks after our own move, in milliseconds
+    Clock {
+        white_ms: u32,
+        black_ms: u32,
+    },
+    GameOver {
+        result: GameResult,
+    },
+    /// The opponent lost their connection and has `grace_seconds` to come back
+    OpponentLeft {
+        grace_seconds: u32,
+    },
+    OpponentBack,
+    /// Resigning, offers and their answers, and chat messages
+    Action {
+        color: PieceColor,
+        action: Action,
+    },
+}
+
+/// An open seek in the server lobby
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SeekInfo {
+    pub id: u32,
+    pub time_control: Option<TimeControl>,
+}
+
+/// A game running on the server that can be watched
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameInfo {
+    pub id: u32,
+    pub time_control: Option<TimeControl>,
+    /// Moves played so far
+    pub plies: usize,
+}
+
+/// What to do in the server lobby
+pub enum LobbyRequest {
+    /// Post a seek and wait for someone to take it
+    Seek(Option<TimeControl>),
+    /// Take up the seek with this id
+    Join(u32),
+    /// Take up the oldest open seek with this time control, or post one if there is none
+    Any(Option<TimeControl>),
+    Resume(String),
+    /// Watch the game with this id
+    Watch(u32),
+}
+
+/// What the reader thread passes on to the game
+enum Incoming {
+    Message(Message),
+    /// The server connection dropped, the reader thread tries to get back in
+    Lost,
+    /// Back in the game on the server, with its moves and clocks
+    Reconnected {
+        stream: TcpStream,
+        moves: Vec<String>,
+        clock_ms: Option<[u32; 2]>,
+    },
+    Closed(String),
+}
+
+/// Where to reconnect to when a server game drops, and how to get back into it
+#[derive(Clone)]
+struct ServerLink {
+    address: String,
+    /// `Resume` for players and `Watch` for spectators
+    rejoin: Message,
+}
+
+/// An open connection to the other player, or to the server that pairs us with them
+pub struct Connection {
+    /// The side we play, spectators see the board from white's side
+    pub local_color: PieceColor,
+    /// Watching a game on the server, we never send moves
+    pub spectating: bool,
+    stream: TcpStream,
+    incoming: Mutex<Receiver<Incoming>>,
+    server: Option<ServerLink>,
+}
+
+/// Game settings agreed on in the handshake
+pub struct Agreement {
+    pub local_color: PieceColor,
+    pub time_control: Option<TimeControl>,
+    pub game: Game,
+    /// Time left for white and black in milliseconds, when resuming a timed game
+    pub clock_ms: Option<[u32; 2]>,
+}
+
+impl Agreement {
+    fn new(
+        local_color: PieceColor,
+        time_control: Option<TimeControl>,
+        variant: &'static dyn Variant,
+        start_fen: &str,
+        chess960: bool,
+        moves: &[String],
+        clock_ms: Option<[u32; 2]>,
+    ) -> Result<Self, String> {
+        let mut start = Position::from_variant_fen(variant, start_fen)
+            .map_err(|err| format!("got an invalid position: {}", err))?;
+        start.chess960 |= chess960;
+        let moves = parse_uci_moves(&start, moves).map_err(|err| format!("got {}", err))?;
+        Ok(Self {
+            local_color,
+            time_control,
+            game: Game::from_moves(start, &moves),
+            clock_ms,
+        })
+    }
+}
+
+impl Connection {
+    /// Wait for the other player to connect to `address`, the host plays `host_color`
+    pub fn host(
+        address: &str,
+        host_color: PieceColor,
+        time_control: Option<TimeControl>,
+        game: &Game,
+    ) -> Result<Self, String> {
+        let address = with_default_port(address);
+        let listener = TcpListener::bind(&address)
+            .map_err(|err| format!("could not listen on {}: {}", address, err))?;
+        println!("Waiting for the other player on {}", address);
+        Self::accept(listener, host_color, time_control, game)
+    }
+
+    /// Wait for a client on `listener` that speaks our protocol version
+    fn accept(
+        listener: TcpListener,
+        host_color: PieceColor,
+        time_control: Option<TimeControl>,
+        game: &Game,
+    ) -> Result<Self, String> {
+        loop {
+            let (stream, peer) = listener
+                .accept()
+                .map_err(|err| format!("could not accept a connection: {}", err))?;
+            let (mut connection, mut reader) = Self::new(stream, host_color)?;
+            match read_message(&mut reader) {
+                Ok(Message::Hello { version }) if version == PROTOCOL_VERSION => {}
+                Ok(Message::Hello { version }) => {
+                    let reason = format!(
+                        "protocol version {} is not supported, the host speaks {}",
+                        version, PROTOCOL_VERSION
+                    );
+                    eprintln!("Rejected {}: {}", peer, reason);
+                    let _ = connection.send(&Message::Reject { reason });
+                    continue;
+                }
+                Ok(other) => {
+                    eprintln!("Rejected {}: expected Hello, got {:?}", peer, other);
+                    continue;
+                }
+                Err(err) => {
+                    eprintln!("Rejected {}: {}", peer, err);
+                    continue;
+                }
+            }
+
+            connection.send(&Message::Welcome {
+                version: PROTOCOL_VERSION,
+                your_color: host_color.opponent(),
+                time_control,
+                variant: game.start.variant.name().to_string(),
+                start_fen: game.start.to_fen(),
+                chess960: game.start.chess960,
+                moves: uci_moves(&game.start, &game.move_list()),
+            })?;
+            println!("{} joined", peer);
+            connection.start_reading(reader);
+            return Ok(connection);
+        }
+    }
+
+    /// Join a game hosted at `address`
+    pub fn connect(address: &str) -> Result<(Self, Agreement), String> {
+        let (mut connection, mut reader) = Self::open(address)?;
+        let agreement = match read_message(&mut reader)? {
+            Message::Welcome {
+                version,
+                your_color,
+                time_control,
+                variant,
+                start_fen,
+                chess960,
+                moves,
+            } => {
+                if version != PROTOCOL_VERSION {
+                    return Err(format!(
+                        "the host speaks protocol version {}, this build speaks {}",
+                        version, PROTOCOL_VERSION
+                    ));
+                }
+                let variant = find_variant(&variant).map_err(|err| format!("got {}", err))?;
+                Agreement::new(
+                    your_color,
+                    time_control,
+                    variant,
+                    &start_fen,
+                    chess960,
+                    &moves,
+                    None,
+                )?
+            }
+            Message::Reject { reason } => return Err(format!("the host refused: {}", reason)),
+            other => return Err(format!("expected Welcome, got {:?}", other)),
+        };
+        connection.local_color = agreement.local_color;
+        connection.start_reading(reader);
+        Ok((connection, agreement))
+    }
+
+    /// Get a game from the `chess-server` at `address`, waits until we are paired
+    pub fn join_server(address: &str, request: LobbyRequest) -> Result<(Self, Agreement), String> {
+        let (mut connection, mut reader) = Self::open(address)?;
+        let seeks = match read_message(&mut reader)? {
+            Message::Lobby { seeks, games } => {
+                for game in games {
+                    let time_control = game
+                        .time_control
+                        .map_or("untimed".to_string(), |time_control| {
+                            time_control.to_string()
+                        });
+                    println!(
+                        "Game {} is running ({}, {} plies played), watch it with --watch {}",
+                        game.id, time_control, game.plies, game.id
+                    );
+                }
+                seeks
+            }
+            Message::Reject { reason } => return Err(format!("the server refused: {}", reason)),
+            other => return Err(format!("expected Lobby, got {:?}", other)),
+        };
+
+        let message = match request {
+            LobbyRequest::Seek(time_control) => Message::Seek { time_control },
+            LobbyRequest::Join(id) => Message::AcceptSeek { id },
+            LobbyRequest::Any(time_control) => {
+                match seeks.iter().find(|seek| seek.time_control == time_control) {
+                    Some(seek) => Message::AcceptSeek { id: seek.id },
+                    None => Message::Seek { time_control },
+                }
+            }
+            LobbyRequest::Resume(token) => Message::Resume { token },
+            LobbyRequest::Watch(id) => Message::Watch { id },
+        };
+        connection.send(&message)?;
+
+        loop {
+            match read_message(&mut reader)? {
+                Message::Seeking { id } => println!("Seek {} is open, waiting for an opponent", id),
+                Message::Lobby { .. } => {}
+                Message::GameStarted {
+                    your_color,
+                    time_control,
+                    variant,
+                    start_fen,
+                    chess960,
+                    moves,
+                    clock_ms,
+                    token,
+                } => {
+                    println!(
+                        "Playing {}, resume this game with --resume {}",
+                        your_color, token
+                    );
+                    let variant = find_variant(&variant).map_err(|err| format!("got {}", err))?;
+                    let agreement = Agreement::new(
+                        your_color,
+                        time_control,
+                        variant,
+                        &start_fen,
+                        chess960,
+                        &moves,
+                        clock_ms,
+                    )?;
+                    connection.local_color = your_color;
+                    connection.server = Some(ServerLink {
+                        address: address.to_string(),
+                        rejoin: Message::Resume { token },
+                    });
+                    connection.start_reading(reader);
+                    return Ok((connection, agreement));
+                }
+                Message::Watching {
+                    time_control,
+                    variant,
+                    start_fen,
+                    chess960,
+                    moves,
+                    clock_ms,
+                } => {
+                    println!("Watching the game");
+                    let variant = find_variant(&variant).map_err(|err| format!("got {}", err))?;
+                    let agreement = Agreement::new(
+                        PieceColor::Light,
+                        time_control,
+                        variant,
+                        &start_fen,
+                        chess960,
+                        &moves,
+                        clock_ms,
+                    )?;
+                    connection.spectating = true;
+                    connection.server = Some(ServerLink {
+                        address: address.to_string(),
+                        rejoin: message,
+                    });
+                    connection.start_reading(reader);
+                    return Ok((connection, agreement));
+                }
+                Message::Reject { reason } => {
+                    return Err(format!("the server refused: {}", reason))
+                }
+                other => return Err(format!("unexpected message {:?}", other)),
+            }
+        }
+    }
+
+    /// Connect to `address` and say hello
+    fn open(address: &str) -> Result<(Self, BufReader<TcpStream>), String> {
+        let stream = open_stream(address)?;
+        // The color is only known after the handshake
+        let (mut connection, reader) = Self::new(stream, PieceColor::Light)?;
+        connection.send(&Message::Hello {
+            version: PROTOCOL_VERSION,
+        })?;
+        Ok((connection, reader))
+    }
+
+    fn new(
+        stream: TcpStream,
+        local_color: PieceColor,
+    ) -> Result<(Self, BufReader<TcpStream>), String> {
+        let reader = BufReader::new(
+            stream
+                .try_clone()
+                .map_err(|err| format!("could not use the connection: {}", err))?,
+        );
+        // Replaced when the reader thread starts
+        let (_, receiver) = mpsc::channel();
+        let connection = Self {
+            local_color,
+            stream,
+            incoming: Mutex::new(receiver),
+            spectating: false,
+            server: None,
+        };
+        Ok((connection, reader))
+    }
+
+    /// Read messages on a background thread from now on
+    fn start_reading(&mut self, reader: BufReader<TcpStream>) {
+        let (sender, receiver) = mpsc::channel();
+        self.incoming = Mutex::new(receiver);
+        let server = self.server.clone();
+        thread::spawn(move || read_messages(reader, sender, server));
+    }
+
+    pub fn send(&mut self, message: &Message) -> Result<(), String> {
+        write_message(&mut self.stream, message)
+    }
+}
+
+fn read_messages(
+    mut reader: BufReader<TcpStream>,
+    sender: Sender<Incoming>,
+    server: Option<ServerLink>,
+) {
+    loop {
+        let err = match read_message(&mut reader) {
+            Ok(message) => {
+                if sender.send(Incoming::Message(message)).is_err() {
+                    return;
+                }
+                continue;
+            }
+            Err(err) => err,
+        };
+
+        // Games on a server survive dropped connections
+        let server = match &server {
+            Some(server) => server,
+            None => {
+                let _ = sender.send(Incoming::Closed(err));
+                return;
+            }
+        };
+        if sender.send(Incoming::Lost).is_err() {
+            return;
+        }
+        match reconnect(server) {
+            Ok((new_reader, incoming)) => {
+                reader = new_reader;
+                if sender.send(incoming).is_err() {
+                    return;
+                }
+            }
+            Err(err) => {
+                let _ = sender.send(Incoming::Closed(err));
+                return;
+            }
+        }
+    }
+}
+
+/// Keep trying to resume the game on the server for a while
+fn reconnect(server: &ServerLink) -> Result<(BufReader<TcpStream>, Incoming), String> {
+    let started = Instant::now();
+    let mut last_error = String::new();
+    while started.elapsed() < RECONNECT_WINDOW {
+        thread::sleep(RECONNECT_INTERVAL);
+        match resume(server) {
+            Ok(resumed) => return Ok(resumed),
+            Err(err) => last_error = err,
+        }
+    }
+    Err(format!("could not get back into the game: {}", last_error))
+}
+
+fn resume(server: &ServerLink) -> Result<(BufReader<TcpStream>, Incoming), String> {
+    let mut stream = open_stream(&server.address)?;
+    let mut reader = BufReader::new(
+        stream
+            .try_clone()
+            .map_err(|err| format!("could not use the connection: {}", err))?,
+    );
+    write_message(
+        &mut stream,
+        &Message::Hello {
+            version: PROTOCOL_VERSION,
+        },
+    )?;
+    write_message(&mut stream, &server.rejoin)?;
+    loop {
+        match read_message(&mut reader)? {
+            Message::GameStarted {
+                moves, clock_ms, ..
+            }
+            | Message::Watching {
+                moves, clock_ms, ..
+            } => {
+                let incoming = Incoming::Reconnected {
+                    stream,
+                    moves,
+                    clock_ms,
+                };
+                return Ok((reader, incoming));
+            }
+            Message::Reject { reason } => return Err(reason),
+            // The game ended while we were away
+            message @ Message::GameOver { .. } => return Ok((reader, Incoming::Message(message))),
+            _ => {}
+        }
+    }
+}
+
+fn open_stream(address: &str) -> Result<TcpStream, String> {
+    let address = with_default_port(address);
+    let socket_address = address
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addresses| addresses.next())
+        .ok_or_else(|| format!("could not resolve {}", address))?;
+    let stream = TcpStream::connect_timeout(&socket_address, CONNECT_TIMEOUT)
+        .map_err(|err| format!("could not connect to {}: {}", address, err))?;
+    // Moves are tiny, send them right away
+    stream
+        .set_nodelay(true)
+        .map_err(|err| format!("could not use the connection: {}", err))?;
+    Ok(stream)
+}
+
+/// Add `DEFAULT_PORT` to addresses without a port
+pub fn with_default_port(address: &str) -> String {
+    if address.contains(':') {
+        address.to_string()
+    } else {
+        format!("{}:{}", address, DEFAULT_PORT)
+    }
+}
+
+pub fn read_message(reader: &mut impl BufRead) -> Result<Message, String> {
+    let mut line = String::new();
+    let read = reader
+        .read_line(&mut line)
+        .map_err(|err| format!("connection lost: {}", err))?;
+    if read == 0 {
+        return Err("the other side left".to_string());
+    }
+    ron::from_str(line.trim()).map_err(|err| format!("invalid message: {}", err))
+}
+
+pub fn write_message(stream: &mut TcpStream, message: &Message) -> Result<(), String> {
+    let line = ron::to_string(message).map_err(|err| err.to_string())?;
+    writeln!(stream, "{}", line)
+        .and_then(|_| stream.flush())
+        .map_err(|err| format!("could not send: {}", err))
+}
+
+/// Check a move received as ply `ply` against our own `game`, the position after it has to be
+/// `fen_after`
+pub fn check_move(game: &Game, ply: usize, text: &str, fen_after: &str) -> Result<Move, String> {
+    if ply != game.moves.len() {
+        return Err(format!(
+            "got move {} for ply {}, expected ply {}",
+            text,
+            ply,
+            game.moves.len()
+        ));
+    }
+    let mv = parse_uci(&game.position, text)?;
+    let mut after = game.position.clone();
+    after.make_move(&mv);
+    if after.to_fen() != fen_after {
+        return Err(format!(
+            "after {} the boards differ: {} here, {} there",
+            text,
+            after.to_fen(),
+            fen_after
+        ));
+    }
+    Ok(mv)
+}
+
+/// Tell the other side about resigning, offers and chat messages from this machine
+fn send_local_actions(mut connection: ResMut<Connection>, mut actions: EventReader<GameAction>) {
+    for GameAction { color, action } in actions.iter() {
+        if connection.spectating || *color != connection.local_color {
+            continue;
+        }
+        let message = Message::Action {
+            color: *color,
+            action: action.clone(),
+        };
+        if let Err(err) = connection.send(&message) {
+            warn!("{}", err);
+        }
+    }
+}
+
+/// Tell the other side about moves made on this machine
+fn send_local_moves(
+    mut connection: ResMut<Connection>,
+    mut game: ResMut<Game>,
+    clock: Res<Clock>,
+    mut moves: EventReader<MoveMade>,
+) {
+    for MoveMade { color, .. } in moves.iter() {
+        if connection.spectating || *color != connection.local_color {
+            continue;
+        }
+        let ply = game.moves.len() - 1;
+        let clock_ms = clock
+            .remaining(*color)
+            .map(|seconds| (seconds * 1000.0) as u32);
+        if let Err(err) = send_move(&mut connection, &game, ply, clock_ms) {
+            if connection.server.is_some() {
+                // The reader thread reconnects, the move is sent again once we are back
+                warn!("{}", err);
+            } else {
+                error!("{}", err);
+                game.end(GameResult::aborted("connection lost"));
+            }
+        }
+    }
+}
+
+fn send_move(
+    connection: &mut Connection,
+    game: &Game,
+    ply: usize,
+    clock_ms: Option<u32>,
+) -> Result<(), String> {
+    let before = game.position_before(ply);
+    let mut after = before.clone();
+    let mv = game.moves[ply].mv;
+    after.make_move(&mv);
+    connection.send(&Message::Move {
+        ply,
+        uci: uci(&before, &mv),
+        fen_after: after.to_fen(),
+        clock_ms,
+    })
+}
+
+/// Play the other side's moves after checking that both boards agree
+fn receive_messages(
+    mut connection: ResMut<Connection>,
+    mut game: ResMut<Game>,
+    mut clock: ResMut<Clock>,
+    mut requests: EventWriter<MoveRequest>,
+    mut actions: EventWriter<GameAction>,
+) {
+    loop {
+        let incoming = match connection.incoming.lock().unwrap().try_recv() {
+            Ok(incoming) => incoming,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Incoming::Closed("connection lost".to_string()),
+        };
+        let message = match incoming {
+            Incoming::Message(message) => message,
+            Incoming::Lost => {
+                warn!("Lost the connection to the server, reconnecting");
+                continue;
+            }
+            Incoming::Reconnected {
+                stream,
+                moves,
+                clock_ms,
+            } => {
+                info!("Back in the game");
+                connection.stream = stream;
+                if let Err(reason) = catch_up(&mut connection, &game, &moves, &mut requests) {
+                    error!("Out of sync: {}", reason);
+                    game.end(GameResult::aborted("the boards are out of sync"));
+                }
+                if let Some(clock_ms) = clock_ms {
+                    set_clocks(&mut clock, clock_ms);
+                }
+                // Moves that follow are checked once the missed ones have been played
+                return;
+            }
+            Incoming::Closed(reason) => {
+                game.end(GameResult::aborted(&reason));
+                return;
+            }
+        };
+
+        match message {
+            Message::Move {
+                ply,
+                uci,
+                fen_after,
+                clock_ms,
+            } => {
+                let mover = game.position.turn;
+                let checked = if !connection.spectating && mover == connection.local_color {
+                    Err(format!("got move {} while it is our turn", uci))
+                } else {
+                    check_move(&game, ply, &uci, &fen_after)
+                };
+                match checked {
+                    Ok(mv) => {
+                        if let Some(ms) = clock_ms {
+                            clock.set_remaining(mover, ms as f32 / 1000.0);
+                        }
+                        requests.send(MoveRequest(mv));
+                        // Spectators can get several moves at once, the next one is checked
+                        // after this one has been played
+                        return;
+                    }
+                    Err(reason) => {
+                        error!("Out of sync: {}", reason);
+                        // A spectator's board doesn't matter to the players
+                        if !connection.spectating {
+                            let _ = connection.send(&Message::Desync {
+                                reason: reason.clone(),
+                            });
+                        }
+                        game.end(GameResult::aborted("the boards are out of sync"));
+                    }
+                }
+            }
+            Message::Desync { reason } => {
+                error!("The other player is out of sync: {}", reason);
+                game.end(GameResult::aborted("the boards are out of sync"));
+            }
+            Message::Clock { white_ms, black_ms } => set_clocks(&mut clock, [white_ms, black_ms]),
+            Message::GameOver { result } => game.end(result),
+            Message::OpponentLeft { grace_seconds } => warn!(
+                "The opponent lost their connection, they have {} seconds to come back",
+                grace_seconds
+            ),
+            Message::OpponentBack => info!("The opponent is back"),
+            Message::Action { color, action } => {
+                // An accepted takeback changes the board, moves that follow are checked after it
+                let accepted = action == Action::Accept;
+                if connection.spectating || color != connection.local_color {
+                    actions.send(GameAction { color, action });
+                }
+                if accepted {
+                    return;
+                }
+            }
+            // Lobby updates don't matter once we are playing
+            Message::Lobby { .. } => {}
+            other => warn!("Unexpected message {:?}", other),
+        }
+    }
+}
+
+fn set_clocks(clock: &mut Clock, [white_ms, black_ms]: [u32; 2]) {
+    clock.set_remaining(PieceColor::Light, white_ms as f32 / 1000.0);
+    clock.set_remaining(PieceColor::Dark, black_ms as f32 / 1000.0);
+}
+
+/// After reconnecting, play the moves the server has that we missed, or send it the ones it
+/// missed from us
+fn catch_up(
+    connection: &mut Connection,
+    game: &Game,
+    server_moves: &[String],
+    requests: &mut EventWriter<MoveRequest>,
+) -> Result<(), String> {
+    let local_moves = uci_moves(&game.start, &game.move_list());
+    let common = local_moves.len().min(server_moves.len());
+    if local_moves[..common] != server_moves[..common] {
+        return Err("the server has different moves".to_string());
+    }
+    let missed = parse_uci_moves(&game.position, &server_moves[common..])
+        .map_err(|err| format!("got {}", err))?;
+    for mv in missed {
+        requests.send(MoveRequest(mv));
+    }
+    for ply in common..local_moves.len() {
+        send_move(connection, game, ply, None)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A host playing white and a client connected to it over localhost
+    fn connected_pair() -> (Connection, Connection, Agreement) {
+        connected_pair_with(Game::default())
+    }
+
+    /// A host playing white in `game` and a client connected to it over localhost
+    fn connected_pair_with(game: Game) -> (Connection, Connection, Agreement) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let host = thread::spawn(move || {
+            Connection::accept(listener, PieceColor::Light, None, &game).unwrap()
+        });
+        let (client, agreement) = Connection::connect(&address).unwrap();
+        (host.join().unwrap(), client, agreement)
+    }
+
+    fn next_message(connection: &Connection) -> Message {
+        let incoming = connection.incoming.lock().unwrap();
+        match incoming.recv_timeout(Duration::from_secs(5)) {
+            Ok(Incoming::Message(message)) => message,
+            Ok(_) => panic!("the connection closed"),
+            Err(err) => panic!("no message arrived: {}", err),
+        }
+    }
+
+    fn received_move(connection: &Connection) -> (usize, String, String) {
+        match next_message(connection) {
+            Message::Move {
+                ply,
+                uci,
+                fen_after,
+                ..
+            } => (ply, uci, fen_after),
+            other => panic!("expected a move, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn handshake_agrees_on_colors_and_position() {
+        let (host, client, agreement) = connected_pair();
+        assert_eq!(host.local_color, PieceColor::Light);
+        assert_eq!(client.local_color, PieceColor::Dark);
+        assert_eq!(agreement.local_color, PieceColor::Dark);
+        assert_eq!(agreement.time_control, None);
+        assert_eq!(
+            agreement.game.position.to_fen(),
+            Game::default().position.to_fen()
+        );
+    }
+
+    #[test]
+    fn moves_reach_the_other_side() {
+        let (mut host, client, agreement) = connected_pair();
+        let mut game = Game::default();
+        let mv = parse_uci(&game.position, "e2e4").unwrap();
+        game.play(&mv);
+        send_move(&mut host, &game, 0, None).unwrap();
+
+        let (ply, uci, fen_after) = received_move(&client);
+        assert_eq!(check_move(&agreement.game, ply, &uci, &fen_after), Ok(mv));
+    }
+
+    #[test]
+    fn chess960_castling_is_understood_by_both_sides() {
+        // Position 518 has the classical setup, only the flag tells it apart
+        let start = Position::chess960(518).unwrap();
+        let texts = ["e2e4", "e7e5", "g1f3", "g8f6", "f1c4", "f8c5", "d2d3"].map(String::from);
+        let moves = parse_uci_moves(&start, &texts).unwrap();
+        let game = Game::from_moves(start.clone(), &moves);
+        let (host, mut client, agreement) = connected_pair_with(Game::from_moves(start, &moves));
+        assert!(agreement.game.start.chess960);
+
+        // Black castles on the client, written as the king taking its rook
+        let mut client_game = agreement.game;
+        let castling = client_game.position.move_to((7, 4, 0), (7, 6, 0)).unwrap();
+        client_game.play(&castling);
+        send_move(&mut client, &client_game, 7, None).unwrap();
+
+        let (ply, uci, fen_after) = received_move(&host);
+        assert_eq!(uci, "e8h8");
+        assert_eq!(check_move(&game, ply, &uci, &fen_after), Ok(castling));
+    }
+
+    #[test]
+    fn desyncs_and_illegal_moves_are_detected() {
+        let (mut host, mut client, agreement) = connected_pair();
+        let moves = [
+            (
+                0,
+                "e2e4",
+                "8/8/8/8/8/8/8/K6k w - - 0 1",
+                "the boards differ",
+            ),
+            (0, "e2e5", "", "not legal"),
+            (3, "e2e4", "", "expected ply 0"),
+        ];
+        for (ply, uci, fen_after, _) in moves {
+            host.send(&Message::Move {
+                ply,
+                uci: uci.to_string(),
+                fen_after: fen_after.to_string(),
+                clock_ms: None,
+            })
+            .unwrap();
+        }
+        for (_, _, _, expected) in moves {
+            let (ply, uci, fen_after) = received_move(&client);
+            let err = check_move(&agreement.game, ply, &uci, &fen_after).unwrap_err();
+            assert!(err.contains(expected), "{}", err);
+        }
+
+        // The side that noticed tells the other one
+        client
+            .send(&Message::Desync {
+                reason: "the boards differ".to_string(),
+            })
+            .unwrap();
+        assert!(matches!(next_message(&host), Message::Desync { .. }));
+    }
+}
@@ -0,0 +1,577 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    pieces::{PieceColor, PieceType},
+    rules::{BoardShape, CastlingSide, GameResult, Move, Position},
+    variants::{find_variant, Standard, Variant},
+};
+
+/// Name of a square of the usual board, like `e4`
+pub fn square_name(square: (u8, u8, u8)) -> String {
+    BoardShape::STANDARD.square_name(square)
+}
+
+pub fn parse_square(name: &str) -> Option<(u8, u8, u8)> {
+    BoardShape::STANDARD.parse_square(name)
+}
+
+/// Standard algebraic notation of a legal move, like `Nbd7`, `exd5`, `O-O` or `e8=Q+`
+pub fn san(position: &Position, mv: &Move) -> String {
+    let board = position.variant.board();
+    let mut san = match position.castling_side(mv) {
+        _ if mv.drop.is_some() => drop_text(position, mv),
+        Some(CastlingSide::King) => "O-O".to_string(),
+        Some(CastlingSide::Queen) => "O-O-O".to_string(),
+        None => {
+            let piece = position
+                .piece_at(mv.from)
+                .expect("there is a piece to move");
+            let is_capture = position.piece_at(mv.to).is_some()
+                || piece.piece_type == PieceType::Pawn && Some(mv.to) == position.en_passant;
+            // Other pieces of the same type that can reach the same square
+            let others = || -> Vec<Move> {
+                position
+                    .legal_moves()
+                    .into_iter()
+                    .filter(|other| {
+                        other.to == mv.to
+                            && other.from != mv.from
+                            && position.piece_at(other.from).map(|other| other.piece_type)
+                                == Some(piece.piece_type)
+                    })
+                    .collect()
+            };
+            let mut san = String::new();
+            if piece.piece_type == PieceType::Pawn {
+                // Pawns on stacked boards reach a square from the level below as well, and the
+                // side armies of four-player chess capture along a file, so the file alone
+                // doesn't always tell pawns apart
+                let same_file = (is_capture || board.levels > 1)
+                    && others().iter().any(|other| other.from.1 == mv.from.1);
+                if same_file {
+                    san.push_str(&board.square_name(mv.from));
+                } else if is_capture {
+                    san.push(board.file_letter(mv.from.1));
+                }
+            } else {
+                san.push(piece.piece_type.letter());
+                let others = others();
+                if !others.is_empty() {
+                    // The rank alone doesn't tell squares on stacked boards apart
+                    if board.levels > 1 {
+                        san.push_str(&board.square_name(mv.from));
+                    } else if others.iter().all(|other| other.from.1 != mv.from.1) {
+                        san.push(board.file_letter(mv.from.1));
+                    } else if others.iter().all(|other| other.from.0 != mv.from.0) {
+                        san.push_str(&(mv.from.0 + 1).to_string());
+                    } else {
+                        san.push_str(&board.square_name(mv.from));
+                    }
+                }
+            }
+            if is_capture {
+                san.push('x');
+            }
+            san.push_str(&board.square_name(mv.to));
+            if let Some(promotion) = mv.promotion {
+                san.push('=');
+                san.push(promotion.letter());
+            }
+            san
+        }
+    };
+
+    let mut after = position.clone();
+    after.make_move(mv);
+    if after.is_in_check(after.turn) {
+        san.push(if after.legal_moves().is_empty() {
+            '#'
+        } else {
+            '+'
+        });
+    }
+    san
+}
+
+/// Find the legal move written in standard algebraic notation
+pub fn parse_san(position: &Position, text: &str) -> Result<Move, String> {
+    let san = text.trim_end_matches(['+', '#', '!', '?']);
+    let legal_moves = position.legal_moves();
+
+    let castling = match san {
+        "O-O" | "0-0" => Some(CastlingSide::King),
+        "O-O-O" | "0-0-0" => Some(CastlingSide::Queen),
+        _ => None,
+    };
+    if let Some(side) = castling {
+        return legal_moves
+            .into_iter()
+            .find(|mv| position.castling_side(mv) == Some(side))
+            .ok_or_else(|| format!("{} is not legal here", text));
+    }
+    if san.contains('@') {
+        return parse_drop(position, text);
+    }
+
+    let (rest, promotion) = match san.char_indices().last() {
+        Some((index, c)) if c.is_ascii_uppercase() => {
+            let promotion = PieceType::from_letter(c)
+                .ok_or_else(|| format!("unknown promotion in {}", text))?;
+            (san[..index].trim_end_matches('='), Some(promotion))
+        }
+        _ => (san, None),
+    };
+    let rest = rest.replace('x', "");
+    // The target is the longest square name at the end, in front of it are the piece and the
+    // square, file or rank it comes from
+    let board = position.variant.board();
+    let (index, to) = rest
+        .char_indices()
+        .find_map(|(index, _)| Some((index, board.parse_square(&rest[index..])?)))
+        .ok_or_else(|| format!("{} has no target square", text))?;
+    let (piece_type, disambiguation) = match rest[..index].chars().next() {
+        // A pawn on stacked boards comes from a square named level first, like `Ce2De3`
+        _ if board.parse_square(&rest[..index]).is_some() => (PieceType::Pawn, &rest[..index]),
+        Some(c) if c.is_ascii_uppercase() => (
+            PieceType::from_letter(c).ok_or_else(|| format!("unknown piece in {}", text))?,
+            &rest[1..index],
+        ),
+        _ => (PieceType::Pawn, &rest[..index]),
+    };
+    let (file, rank, level) = match board.parse_square(disambiguation) {
+        Some((x, y, z)) => (Some(y), Some(x), Some(z)),
+        None => {
+            let file = disambiguation
+                .chars()
+                .find(char::is_ascii_lowercase)
+                .and_then(|c| board.parse_file(c));
+            let digits: String = disambiguation
+                .chars()
+                .filter(char::is_ascii_digit)
+                .collect();
+            (
+                file,
+                digits.parse::<u8>().ok().map(|rank| rank.wrapping_sub(1)),
+                None,
+            )
+        }
+    };
+
+    let mut candidates = legal_moves.into_iter().filter(|mv| {
+        mv.to == to
+            && mv.promotion == promotion
+            && position.castling_side(mv).is_none()
+            && position.piece_at(mv.from).map(|piece| piece.piece_type) == Some(piece_type)
+            && file.map_or(true, |file| mv.from.1 == file)
+            && rank.map_or(true, |rank| mv.from.0 == rank)
+            && level.map_or(true, |level| mv.from.2 == level)
+    });
+    match (candidates.next(), candidates.next()) {
+        (Some(mv), None) => Ok(mv),
+        (None, _) => Err(format!("{} is not legal here", text)),
+        (Some(_), Some(_)) => Err(format!("{} is ambiguous", text)),
+    }
+}
+
+/// Long algebraic notation used by UCI, like `e2e4` or `e7e8q`. Castling is written as the king
+/// moving two squares, or onto its rook in Chess960 positions.
+pub fn uci(position: &Position, mv: &Move) -> String {
+    if mv.drop.is_some() {
+        return drop_text(position, mv);
+    }
+    let to = match position.castling_destination(mv) {
+        Some((king, _)) if !position.chess960 => king,
+        _ => mv.to,
+    };
+    let board = position.variant.board();
+    let mut uci = board.square_name(mv.from) + &board.square_name(to);
+    if let Some(promotion) = mv.promotion {
+        uci.push(promotion.letter().to_ascii_lowercase());
+    }
+    uci
+}
+
+/// Find the legal move written in long algebraic notation, both ways of writing castling work
+/// except in Chess960 where the king can also step onto the square it castles to
+pub fn parse_uci(position: &Position, text: &str) -> Result<Move, String> {
+    let text = text.trim();
+    if text.contains('@') {
+        return parse_drop(position, text);
+    }
+    let board = position.variant.board();
+    let invalid = || format!("invalid move {}", text);
+    let (from, rest) = board.split_square(text).ok_or_else(invalid)?;
+    let (to, rest) = board.split_square(rest).ok_or_else(invalid)?;
+    let promotion = rest.chars().next().and_then(PieceType::from_letter);
+    position
+        .legal_moves()
+        .into_iter()
+        .find(|mv| {
+            mv.from == from
+                && mv.promotion == promotion
+                && (mv.to == to
+                    || !position.chess960
+                        && position.castling_destination(mv).map(|(king, _)| king) == Some(to))
+        })
+        .ok_or_else(|| format!("{} is not legal here", text))
+}
+
+/// A crazyhouse drop like `N@f3`, written the same in SAN and UCI
+fn drop_text(position: &Position, mv: &Move) -> String {
+    let piece_type = mv.drop.expect("the move is a drop");
+    let square = position.variant.board().square_name(mv.to);
+    format!("{}@{}", piece_type.letter(), square)
+}
+
+/// Find the legal drop written like `N@f3`, a pawn drop may leave out the `P`
+fn parse_drop(position: &Position, text: &str) -> Result<Move, String> {
+    let (piece, square) = text
+        .trim_end_matches(['+', '#', '!', '?'])
+        .split_once('@')
+        .ok_or_else(|| format!("invalid drop {}", text))?;
+    let piece_type = match piece {
+        "" => PieceType::Pawn,
+        letter => letter
+            .chars()
+            .next()
+            .and_then(PieceType::from_letter)
+            .filter(|_| letter.len() == 1)
+            .ok_or_else(|| format!("unknown piece in {}", text))?,
+    };
+    let to = position
+        .variant
+        .board()
+        .parse_square(square)
+        .ok_or_else(|| format!("invalid square in {}", text))?;
+    let mv = Move::drop(piece_type, to);
+    if position.is_legal(&mv) {
+        Ok(mv)
+    } else {
+        Err(format!("{} is not legal here", text))
+    }
+}
+
+/// Write moves played from `start` in UCI notation
+pub fn uci_moves(start: &Position, moves: &[Move]) -> Vec<String> {
+    let mut position = start.clone();
+    moves
+        .iter()
+        .map(|mv| {
+            let text = uci(&position, mv);
+            position.make_move(mv);
+            text
+        })
+        .collect()
+}
+
+/// Read moves in UCI notation played one after the other from `start`
+pub fn parse_uci_moves(start: &Position, moves: &[String]) -> Result<Vec<Move>, String> {
+    let mut position = start.clone();
+    moves
+        .iter()
+        .map(|text| {
+            let mv = parse_uci(&position, text)?;
+            position.make_move(&mv);
+            Ok(mv)
+        })
+        .collect()
+}
+
+/// A game read from PGN
+pub struct Pgn {
+    pub tags: Vec<(String, String)>,
+    pub start: Position,
+    pub moves: Vec<Move>,
+    /// Result token at the end of the movetext, like `1-0`, if the game is finished
+    pub result: Option<String>,
+}
+
+/// Parse the first game of a PGN file, the moves are checked while they are read
+pub fn parse_pgn(text: &str) -> Result<Pgn, String> {
+    let mut tags = Vec::new();
+    let mut movetext = String::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('[') && line.ends_with(']') {
+            if !movetext.trim().is_empty() {
+                // The next game starts
+                break;
+            }
+            let inner = &line[1..line.len() - 1];
+            if let Some((name, value)) = inner.split_once(' ') {
+                tags.push((
+                    name.to_string(),
+                    value.trim().trim_matches('"').replace("\\\"", "\""),
+                ));
+            }
+        } else if !line.starts_with('%') {
+            movetext.push_str(line);
+            movetext.push('\n');
+        }
+    }
+
+    let mut chess960 = false;
+    let variant: &'static dyn Variant = match tags.iter().find(|(name, _)| name == "Variant") {
+        Some((_, name)) if ["chess960", "fischerandom"].contains(&name.to_lowercase().as_str()) => {
+            chess960 = true;
+            &Standard
+        }
+        Some((_, name)) => find_variant(name)?,
+        None => &Standard,
+    };
+    let mut start = match tags.iter().find(|(name, _)| name == "FEN") {
+        Some((_, fen)) => Position::from_variant_fen(variant, fen)?,
+        None => Position::starting(variant),
+    };
+    start.chess960 |= chess960;
+
+    let mut position = start.clone();
+    let mut moves = Vec::new();
+    let mut result = None;
+    for token in movetext_tokens(&movetext) {
+        match token.as_str() {
+            "1-0" | "0-1" | "1/2-1/2" => {
+                result = Some(token);
+                break;
+            }
+            "*" => break,
+            san => {
+                let mv = parse_san(&position, san)
+                    .map_err(|err| format!("move {}: {}", position.fullmove_number, err))?;
+                position.make_move(&mv);
+                moves.push(mv);
+            }
+        }
+    }
+
+    Ok(Pgn {
+        tags,
+        start,
+        moves,
+        result,
+    })
+}
+
+/// Split movetext into moves and the result, dropping move numbers, comments, variations and
+/// annotation glyphs
+fn movetext_tokens(movetext: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut comment = false;
+    let mut line_comment = false;
+    let mut variation_depth = 0;
+    for c in movetext.chars() {
+        if line_comment {
+            line_comment = c != '\n';
+            continue;
+        }
+        if comment {
+            comment = c != '}';
+            continue;
+        }
+        match c {
+            '{' => comment = true,
+            ';' => line_comment = true,
+            '(' => variation_depth += 1,
+            ')' => variation_depth -= 1,
+            c if variation_depth > 0 || c.is_whitespace() => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+                continue;
+            }
+            c => {
+                token.push(c);
+                continue;
+            }
+        }
+        if !token.is_empty() {
+            tokens.push(std::mem::take(&mut token));
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+
+    tokens
+        .into_iter()
+        .filter_map(|token| {
+            // Strip move numbers like `12.` or `12...`, which may be glued to the move
+            let token = match token.rfind('.') {
+                Some(index) => &token[index + 1..],
+                None => &token,
+            };
+            (!token.is_empty() && !token.starts_with('$')).then(|| token.to_string())
+        })
+        .collect()
+}
+
+/// Today's date for the PGN `Date` tag, like `2022.01.09`, or `????.??.??` if the system clock
+/// is off
+pub fn pgn_date() -> String {
+    let seconds = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => elapsed.as_secs() as i64,
+        Err(_) => return "????.??.??".to_string(),
+    };
+    // Days since 1970-01-01 to year, month and day, after Howard Hinnant's `civil_from_days`
+    let days = seconds / 86_400 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+/// Write a game as PGN, `moves` are played from `start`
+pub fn write_pgn(
+    tags: &[(String, String)],
+    start: &Position,
+    moves: &[Move],
+    result: Option<&GameResult>,
+) -> String {
+    let mut pgn = String::new();
+    for (name, value) in tags {
+        pgn.push_str(&format!("[{} \"{}\"]\n", name, value.replace('"', "\\\"")));
+    }
+    let result = result.map_or("*", GameResult::pgn);
+    pgn.push_str(&format!("[Result \"{}\"]\n", result));
+    if start.chess960 {
+        pgn.push_str("[Variant \"Chess960\"]\n");
+    } else if !start.is_standard() {
+        pgn.push_str(&format!("[Variant \"{}\"]\n", start.variant.name()));
+    }
+    if start.to_fen() != start.variant.starting_fen() {
+        pgn.push_str("[SetUp \"1\"]\n");
+        pgn.push_str(&format!("[FEN \"{}\"]\n", start.to_fen()));
+    }
+    pgn.push('\n');
+
+    let mut position = start.clone();
+    let mut line = String::new();
+    for (index, mv) in moves.iter().enumerate() {
+        let mut token = String::new();
+        if position.turn == PieceColor::Light {
+            token.push_str(&format!("{}. ", position.fullmove_number));
+        } else if index == 0 {
+            token.push_str(&format!("{}... ", position.fullmove_number));
+        }
+        token.push_str(&san(&position, mv));
+        position.make_move(mv);
+
+        if !line.is_empty() && line.len() + token.len() + 1 > 80 {
+            pgn.push_str(&line);
+            pgn.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    if !line.is_empty() && line.len() + result.len() + 1 > 80 {
+        pgn.push_str(&line);
+        pgn.push('\n');
+        line.clear();
+    }
+    if !line.is_empty() {
+        line.push(' ');
+    }
+    line.push_str(result);
+    pgn.push_str(&line);
+    pgn.push('\n');
+    pgn
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pieces::Piece,
+        variants::{FourPlayer, Raumschach},
+    };
+
+    /// A position of `variant` with only `pieces` on the board and `turn` to move
+    fn position_with(
+        variant: &'static dyn Variant,
+        turn: PieceColor,
+        pieces: &[(PieceColor, PieceType, (u8, u8, u8))],
+    ) -> Position {
+        let mut position = Position::starting(variant);
+        position.pieces = pieces
+            .iter()
+            .map(|&(color, piece_type, (x, y, z))| Piece {
+                color,
+                piece_type,
+                x,
+                y,
+                z,
+            })
+            .collect();
+        position.turn = turn;
+        position.castling = Default::default();
+        position.en_passant = None;
+        position
+    }
+
+    /// SAN of the legal moves to `to`, each read back as the same move
+    fn round_trip_to(position: &Position, to: (u8, u8, u8)) -> Vec<String> {
+        position
+            .legal_moves()
+            .into_iter()
+            .filter(|mv| mv.to == to)
+            .map(|mv| {
+                let text = san(position, &mv);
+                assert_eq!(parse_san(position, &text), Ok(mv), "{}", text);
+                text
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pawns_of_one_file_taking_on_the_same_square_are_told_apart() {
+        use PieceColor::*;
+        use PieceType::*;
+        // Blue pawns take towards the ranks on either side, both of these take on c6
+        let position = position_with(
+            &FourPlayer::FREE_FOR_ALL,
+            Blue,
+            &[
+                (Light, King, (0, 7, 0)),
+                (Blue, King, (6, 0, 0)),
+                (Dark, King, (13, 7, 0)),
+                (Green, King, (7, 13, 0)),
+                (Blue, Pawn, (4, 1, 0)),
+                (Blue, Pawn, (6, 1, 0)),
+                (Dark, Knight, (5, 2, 0)),
+            ],
+        );
+        assert_eq!(round_trip_to(&position, (5, 2, 0)), ["b5xc6", "b7xc6"]);
+    }
+
+    #[test]
+    fn pawns_on_two_levels_reaching_the_same_square_are_told_apart() {
+        use PieceColor::*;
+        use PieceType::*;
+        // One pawn moves forward on level B, the other one up from level A, both to Bc3
+        let position = position_with(
+            &Raumschach,
+            Light,
+            &[
+                (Light, King, (0, 0, 0)),
+                (Dark, King, (4, 4, 4)),
+                (Light, Pawn, (1, 2, 1)),
+                (Light, Pawn, (2, 2, 0)),
+            ],
+        );
+        assert_eq!(round_trip_to(&position, (2, 2, 1)), ["Bc2Bc3", "Ac3Bc3"]);
+    }
+}
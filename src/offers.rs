@@ -0,0 +1,179 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game::{Game, GameSystem, MoveMade, ResetBoardEvent},
+    pieces::PieceColor,
+    players::Players,
+    rules::GameResult,
+};
+
+/// Resigning, draw and takeback offers and the chat log, for local and network games
+pub struct OffersPlugin;
+impl Plugin for OffersPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingOffer>()
+            .init_resource::<ChatLog>()
+            .add_event::<GameAction>()
+            .add_system(
+                apply_actions
+                    .label(OfferSystem::ApplyActions)
+                    .before(GameSystem::ApplyMoves),
+            )
+            .add_system(expire_offers.after(GameSystem::ApplyMoves));
+    }
+}
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum OfferSystem {
+    ApplyActions,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Offer {
+    Draw,
+    /// Take back the last move of the side asking for it
+    Takeback,
+}
+
+/// Something a player does besides moving
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Action {
+    Resign,
+    Offer(Offer),
+    /// Agree to the opponent's pending offer
+    Accept,
+    Decline,
+    Chat(String),
+}
+
+/// Sent when `color` does something, from the chat panel or from the other side of a network game
+pub struct GameAction {
+    pub color: PieceColor,
+    pub action: Action,
+}
+
+/// The offer waiting for an answer, playing on instead of answering declines it
+#[derive(Default)]
+pub struct PendingOffer(pub Option<(PieceColor, Offer)>);
+
+/// Chat messages and what happened in the game, oldest first
+#[derive(Default)]
+pub struct ChatLog(pub Vec<String>);
+
+impl ChatLog {
+    pub fn add(&mut self, line: String) {
+        self.0.push(line);
+    }
+}
+
+fn apply_actions(
+    mut game: ResMut<Game>,
+    players: Res<Players>,
+    mut pending: ResMut<PendingOffer>,
+    mut log: ResMut<ChatLog>,
+    mut actions: EventReader<GameAction>,
+    mut reset: EventWriter<ResetBoardEvent>,
+) {
+    for GameAction { color, action } in actions.iter() {
+        let color = *color;
+        if let Action::Chat(text) = action {
+            log.add(format!("{}: {}", color, text));
+            continue;
+        }
+        if game.is_over() {
+            continue;
+        }
+        match action {
+            Action::Resign => {
+                log.add(format!("{} resigns", color));
+                let result = game
+                    .position
+                    .variant
+                    .forfeit(&game.position, color, "resignation");
+                game.end(result);
+            }
+            Action::Offer(offer) => {
+                // A draw or takeback would have to be agreed by everyone else, which needs more
+                // than one player answering
+                if game.position.variant.colors().len() > 2 {
+                    log.add(format!(
+                        "{} has no draw or takeback offers",
+                        game.position.variant.name()
+                    ));
+                    continue;
+                }
+                if *offer == Offer::Takeback && takeback_plies(&game, color).is_none() {
+                    continue;
+                }
+                log.add(match offer {
+                    Offer::Draw => format!("{} offers a draw", color),
+                    Offer::Takeback => format!("{} asks to take back their move", color),
+                });
+                pending.0 = Some((color, *offer));
+                // Engines take moves back but play on for a win
+                if players.is_engine(color.opponent()) {
+                    let accepted = *offer == Offer::Takeback;
+                    answer_offer(&mut game, &mut pending, &mut log, &mut reset, accepted);
+                }
+            }
+            Action::Accept | Action::Decline => {
+                // Only the opponent of whoever made the offer can answer it
+                if pending.0.map(|(from, _)| from) != Some(color.opponent()) {
+                    continue;
+                }
+                let accepted = *action == Action::Accept;
+                answer_offer(&mut game, &mut pending, &mut log, &mut reset, accepted);
+            }
+            Action::Chat(_) => {}
+        }
+    }
+}
+
+fn answer_offer(
+    game: &mut Game,
+    pending: &mut PendingOffer,
+    log: &mut ChatLog,
+    reset: &mut EventWriter<ResetBoardEvent>,
+    accepted: bool,
+) {
+    let (from, offer) = match pending.0.take() {
+        Some(pending) => pending,
+        None => return,
+    };
+    let answer = if accepted { "accepts" } else { "declines" };
+    log.add(format!("{} {}", from.opponent(), answer));
+    if !accepted {
+        return;
+    }
+    match offer {
+        Offer::Draw => game.end(GameResult::draw("agreement")),
+        Offer::Takeback => {
+            if let Some(plies) = takeback_plies(game, from) {
+                game.take_back(plies);
+                reset.send(ResetBoardEvent);
+            }
+        }
+    }
+}
+
+/// How many moves to take back so `color` can play their last move again
+pub fn takeback_plies(game: &Game, color: PieceColor) -> Option<usize> {
+    let plies = if game.position.turn == color { 2 } else { 1 };
+    (plies <= game.moves.len()).then(|| plies)
+}
+
+fn expire_offers(
+    mut pending: ResMut<PendingOffer>,
+    mut log: ResMut<ChatLog>,
+    mut moves: EventReader<MoveMade>,
+) {
+    for MoveMade { color, .. } in moves.iter() {
+        if let Some((from, _)) = pending.0 {
+            if from != *color {
+                log.add(format!("{} plays on", color));
+                pending.0 = None;
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{assets::load_ron, pieces::PieceType, settings::Settings};
+
+/// Piece set used when no other one is chosen, also compiled in as a fallback
+pub const DEFAULT_PIECE_SET: &str = "classic";
+const DEFAULT_PIECE_SET_RON: &str = include_str!("../assets/piece_sets/classic.ron");
+
+/// A set of 3D piece models, loaded from `assets/piece_sets/<name>.ron`
+#[derive(Deserialize)]
+pub struct PieceSet {
+    pub name: String,
+    pub pieces: HashMap<PieceType, PieceModel>,
+}
+
+/// How a single piece type is built from meshes
+#[derive(Deserialize)]
+pub struct PieceModel {
+    /// Asset paths of the meshes making up the piece, they all share the same transform
+    pub meshes: Vec<String>,
+    /// Offset from the center of the square
+    #[serde(default)]
+    pub offset: [f32; 3],
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    /// Euler angles in degrees, applied in x, y, z order
+    #[serde(default)]
+    pub rotation: [f32; 3],
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+impl PieceModel {
+    fn transform(&self) -> Transform {
+        let [x, y, z] = self.rotation;
+        Transform {
+            translation: self.offset.into(),
+            rotation: Quat::from_euler(
+                EulerRot::XYZ,
+                x.to_radians(),
+                y.to_radians(),
+                z.to_radians(),
+            ),
+            scale: Vec3::splat(self.scale),
+        }
+    }
+}
+
+impl PieceSet {
+    /// Load the piece set `name` from the assets folder, falling back to the built-in set
+    pub fn load(name: &str) -> Self {
+        load_ron(&format!("piece_sets/{}.ron", name)).unwrap_or_default()
+    }
+}
+
+impl Default for PieceSet {
+    fn default() -> Self {
+        ron::from_str(DEFAULT_PIECE_SET_RON).expect("built-in piece set is valid")
+    }
+}
+
+/// Mesh handles of the active piece set, ready to be spawned
+pub struct PieceModels {
+    models: HashMap<PieceType, (Vec<Handle<Mesh>>, Transform)>,
+}
+
+impl PieceModels {
+    pub fn new(piece_set: &PieceSet, asset_server: &AssetServer) -> Self {
+        info!("Using piece set {}", piece_set.name);
+        let models = piece_set
+            .pieces
+            .iter()
+            .map(|(piece_type, model)| {
+                let meshes = model
+                    .meshes
+                    .iter()
+                    .map(|path| asset_server.load(path.as_str()))
+                    .collect();
+                (*piece_type, (meshes, model.transform()))
+            })
+            .collect();
+        Self { models }
+    }
+
+    /// Spawn the meshes of `piece_type` as children of a piece
+    pub fn spawn_meshes(
+        &self,
+        parent: &mut ChildBuilder,
+        piece_type: PieceType,
+        material: Handle<StandardMaterial>,
+    ) {
+        let (meshes, transform) = if let Some(model) = self.models.get(&piece_type) {
+            model
+        } else {
+            warn!("The piece set has no model for {:?}", piece_type);
+            return;
+        };
+        for mesh in meshes {
+            parent.spawn_bundle(PbrBundle {
+                mesh: mesh.clone(),
+                material: material.clone(),
+                transform: *transform,
+                ..Default::default()
+            });
+        }
+    }
+}
+
+impl FromWorld for PieceModels {
+    fn from_world(world: &mut World) -> Self {
+        let name = world
+            .get_resource::<Settings>()
+            .map_or(DEFAULT_PIECE_SET.to_string(), |settings| {
+                settings.piece_set.clone()
+            });
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+        PieceModels::new(&PieceSet::load(&name), asset_server)
+    }
+}
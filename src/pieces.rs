@@ -1,419 +1,197 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game::Game,
+    piece_set::PieceModels,
+    rules::Position,
+    theme::{Theme, Themes},
+};
 
 pub struct PiecesPlugin;
 impl Plugin for PiecesPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(create_pieces)
-            .add_system(move_pieces);
+        app.init_resource::<PieceModels>()
+            .init_resource::<PieceMaterials>()
+            .add_system(update_piece_models)
+            .add_system(gray_out_pieces);
     }
 }
 
-fn create_pieces(
+/// Piece type the meshes of a piece entity were spawned for
+#[derive(Component)]
+pub struct PieceModel(PieceType);
+
+/// Give new pieces their meshes, and swap them out when a pawn is promoted
+fn update_piece_models(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    models: Res<PieceModels>,
+    materials: Res<PieceMaterials>,
+    game: Res<Game>,
+    query: Query<(Entity, &Piece, Option<&PieceModel>, Option<&Children>), Changed<Piece>>,
 ) {
-    // Load all the meshes
-    let king_handle: Handle<Mesh> =
-        asset_server.load("models/chess/kit/pieces.glb#Mesh0/Primitive0");
-    let king_cross_handle: Handle<Mesh> =
-        asset_server.load("models/chess/kit/pieces.glb#Mesh1/Primitive0");
-    let pawn_handle: Handle<Mesh> =
-        asset_server.load("models/chess/kit/pieces.glb#Mesh2/Primitive0");
-    let knight_1_handle: Handle<Mesh> =
-        asset_server.load("models/chess/kit/pieces.glb#Mesh3/Primitive0");
-    let knight_2_handle: Handle<Mesh> =
-        asset_server.load("models/chess/kit/pieces.glb#Mesh4/Primitive0");
-    let rook_handle: Handle<Mesh> =
-        asset_server.load("models/chess/kit/pieces.glb#Mesh5/Primitive0");
-    let bishop_handle: Handle<Mesh> =
-        asset_server.load("models/chess/kit/pieces.glb#Mesh6/Primitive0");
-    let queen_handle: Handle<Mesh> =
-        asset_server.load("models/chess/kit/pieces.glb#Mesh7/Primitive0");
-
-    // Add some materials
-    let light_material = materials.add(Color::rgb(1.0, 0.9, 0.9).into());
-    let dark_material = materials.add(Color::rgb(0.0, 0.1, 0.1).into());
-
-    spawn_rook(
-        &mut commands,
-        light_material.clone(),
-        PieceColor::Light,
-        rook_handle.clone(),
-        (0, 0),
-    );
-    spawn_knight(
-        &mut commands,
-        light_material.clone(),
-        PieceColor::Light,
-        knight_1_handle.clone(),
-        knight_2_handle.clone(),
-        (0, 1),
-    );
-    spawn_bishop(
-        &mut commands,
-        light_material.clone(),
-        PieceColor::Light,
-        bishop_handle.clone(),
-        (0, 2),
-    );
-    spawn_queen(
-        &mut commands,
-        light_material.clone(),
-        PieceColor::Light,
-        queen_handle.clone(),
-        (0, 3),
-    );
-    spawn_king(
-        &mut commands,
-        light_material.clone(),
-        PieceColor::Light,
-        king_handle.clone(),
-        king_cross_handle.clone(),
-        (0, 4),
-    );
-    spawn_bishop(
-        &mut commands,
-        light_material.clone(),
-        PieceColor::Light,
-        bishop_handle.clone(),
-        (0, 5),
-    );
-    spawn_knight(
-        &mut commands,
-        light_material.clone(),
-        PieceColor::Light,
-        knight_1_handle.clone(),
-        knight_2_handle.clone(),
-        (0, 6),
-    );
-    spawn_rook(
-        &mut commands,
-        light_material.clone(),
-        PieceColor::Light,
-        rook_handle.clone(),
-        (0, 7),
-    );
-
-    for i in 0..8 {
-        spawn_pawn(
-            &mut commands,
-            light_material.clone(),
-            PieceColor::Light,
-            pawn_handle.clone(),
-            (1, i),
-        );
-    }
-
-    spawn_rook(
-        &mut commands,
-        dark_material.clone(),
-        PieceColor::Dark,
-        rook_handle.clone(),
-        (7, 0),
-    );
-    spawn_knight(
-        &mut commands,
-        dark_material.clone(),
-        PieceColor::Dark,
-        knight_1_handle.clone(),
-        knight_2_handle.clone(),
-        (7, 1),
-    );
-    spawn_bishop(
-        &mut commands,
-        dark_material.clone(),
-        PieceColor::Dark,
-        bishop_handle.clone(),
-        (7, 2),
-    );
-    spawn_queen(
-        &mut commands,
-        dark_material.clone(),
-        PieceColor::Dark,
-        queen_handle,
-        (7, 3),
-    );
-    spawn_king(
-        &mut commands,
-        dark_material.clone(),
-        PieceColor::Dark,
-        king_handle,
-        king_cross_handle,
-        (7, 4),
-    );
-    spawn_bishop(
-        &mut commands,
-        dark_material.clone(),
-        PieceColor::Dark,
-        bishop_handle,
-        (7, 5),
-    );
-    spawn_knight(
-        &mut commands,
-        dark_material.clone(),
-        PieceColor::Dark,
-        knight_1_handle,
-        knight_2_handle,
-        (7, 6),
-    );
-    spawn_rook(
-        &mut commands,
-        dark_material.clone(),
-        PieceColor::Dark,
-        rook_handle,
-        (7, 7),
-    );
-
-    for i in 0..8 {
-        spawn_pawn(
-            &mut commands,
-            dark_material.clone(),
-            PieceColor::Dark,
-            pawn_handle.clone(),
-            (6, i),
-        );
+    for (entity, piece, model, children) in query.iter() {
+        if model.map_or(false, |model| model.0 == piece.piece_type) {
+            continue;
+        }
+        for &child in children.into_iter().flat_map(|children| children.iter()) {
+            commands.entity(child).despawn_recursive();
+        }
+        commands
+            .entity(entity)
+            .insert(PieceModel(piece.piece_type))
+            .with_children(|parent| {
+                models.spawn_meshes(
+                    parent,
+                    piece.piece_type,
+                    materials.for_piece(&game.position, piece.color),
+                )
+            });
     }
 }
 
-fn move_pieces(time: Res<Time>, mut query: Query<(&mut Transform, &Piece)>) {
-    for (mut transform, piece) in query.iter_mut() {
-        // Get the direction to move in
-        let direction = Vec3::new(piece.x as f32, 0.0, piece.y as f32) - transform.translation;
-
-        // Only move if the piece isn't already there (distance is big)
-        if direction.length() > 0.1 {
-            transform.translation += direction.normalize() * time.delta_seconds();
+/// Turn the pieces of a player who is out of a four-player game gray, and back when that is
+/// taken back
+fn gray_out_pieces(
+    game: Res<Game>,
+    materials: Res<PieceMaterials>,
+    pieces: Query<(&Piece, &Children)>,
+    mut meshes: Query<&mut Handle<StandardMaterial>>,
+) {
+    if !game.is_changed() {
+        return;
+    }
+    for (piece, children) in pieces.iter() {
+        let material = materials.for_piece(&game.position, piece.color);
+        for &child in children.iter() {
+            if let Ok(mut handle) = meshes.get_mut(child) {
+                if *handle != material {
+                    *handle = material.clone();
+                }
+            }
         }
     }
 }
 
-fn spawn_king(
-    commands: &mut Commands,
-    material: Handle<StandardMaterial>,
-    piece_color: PieceColor,
-    mesh: Handle<Mesh>,
-    mesh_cross: Handle<Mesh>,
-    (x, y): (u8, u8),
-) {
-    commands
-        .spawn_bundle(PbrBundle {
-            transform: Transform::from_translation(Vec3::new(x as f32, 0.0, y as f32)),
-            ..Default::default()
-        })
-        .insert(Piece {
-            color: piece_color,
-            piece_type: PieceType::King,
-            x,
-            y,
-        })
-        .with_children(|parent| {
-            parent.spawn_bundle(PbrBundle {
-                mesh,
-                material: material.clone(),
-                transform: {
-                    let mut transform = Transform::from_translation(Vec3::new(-0.2, 0.0, -1.9));
-                    transform.apply_non_uniform_scale(Vec3::new(0.2, 0.2, 0.2));
-                    transform
-                },
-                ..Default::default()
-            });
-            parent.spawn_bundle(PbrBundle {
-                mesh: mesh_cross,
-                material,
-                transform: {
-                    let mut transform = Transform::from_translation(Vec3::new(-0.2, 0.0, -1.9));
-                    transform.apply_non_uniform_scale(Vec3::new(0.2, 0.2, 0.2));
-                    transform
-                },
-                ..Default::default()
-            });
-        });
+/// Materials shared by all pieces of a color, changed in place when the theme changes
+pub struct PieceMaterials {
+    light: Handle<StandardMaterial>,
+    dark: Handle<StandardMaterial>,
+    blue: Handle<StandardMaterial>,
+    green: Handle<StandardMaterial>,
+    /// Pieces of a player who is out of a four-player game
+    out: Handle<StandardMaterial>,
 }
 
-fn spawn_knight(
-    commands: &mut Commands,
-    material: Handle<StandardMaterial>,
-    piece_color: PieceColor,
-    mesh_1: Handle<Mesh>,
-    mesh_2: Handle<Mesh>,
-    (x, y): (u8, u8),
-) {
-    commands
-        .spawn_bundle(PbrBundle {
-            transform: Transform::from_translation(Vec3::new(x as f32, 0.0, y as f32)),
-            ..Default::default()
-        })
-        .insert(Piece {
-            color: piece_color,
-            piece_type: PieceType::Knight,
-            x,
-            y,
-        })
-        .with_children(|parent| {
-            parent.spawn_bundle(PbrBundle {
-                mesh: mesh_1,
-                material: material.clone(),
-                transform: {
-                    let mut transform = Transform::from_translation(Vec3::new(-0.2, 0.0, 0.9));
-                    transform.apply_non_uniform_scale(Vec3::new(0.2, 0.2, 0.2));
-                    transform
-                },
-                ..Default::default()
-            });
-            parent.spawn_bundle(PbrBundle {
-                mesh: mesh_2,
-                material,
-                transform: {
-                    let mut transform = Transform::from_translation(Vec3::new(-0.2, 0.0, 0.9));
-                    transform.apply_non_uniform_scale(Vec3::new(0.2, 0.2, 0.2));
-                    transform
-                },
-                ..Default::default()
-            });
-        });
-}
-
-fn spawn_queen(
-    commands: &mut Commands,
-    material: Handle<StandardMaterial>,
-    piece_color: PieceColor,
-    mesh: Handle<Mesh>,
-    (x, y): (u8, u8),
-) {
-    commands
-        .spawn_bundle(PbrBundle {
-            transform: Transform::from_translation(Vec3::new(x as f32, 0.0, y as f32)),
-            ..Default::default()
-        })
-        .insert(Piece {
-            color: piece_color,
-            piece_type: PieceType::Queen,
-            x,
-            y,
-        })
-        .with_children(|parent| {
-            parent.spawn_bundle(PbrBundle {
-                mesh,
-                material,
-                transform: {
-                    let mut transform = Transform::from_translation(Vec3::new(-0.2, 0.0, -0.95));
-                    transform.apply_non_uniform_scale(Vec3::new(0.2, 0.2, 0.2));
-                    transform
-                },
-                ..Default::default()
-            });
-        });
-}
+impl PieceMaterials {
+    pub fn get(&self, color: PieceColor) -> Handle<StandardMaterial> {
+        match color {
+            PieceColor::Light => self.light.clone(),
+            PieceColor::Dark => self.dark.clone(),
+            PieceColor::Blue => self.blue.clone(),
+            PieceColor::Green => self.green.clone(),
+        }
+    }
 
-fn spawn_bishop(
-    commands: &mut Commands,
-    material: Handle<StandardMaterial>,
-    piece_color: PieceColor,
-    mesh: Handle<Mesh>,
-    (x, y): (u8, u8),
-) {
-    commands
-        .spawn_bundle(PbrBundle {
-            transform: Transform::from_translation(Vec3::new(x as f32, 0.0, y as f32)),
-            ..Default::default()
-        })
-        .insert(Piece {
-            color: piece_color,
-            piece_type: PieceType::Bishop,
-            x,
-            y,
-        })
-        .with_children(|parent| {
-            parent.spawn_bundle(PbrBundle {
-                mesh,
-                material,
-                transform: {
-                    let mut transform = Transform::from_translation(Vec3::new(-0.1, 0.0, 0.0));
-                    transform.apply_non_uniform_scale(Vec3::new(0.2, 0.2, 0.2));
-                    transform
-                },
-                ..Default::default()
-            });
-        });
-}
+    /// Material of a piece of `color`, gray once its player is out of the game
+    pub fn for_piece(&self, position: &Position, color: PieceColor) -> Handle<StandardMaterial> {
+        if position.variant.is_out(position, color) {
+            self.out.clone()
+        } else {
+            self.get(color)
+        }
+    }
 
-fn spawn_rook(
-    commands: &mut Commands,
-    material: Handle<StandardMaterial>,
-    piece_color: PieceColor,
-    mesh: Handle<Mesh>,
-    (x, y): (u8, u8),
-) {
-    commands
-        .spawn_bundle(PbrBundle {
-            transform: Transform::from_translation(Vec3::new(x as f32, 0.0, y as f32)),
-            ..Default::default()
-        })
-        .insert(Piece {
-            color: piece_color,
-            piece_type: PieceType::Rook,
-            x,
-            y,
-        })
-        .with_children(|parent| {
-            parent.spawn_bundle(PbrBundle {
-                mesh,
-                material,
-                transform: {
-                    let mut transform = Transform::from_translation(Vec3::new(-0.1, 0.0, 1.8));
-                    transform.apply_non_uniform_scale(Vec3::new(0.2, 0.2, 0.2));
-                    transform
-                },
-                ..Default::default()
-            });
-        });
+    pub fn apply_theme(&self, theme: &Theme, materials: &mut Assets<StandardMaterial>) {
+        for (handle, style) in [
+            (&self.light, &theme.light_piece),
+            (&self.dark, &theme.dark_piece),
+            (&self.blue, &theme.blue_piece),
+            (&self.green, &theme.green_piece),
+            (&self.out, &theme.out_piece),
+        ] {
+            if let Some(material) = materials.get_mut(handle) {
+                *material = style.into();
+            }
+        }
+    }
 }
 
-fn spawn_pawn(
-    commands: &mut Commands,
-    material: Handle<StandardMaterial>,
-    piece_color: PieceColor,
-    mesh: Handle<Mesh>,
-    (x, y): (u8, u8),
-) {
-    commands
-        .spawn_bundle(PbrBundle {
-            transform: Transform::from_translation(Vec3::new(x as f32, 0.0, y as f32)),
-            ..Default::default()
-        })
-        .insert(Piece {
-            color: piece_color,
-            piece_type: PieceType::Pawn,
-            x,
-            y,
-        })
-        .with_children(|parent| {
-            parent.spawn_bundle(PbrBundle {
-                mesh,
-                material,
-                transform: {
-                    let mut transform = Transform::from_translation(Vec3::new(-0.2, 0.0, 2.6));
-                    transform.apply_non_uniform_scale(Vec3::new(0.2, 0.2, 0.2));
-                    transform
-                },
-                ..Default::default()
-            });
-        });
+impl FromWorld for PieceMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let theme = world
+            .get_resource_or_insert_with(Themes::load)
+            .active()
+            .clone();
+        let mut materials = world
+            .get_resource_mut::<Assets<StandardMaterial>>()
+            .unwrap();
+        PieceMaterials {
+            light: materials.add((&theme.light_piece).into()),
+            dark: materials.add((&theme.dark_piece).into()),
+            blue: materials.add((&theme.blue_piece).into()),
+            green: materials.add((&theme.green_piece).into()),
+            out: materials.add((&theme.out_piece).into()),
+        }
+    }
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum PieceColor {
     Light,
     Dark,
+    /// The side armies of four-player chess, on the a-file and the last file
+    Blue,
+    Green,
 }
 
 impl PieceColor {
+    /// The color on the other side of the board
     pub fn opponent(&self) -> Self {
         use PieceColor::*;
         match self {
             Light => Dark,
             Dark => Light,
+            Blue => Green,
+            Green => Blue,
+        }
+    }
+
+    /// Index for arrays holding something for each side, white first
+    pub fn index(self) -> usize {
+        match self {
+            PieceColor::Light => 0,
+            PieceColor::Dark => 1,
+            PieceColor::Blue => 2,
+            PieceColor::Green => 3,
+        }
+    }
+
+    /// Direction the pawns of the color move in, as a step in `(x, y)`
+    pub fn forward(self) -> (i8, i8) {
+        match self {
+            PieceColor::Light => (1, 0),
+            PieceColor::Dark => (-1, 0),
+            PieceColor::Blue => (0, 1),
+            PieceColor::Green => (0, -1),
+        }
+    }
+}
+
+/// Parses `white`, `black`, `blue` or `green`
+impl std::str::FromStr for PieceColor {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.to_ascii_lowercase().as_str() {
+            "white" => Ok(PieceColor::Light),
+            "black" => Ok(PieceColor::Dark),
+            "blue" => Ok(PieceColor::Blue),
+            "green" => Ok(PieceColor::Green),
+            _ => Err(format!(
+                "expected white, black, blue or green, found '{}'",
+                text
+            )),
         }
     }
 }
@@ -423,11 +201,13 @@ impl std::fmt::Display for PieceColor {
         match self {
             PieceColor::Light => f.write_str("White"),
             PieceColor::Dark => f.write_str("Black"),
+            PieceColor::Blue => f.write_str("Blue"),
+            PieceColor::Green => f.write_str("Green"),
         }
     }
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Deserialize)]
 pub enum PieceType {
     King,
     Queen,
@@ -435,164 +215,91 @@ pub enum PieceType {
     Knight,
     Rook,
     Pawn,
+    /// Moves along the space diagonals of Raumschach, through the corners of a cube
+    Unicorn,
+    /// Bishop and knight in one
+    Archbishop,
+    /// Rook and knight in one
+    Chancellor,
+    /// Queen and knight in one
+    Amazon,
 }
 
-#[derive(Component, Clone, Copy)]
-pub struct Piece {
-    pub color: PieceColor,
-    pub piece_type: PieceType,
-    pub x: u8,
-    pub y: u8,
-}
+impl PieceType {
+    pub const ALL: [PieceType; 10] = [
+        PieceType::King,
+        PieceType::Queen,
+        PieceType::Bishop,
+        PieceType::Knight,
+        PieceType::Rook,
+        PieceType::Pawn,
+        PieceType::Unicorn,
+        PieceType::Archbishop,
+        PieceType::Chancellor,
+        PieceType::Amazon,
+    ];
 
-impl Piece {
-    /// Returns whether the move is valid
-    pub fn is_move_valid(&self, new_pos @ (x, y): (u8, u8), pieces: Vec<Piece>) -> bool {
-        // TODO en passant, castling
-        // If there's a piece of the same color in the same square, it can't move
-        let color_of_new = color_of_square(new_pos, &pieces);
-        if color_of_new == Some(self.color) {
-            return false;
+    /// Letter used in FEN and SAN, uppercase
+    pub fn letter(self) -> char {
+        match self {
+            PieceType::King => 'K',
+            PieceType::Queen => 'Q',
+            PieceType::Bishop => 'B',
+            PieceType::Knight => 'N',
+            PieceType::Rook => 'R',
+            PieceType::Pawn => 'P',
+            PieceType::Unicorn => 'U',
+            PieceType::Archbishop => 'A',
+            PieceType::Chancellor => 'C',
+            PieceType::Amazon => 'Z',
         }
+    }
 
-        let pos = (self.x, self.y);
-        let x_diff = self.x.abs_diff(x);
-        let y_diff = self.y.abs_diff(y);
-        let is_path_empty = is_path_empty(pos, new_pos, &pieces);
-        match self.piece_type {
-            PieceType::King => {
-                // Horizontal
-                x_diff == 1 && self.y == y
-                    // Vertical
-                    || y_diff == 1 && self.x == x
-                    // Diagonal
-                    || x_diff == 1 && y_diff == 1
-            }
-            PieceType::Queen => {
-                is_path_empty
-                    && (x_diff == y_diff
-                        || (self.x == x && self.y != y)
-                        || (self.y == y && self.x != x))
-            }
-            PieceType::Bishop => is_path_empty && x_diff == y_diff,
-            PieceType::Knight => x_diff == 2 && y_diff == 1 || y_diff == 2 && x_diff == 1,
-            PieceType::Rook => {
-                is_path_empty && (self.y == y && self.x != x || self.x == x && self.y != y)
-            }
-            PieceType::Pawn => {
-                match self.color {
-                    PieceColor::Light => {
-                        // Has to move forward
-                        if x <= self.x {
-                            return false;
-                        }
-                        // Normal move
-                        if x_diff == 1 && self.y == y && color_of_new.is_none() {
-                            return true;
-                        }
-
-                        // Move 2 squares
-                        if self.x == 1
-                            && x_diff == 2
-                            && self.y == y
-                            && is_path_empty
-                            && color_of_new.is_none()
-                        {
-                            return true;
-                        }
-
-                        // Take piece
-                        if x_diff == 1 && y_diff == 1 && color_of_new == Some(PieceColor::Dark) {
-                            return true;
-                        }
-                    }
-                    PieceColor::Dark => {
-                        // Has to move forward
-                        if x >= self.x {
-                            return false;
-                        }
-                        // Normal move
-                        if x_diff == 1 && self.y == y && color_of_new.is_none() {
-                            return true;
-                        }
-
-                        // Move 2 squares
-                        if self.x == 6
-                            && x_diff == 2
-                            && self.y == y
-                            && is_path_empty
-                            && color_of_new.is_none()
-                        {
-                            return true;
-                        }
+    pub fn from_letter(letter: char) -> Option<Self> {
+        Some(match letter.to_ascii_uppercase() {
+            'K' => PieceType::King,
+            'Q' => PieceType::Queen,
+            'B' => PieceType::Bishop,
+            'N' => PieceType::Knight,
+            'R' => PieceType::Rook,
+            'P' => PieceType::Pawn,
+            'U' => PieceType::Unicorn,
+            'A' => PieceType::Archbishop,
+            'C' => PieceType::Chancellor,
+            'Z' => PieceType::Amazon,
+            _ => return None,
+        })
+    }
 
-                        // Take piece
-                        if x_diff == 1 && y_diff == 1 && color_of_new == Some(PieceColor::Light) {
-                            return true;
-                        }
-                    }
-                }
-                false
-            }
-        }
+    /// How the piece moves in Betza notation, pawns and the unicorn move their own way
+    pub fn betza(self) -> Option<&'static str> {
+        Some(match self {
+            PieceType::King => "K",
+            PieceType::Queen => "Q",
+            PieceType::Bishop => "B",
+            PieceType::Knight => "N",
+            PieceType::Rook => "R",
+            PieceType::Archbishop => "BN",
+            PieceType::Chancellor => "RN",
+            PieceType::Amazon => "QN",
+            PieceType::Pawn | PieceType::Unicorn => return None,
+        })
     }
 }
 
-/// Returns None if the square is empty, retuns Some with the color if not
-fn color_of_square((x, y): (u8, u8), pieces: &[Piece]) -> Option<PieceColor> {
-    for piece in pieces {
-        if piece.x == x && piece.y == y {
-            return Some(piece.color);
-        }
-    }
-    None
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Piece {
+    pub color: PieceColor,
+    pub piece_type: PieceType,
+    pub x: u8,
+    pub y: u8,
+    /// Level of stacked boards, always 0 on a flat board
+    pub z: u8,
 }
 
-fn is_path_empty(begin: (u8, u8), end: (u8, u8), pieces: &[Piece]) -> bool {
-    // Same column
-    if begin.0 == end.0 {
-        for piece in pieces {
-            if piece.x == begin.0
-                && ((piece.y > begin.1 && piece.y < end.1)
-                    || (piece.y > end.1 && piece.y < begin.1))
-            {
-                return false;
-            }
-        }
-    }
-    // Same row
-    if begin.1 == end.1 {
-        for piece in pieces {
-            if piece.y == begin.1
-                && ((piece.x > begin.0 && piece.x < end.0)
-                    || (piece.x > end.0 && piece.x < begin.0))
-            {
-                return false;
-            }
-        }
-    }
-    let x_diff = begin.0.abs_diff(end.0);
-    let y_diff = begin.1.abs_diff(end.1);
-    if x_diff == y_diff {
-        for i in 1..x_diff {
-            let pos = if begin.0 < end.0 && begin.1 < end.1 {
-                // left bottom - right top
-                (begin.0 + i as u8, begin.1 + i as u8)
-            } else if begin.0 < end.0 && begin.1 > end.1 {
-                // left top - right bottom
-                (begin.0 + i as u8, begin.1 - i as u8)
-            } else if begin.0 > end.0 && begin.1 < end.1 {
-                // right bottom - left top
-                (begin.0 - i as u8, begin.1 + i as u8)
-            } else {
-                // right top - left bottom
-                (begin.0 - i as u8, begin.1 - i as u8)
-            };
-            if color_of_square(pos, pieces).is_some() {
-                return false;
-            }
-        }
+impl Piece {
+    /// The square the piece is on, as `(x, y, z)`
+    pub fn square(&self) -> (u8, u8, u8) {
+        (self.x, self.y, self.z)
     }
-
-    true
 }
@@ -0,0 +1,283 @@
+use std::{
+    path::PathBuf,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use bevy::{
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task},
+};
+use futures_lite::future;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ai::{self, DEFAULT_DEPTH},
+    clock::Clock,
+    game::{Game, GameSystem, MoveRequest, ResetBoardEvent},
+    headless::ScriptedMoves,
+    pieces::PieceColor,
+    rules::Move,
+    uci::{SearchLimit, UciEngine},
+};
+
+pub struct PlayersPlugin;
+impl Plugin for PlayersPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Players>()
+            .add_system(cancel_engine_searches.before(GameSystem::ApplyMoves))
+            .add_system(start_engine_search.after(GameSystem::ApplyMoves))
+            .add_system(finish_engine_search.before(GameSystem::ApplyMoves));
+    }
+}
+
+/// How long an external engine thinks in untimed games
+const ENGINE_MOVE_TIME_MS: u32 = 1000;
+
+/// Who makes the moves for one side
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum PlayerKind {
+    Human,
+    /// The built-in engine searching `depth` plies deep
+    Ai {
+        depth: u32,
+    },
+    /// An external UCI engine started from `path`
+    Uci {
+        path: PathBuf,
+    },
+    /// Someone playing on another machine, their moves arrive over the network
+    Remote,
+}
+
+/// Parses `human`, `ai` or the path to a UCI engine
+impl FromStr for PlayerKind {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "human" => Ok(PlayerKind::Human),
+            "ai" => Ok(PlayerKind::Ai {
+                depth: DEFAULT_DEPTH,
+            }),
+            "" => Err("expected human, ai or the path to a UCI engine".to_string()),
+            path => Ok(PlayerKind::Uci {
+                path: PathBuf::from(path),
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for PlayerKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlayerKind::Human => write!(f, "Human"),
+            PlayerKind::Remote => write!(f, "Remote player"),
+            PlayerKind::Ai { depth } => write!(f, "AI (depth {})", depth),
+            PlayerKind::Uci { path } => match path.file_stem() {
+                Some(name) => write!(f, "{}", name.to_string_lossy()),
+                None => write!(f, "{}", path.display()),
+            },
+        }
+    }
+}
+
+/// The players of every side, external engines are started when they first have to move
+pub struct Players {
+    light: PlayerKind,
+    dark: PlayerKind,
+    /// The other two sides of four-player chess
+    blue: PlayerKind,
+    green: PlayerKind,
+    engines: [Arc<Mutex<Option<UciEngine>>>; 4],
+}
+
+impl Default for Players {
+    fn default() -> Self {
+        Self::new(
+            PlayerKind::Human,
+            PlayerKind::Human,
+            PlayerKind::Human,
+            PlayerKind::Human,
+        )
+    }
+}
+
+impl Players {
+    pub fn new(light: PlayerKind, dark: PlayerKind, blue: PlayerKind, green: PlayerKind) -> Self {
+        Self {
+            light,
+            dark,
+            blue,
+            green,
+            engines: Default::default(),
+        }
+    }
+
+    /// Let the other machine make the moves for `color`
+    pub fn set_remote(&mut self, color: PieceColor) {
+        *self.get_mut(color) = PlayerKind::Remote;
+    }
+
+    pub fn get(&self, color: PieceColor) -> &PlayerKind {
+        match color {
+            PieceColor::Light => &self.light,
+            PieceColor::Dark => &self.dark,
+            PieceColor::Blue => &self.blue,
+            PieceColor::Green => &self.green,
+        }
+    }
+
+    fn get_mut(&mut self, color: PieceColor) -> &mut PlayerKind {
+        match color {
+            PieceColor::Light => &mut self.light,
+            PieceColor::Dark => &mut self.dark,
+            PieceColor::Blue => &mut self.blue,
+            PieceColor::Green => &mut self.green,
+        }
+    }
+
+    pub fn is_human(&self, color: PieceColor) -> bool {
+        *self.get(color) == PlayerKind::Human
+    }
+
+    /// Whether the built-in AI or an external engine plays `color`
+    pub fn is_engine(&self, color: PieceColor) -> bool {
+        matches!(
+            self.get(color),
+            PlayerKind::Ai { .. } | PlayerKind::Uci { .. }
+        )
+    }
+
+    fn engine(&self, color: PieceColor) -> Arc<Mutex<Option<UciEngine>>> {
+        self.engines[color.index()].clone()
+    }
+}
+
+/// A search running in the background for the side to move after `ply` moves
+#[derive(Component)]
+struct EngineSearch {
+    color: PieceColor,
+    ply: usize,
+    task: Task<Result<Move, String>>,
+}
+
+/// Start searching when an engine is to move and no search is running yet
+fn start_engine_search(
+    mut commands: Commands,
+    pool: Res<AsyncComputeTaskPool>,
+    game: Res<Game>,
+    players: Res<Players>,
+    clock: Res<Clock>,
+    script: Option<Res<ScriptedMoves>>,
+    searches: Query<&EngineSearch>,
+) {
+    if game.is_over() || searches.iter().any(|search| search.ply == game.moves.len()) {
+        return;
+    }
+    // Scripted moves come first
+    if script.map_or(false, |script| !script.is_empty()) {
+        return;
+    }
+    let color = game.position.turn;
+    let task = match players.get(color).clone() {
+        PlayerKind::Human | PlayerKind::Remote => return,
+        PlayerKind::Ai { depth } => {
+            let position = game.position.clone();
+            pool.spawn(async move {
+                ai::best_move(&position, depth).ok_or_else(|| "no legal moves".to_string())
+            })
+        }
+        PlayerKind::Uci { path } => {
+            let engine = players.engine(color);
+            let start = game.start.clone();
+            let moves = game.move_list();
+            let limit = match (
+                clock.remaining(PieceColor::Light),
+                clock.remaining(PieceColor::Dark),
+            ) {
+                (Some(white), Some(black)) => {
+                    let increment = (clock.increment() * 1000.0) as u32;
+                    SearchLimit::Clock {
+                        white: (white * 1000.0) as u32,
+                        black: (black * 1000.0) as u32,
+                        white_increment: increment,
+                        black_increment: increment,
+                    }
+                }
+                _ => SearchLimit::MoveTime {
+                    milliseconds: ENGINE_MOVE_TIME_MS,
+                },
+            };
+            pool.spawn(async move {
+                let mut engine = engine.lock().map_err(|_| "engine crashed".to_string())?;
+                if engine.is_none() {
+                    let started = UciEngine::start(&path)?;
+                    info!("{} plays {}", started.name(), color);
+                    *engine = Some(started);
+                }
+                engine.as_mut().unwrap().best_move(&start, &moves, limit)
+            })
+        }
+    };
+    commands.spawn().insert(EngineSearch {
+        color,
+        ply: game.moves.len(),
+        task,
+    });
+}
+
+/// Stop waiting for searches in a game that was replaced or had moves taken back
+fn cancel_engine_searches(
+    mut commands: Commands,
+    mut resets: EventReader<ResetBoardEvent>,
+    searches: Query<Entity, With<EngineSearch>>,
+) {
+    if resets.iter().count() == 0 {
+        return;
+    }
+    for entity in searches.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+/// Play the move an engine found, an engine that fails or finds an illegal move loses the game
+/// instead of being asked again
+fn finish_engine_search(
+    mut commands: Commands,
+    mut game: ResMut<Game>,
+    players: Res<Players>,
+    mut requests: EventWriter<MoveRequest>,
+    mut searches: Query<(Entity, &mut EngineSearch)>,
+) {
+    for (entity, mut search) in searches.iter_mut() {
+        let result = match future::block_on(future::poll_once(&mut search.task)) {
+            Some(result) => result,
+            None => continue,
+        };
+        commands.entity(entity).despawn();
+        // The game moved on while the engine was thinking
+        if search.ply != game.moves.len() || game.is_over() {
+            continue;
+        }
+        match result {
+            Ok(mv) if game.position.is_legal(&mv) => requests.send(MoveRequest(mv)),
+            Ok(mv) => {
+                error!(
+                    "{} played the illegal move {:?}",
+                    players.get(search.color),
+                    mv
+                );
+                let variant = game.position.variant;
+                let result = variant.forfeit(&game.position, search.color, "illegal engine move");
+                game.end(result);
+            }
+            Err(err) => {
+                error!("{}", err);
+                let variant = game.position.variant;
+                let result = variant.forfeit(&game.position, search.color, "engine error");
+                game.end(result);
+            }
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use bevy::prelude::*;
+use bevy_mod_picking::Selection;
+
+use crate::{
+    board::{SelectedPiece, Square},
+    game::{Game, PlayerTurn},
+    menu::{spawn_label, spawn_sized_button, text_style},
+    net::Spectating,
+    pieces::{PieceColor, PieceType},
+    players::Players,
+    variants::{reserve_counts, RESERVE_ORDER},
+};
+
+/// The crazyhouse reserves beside the board, a piece picked there is dropped on the next square
+/// clicked
+pub struct ReservePlugin;
+impl Plugin for ReservePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(init_reserve_panel)
+            .add_system(update_reserve_panel)
+            .add_system(press_reserve_buttons);
+    }
+}
+
+// Component to mark the root node of the reserve panel
+#[derive(Component)]
+struct ReservePanel;
+
+#[derive(Component)]
+struct ReserveButton {
+    color: PieceColor,
+    piece_type: PieceType,
+}
+
+/// Text color of the reserve piece that is about to be dropped
+const PICKED_COLOR: Color = Color::rgb(1.0, 0.8, 0.2);
+
+fn init_reserve_panel(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font: Handle<Font> = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.0),
+                    top: Val::Px(200.0),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                display: Display::None,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(ReservePanel)
+        .with_children(|parent| {
+            for color in [PieceColor::Dark, PieceColor::Light] {
+                spawn_label(parent, font.clone(), &format!("{} reserve", color));
+                parent
+                    .spawn_bundle(NodeBundle {
+                        color: Color::NONE.into(),
+                        ..Default::default()
+                    })
+                    .with_children(|parent| {
+                        for piece_type in RESERVE_ORDER {
+                            spawn_sized_button(
+                                parent,
+                                font.clone(),
+                                "",
+                                ReserveButton { color, piece_type },
+                                56.0,
+                            );
+                        }
+                    });
+            }
+        });
+}
+
+/// Show the panel in variants with reserves, with a button for every piece held
+fn update_reserve_panel(
+    game: Res<Game>,
+    selected_piece: Res<SelectedPiece>,
+    turn: Res<PlayerTurn>,
+    mut panels: Query<&mut Style, (With<ReservePanel>, Without<ReserveButton>)>,
+    mut buttons: Query<(&ReserveButton, &mut Style, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    if !game.is_changed() && !selected_piece.is_changed() {
+        return;
+    }
+    let position = &game.position;
+    for mut style in panels.iter_mut() {
+        style.display = if position.variant.has_reserves() {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+
+    for (button, mut style, children) in buttons.iter_mut() {
+        let count = reserve_counts(position, button.color)
+            .into_iter()
+            .find_map(|(piece_type, count)| (piece_type == button.piece_type).then(|| count))
+            .unwrap_or(0);
+        style.display = if count > 0 {
+            Display::Flex
+        } else {
+            Display::None
+        };
+        let picked = button.color == turn.0 && selected_piece.drop == Some(button.piece_type);
+        for &child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(child) {
+                let section = &mut text.sections[0];
+                section.value = format!("{} {}", button.piece_type.letter(), count);
+                section.style.color = if picked {
+                    PICKED_COLOR
+                } else {
+                    text_style(section.style.font.clone()).color
+                };
+            }
+        }
+    }
+}
+
+/// Pick a piece from the reserve of the human whose turn it is, pressing it again puts it back
+fn press_reserve_buttons(
+    mut selected_piece: ResMut<SelectedPiece>,
+    game: Res<Game>,
+    turn: Res<PlayerTurn>,
+    players: Res<Players>,
+    spectating: Option<Res<Spectating>>,
+    buttons: Query<(&Interaction, &ReserveButton), Changed<Interaction>>,
+    mut squares: Query<&mut Selection, With<Square>>,
+) {
+    if game.is_over() || !players.is_human(turn.0) || spectating.is_some() {
+        return;
+    }
+    for (interaction, button) in buttons.iter() {
+        if *interaction != Interaction::Clicked || button.color != turn.0 {
+            continue;
+        }
+        selected_piece.entity = None;
+        selected_piece.drop = if selected_piece.drop == Some(button.piece_type) {
+            None
+        } else {
+            Some(button.piece_type)
+        };
+        // The piece goes on the next square clicked, not one that was selected before
+        for mut selection in squares.iter_mut() {
+            selection.set_selected(false);
+        }
+    }
+}
@@ -0,0 +1,874 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    betza,
+    bitboard::Board,
+    pieces::{Piece, PieceColor, PieceType},
+    variants::{Standard, Variant, VariantState},
+};
+
+/// A move from one square to another, castling is encoded as the king moving onto its own rook
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct Move {
+    pub from: (u8, u8, u8),
+    pub to: (u8, u8, u8),
+    pub promotion: Option<PieceType>,
+    /// Piece put down from the reserve in crazyhouse, `from` and `to` are both the target square
+    pub drop: Option<PieceType>,
+}
+
+impl Move {
+    pub fn new(from: (u8, u8, u8), to: (u8, u8, u8)) -> Self {
+        Self {
+            from,
+            to,
+            promotion: None,
+            drop: None,
+        }
+    }
+
+    pub fn drop(piece_type: PieceType, to: (u8, u8, u8)) -> Self {
+        Self {
+            from: to,
+            to,
+            promotion: None,
+            drop: Some(piece_type),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CastlingSide {
+    Queen,
+    King,
+}
+
+impl CastlingSide {
+    /// Files the king and the rook end up on, on a board `files` wide
+    pub fn destination_files(self, files: u8) -> (u8, u8) {
+        match self {
+            CastlingSide::Queen => (2, 3),
+            CastlingSide::King => (files - 2, files - 3),
+        }
+    }
+}
+
+/// Files of the rooks that can still castle, per color and side
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct CastlingRights {
+    rooks: [[Option<u8>; 2]; 4],
+}
+
+impl CastlingRights {
+    pub fn get(&self, color: PieceColor, side: CastlingSide) -> Option<u8> {
+        self.rooks[color as usize][side as usize]
+    }
+
+    pub fn set(&mut self, color: PieceColor, side: CastlingSide, rook_file: Option<u8>) {
+        self.rooks[color as usize][side as usize] = rook_file;
+    }
+
+    pub fn remove_rook(&mut self, color: PieceColor, file: u8) {
+        for rook in self.rooks[color as usize].iter_mut() {
+            if *rook == Some(file) {
+                *rook = None;
+            }
+        }
+    }
+}
+
+/// What changed on the board because of a move, used to update the piece entities
+#[derive(Clone, Debug)]
+pub struct MoveEffects {
+    /// Every piece that moved as `(from, to)`, the king comes first when castling
+    pub moved: Vec<((u8, u8, u8), (u8, u8, u8))>,
+    pub captured: Option<Piece>,
+    pub promotion: Option<PieceType>,
+    /// Square of an atomic explosion, the capturing piece is blown up there
+    pub explosion: Option<(u8, u8, u8)>,
+    /// Pieces around the explosion that were blown up with it
+    pub exploded: Vec<Piece>,
+    /// Piece put down from the reserve
+    pub dropped: Option<Piece>,
+}
+
+/// How a game ended, `winner` is `None` for a draw or an aborted game
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct GameResult {
+    pub winner: Option<PieceColor>,
+    pub reason: String,
+    /// The game stopped without a result, like when the connection was lost
+    pub aborted: bool,
+}
+
+impl GameResult {
+    pub fn win(winner: PieceColor, reason: &str) -> Self {
+        Self {
+            winner: Some(winner),
+            reason: reason.to_string(),
+            aborted: false,
+        }
+    }
+
+    pub fn draw(reason: &str) -> Self {
+        Self {
+            winner: None,
+            reason: reason.to_string(),
+            aborted: false,
+        }
+    }
+
+    pub fn aborted(reason: &str) -> Self {
+        Self {
+            winner: None,
+            reason: reason.to_string(),
+            aborted: true,
+        }
+    }
+
+    /// Result as written in PGN, which has none for the side armies of four-player chess
+    pub fn pgn(&self) -> &'static str {
+        if self.aborted {
+            return "*";
+        }
+        match self.winner {
+            Some(PieceColor::Light) => "1-0",
+            Some(PieceColor::Dark) => "0-1",
+            Some(PieceColor::Blue | PieceColor::Green) => "*",
+            None => "1/2-1/2",
+        }
+    }
+}
+
+impl std::fmt::Display for GameResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.aborted {
+            return write!(f, "Game aborted: {}", self.reason);
+        }
+        match self.winner {
+            Some(winner) => write!(f, "{} won by {}", winner, self.reason),
+            None => write!(f, "Draw by {}", self.reason),
+        }
+    }
+}
+
+/// Full state of a game of chess at one point in time
+#[derive(Clone, Debug)]
+pub struct Position {
+    pub pieces: Vec<Piece>,
+    pub turn: PieceColor,
+    pub castling: CastlingRights,
+    /// Square a pawn can be taken on en passant
+    pub en_passant: Option<(u8, u8, u8)>,
+    /// Half moves since the last capture or pawn move, for the fifty-move rule
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+    /// Started from a Chess960 position, castling is written as the king taking its rook in UCI
+    pub chess960: bool,
+    pub variant: &'static dyn Variant,
+    pub state: VariantState,
+}
+
+/// Pieces on the back rank, from the a-file to the h-file
+pub const BACK_RANK: [PieceType; 8] = [
+    PieceType::Rook,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Queen,
+    PieceType::King,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::Rook,
+];
+
+/// Size of the board a variant is played on. A square `(x, y, z)` is on rank `x` and file `y` of
+/// level `z`, boards with several levels stack them.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BoardShape {
+    pub files: u8,
+    pub ranks: u8,
+    pub levels: u8,
+    /// Size of the square left out in each corner, for the cross-shaped four-player board
+    pub corners: u8,
+    /// Hexagonal cells, the middle file has `ranks` of them and each file further out one less
+    pub hexagonal: bool,
+}
+
+/// Files of the hexagonal board, which has no j-file
+const HEX_FILES: &[u8] = b"abcdefghikl";
+
+impl BoardShape {
+    pub const STANDARD: BoardShape = BoardShape {
+        files: 8,
+        ranks: 8,
+        levels: 1,
+        corners: 0,
+        hexagonal: false,
+    };
+
+    /// Number of rows in FEN, the ranks of all levels one after the other
+    pub fn rows(self) -> u8 {
+        self.ranks * self.levels
+    }
+
+    pub fn contains(self, (x, y, z): (u8, u8, u8)) -> bool {
+        if self.hexagonal {
+            let middle = self.files / 2;
+            return z == 0 && y < self.files && x < self.ranks - y.abs_diff(middle);
+        }
+        let in_corner = |value: u8, size: u8| value < self.corners || value >= size - self.corners;
+        x < self.ranks
+            && y < self.files
+            && z < self.levels
+            && !(in_corner(x, self.ranks) && in_corner(y, self.files))
+    }
+
+    /// Every square of the board, level by level
+    pub fn squares(self) -> impl Iterator<Item = (u8, u8, u8)> {
+        (0..self.levels)
+            .flat_map(move |z| (0..self.ranks).map(move |x| (x, z)))
+            .flat_map(move |(x, z)| (0..self.files).map(move |y| (x, y, z)))
+            .filter(move |&square| self.contains(square))
+    }
+
+    /// The square `step` away from `square` on the same level, if it is on the board
+    pub fn offset(self, (x, y, z): (u8, u8, u8), (dx, dy): (i8, i8)) -> Option<(u8, u8, u8)> {
+        let x = x as i16 + dx as i16;
+        let y = y as i16 + dy as i16;
+        if x < 0 || y < 0 || x > u8::MAX as i16 || y > u8::MAX as i16 {
+            return None;
+        }
+        Some((x as u8, y as u8, z)).filter(|&square| self.contains(square))
+    }
+
+    /// Rank the pieces of `color` start on, the side armies of four-player chess start on a file
+    /// and count from the first rank
+    pub fn back_rank(self, color: PieceColor) -> u8 {
+        match color {
+            PieceColor::Light | PieceColor::Blue | PieceColor::Green => 0,
+            PieceColor::Dark => self.ranks - 1,
+        }
+    }
+
+    /// Squares of the FEN row `row` from the a-file on, rows count the ranks of the lowest level
+    /// first. The hexagonal board leaves out the files that don't reach the row.
+    pub fn row_squares(self, row: u8) -> Vec<(u8, u8, u8)> {
+        let (x, z) = (row % self.ranks, row / self.ranks);
+        (0..self.files)
+            .map(|y| (x, y, z))
+            .filter(|&square| !self.hexagonal || self.contains(square))
+            .collect()
+    }
+
+    /// Axial coordinates of a cell of the hexagonal board, as `(file, up)` counted from f1. A step
+    /// to a neighbouring cell is one of `(0, ±1)`, `(±1, 0)` and `±(1, 1)`.
+    pub fn hex_point(self, (x, y, _): (u8, u8, u8)) -> (i8, i8) {
+        let file = y as i8 - (self.files / 2) as i8;
+        (file, x as i8 + file.max(0))
+    }
+
+    /// The cell at axial coordinates `(file, up)`, if it is on the board
+    pub fn hex_square(self, (file, up): (i8, i8)) -> Option<(u8, u8, u8)> {
+        let y = file + (self.files / 2) as i8;
+        let x = up - file.max(0);
+        if x < 0 || y < 0 {
+            return None;
+        }
+        Some((x as u8, y as u8, 0)).filter(|&square| self.contains(square))
+    }
+
+    /// Letter of the file `y`
+    pub fn file_letter(self, y: u8) -> char {
+        match HEX_FILES.get(y as usize) {
+            Some(&letter) if self.hexagonal => letter as char,
+            _ => (b'a' + y) as char,
+        }
+    }
+
+    /// File of a file letter, if the board has it
+    pub fn parse_file(self, letter: char) -> Option<u8> {
+        let file = if self.hexagonal {
+            HEX_FILES.iter().position(|&c| c as char == letter)? as u8
+        } else if letter.is_ascii_lowercase() {
+            letter as u8 - b'a'
+        } else {
+            return None;
+        };
+        (file < self.files).then(|| file)
+    }
+
+    /// Name of a square, like `e4`, with the level in front on stacked boards, like `Cc3`
+    pub fn square_name(self, (x, y, z): (u8, u8, u8)) -> String {
+        let mut name = String::new();
+        if self.levels > 1 {
+            name.push((b'A' + z) as char);
+        }
+        name.push(self.file_letter(y));
+        name.push_str(&(x + 1).to_string());
+        name
+    }
+
+    pub fn parse_square(self, name: &str) -> Option<(u8, u8, u8)> {
+        match self.split_square(name) {
+            Some((square, "")) => Some(square),
+            _ => None,
+        }
+    }
+
+    /// Read the square at the start of `text`, returns it with the rest of the text
+    pub fn split_square(self, text: &str) -> Option<((u8, u8, u8), &str)> {
+        let bytes = text.as_bytes();
+        let mut index = 0;
+        let mut level = 0;
+        if self.levels > 1 {
+            level = bytes.first()?.checked_sub(b'A')?;
+            index += 1;
+        }
+        let file = self.parse_file(*bytes.get(index)? as char)?;
+        index += 1;
+        if level >= self.levels {
+            return None;
+        }
+        let digits = bytes[index..]
+            .iter()
+            .take_while(|byte| byte.is_ascii_digit())
+            .count();
+        let rank: u8 = text[index..index + digits].parse().ok()?;
+        if rank == 0 || rank > self.ranks {
+            return None;
+        }
+        let square = (rank - 1, file, level);
+        if self.hexagonal && !self.contains(square) {
+            return None;
+        }
+        Some((square, &text[index + digits..]))
+    }
+}
+
+/// Pieces a pawn can promote to in most variants
+pub const PROMOTIONS: [PieceType; 4] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+];
+
+impl Default for Position {
+    fn default() -> Self {
+        Self::with_back_rank(BACK_RANK)
+    }
+}
+
+impl Position {
+    /// The starting position with `back_rank` as the pieces of both sides, from the a-file to the
+    /// h-file. Both sides can castle with the outermost rooks.
+    pub fn with_back_rank(back_rank: [PieceType; 8]) -> Self {
+        let mut pieces = Vec::with_capacity(32);
+        let mut castling = CastlingRights::default();
+        for (color, rank, pawn_rank) in [(PieceColor::Light, 0, 1), (PieceColor::Dark, 7, 6)] {
+            for (y, piece_type) in back_rank.into_iter().enumerate() {
+                pieces.push(Piece {
+                    color,
+                    piece_type,
+                    x: rank,
+                    y: y as u8,
+                    z: 0,
+                });
+            }
+            for y in 0..8 {
+                pieces.push(Piece {
+                    color,
+                    piece_type: PieceType::Pawn,
+                    x: pawn_rank,
+                    y,
+                    z: 0,
+                });
+            }
+            let rooks = || (0..8u8).filter(|&file| back_rank[file as usize] == PieceType::Rook);
+            castling.set(color, CastlingSide::Queen, rooks().next());
+            castling.set(color, CastlingSide::King, rooks().last());
+        }
+        Self {
+            pieces,
+            turn: PieceColor::Light,
+            castling,
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            chess960: false,
+            variant: &Standard,
+            state: VariantState::default(),
+        }
+    }
+}
+
+impl Position {
+    pub fn piece_at(&self, square: (u8, u8, u8)) -> Option<&Piece> {
+        self.pieces.iter().find(|piece| piece.square() == square)
+    }
+
+    pub fn king(&self, color: PieceColor) -> Option<&Piece> {
+        self.pieces
+            .iter()
+            .find(|piece| piece.color == color && piece.piece_type == PieceType::King)
+    }
+
+    /// Whether any piece of color `by` attacks the square
+    pub fn is_attacked(&self, square: (u8, u8, u8), by: PieceColor) -> bool {
+        let board = self.variant.board();
+        self.pieces
+            .iter()
+            .filter(|piece| piece.color == by)
+            .any(|piece| match piece.piece_type {
+                // Pawns only attack diagonally
+                PieceType::Pawn => [-1, 1].into_iter().any(|side| {
+                    board.offset(piece.square(), orient(by, (1, side))) == Some(square)
+                }),
+                _ => {
+                    let mut attacked = false;
+                    self.visit_betza_targets(piece, |to, atom| {
+                        attacked |= to == square && atom.captures;
+                    });
+                    attacked
+                }
+            })
+    }
+
+    /// Call `visit` with every square the piece reaches by its Betza atoms, up to and including
+    /// the first piece in the way
+    fn visit_betza_targets(
+        &self,
+        piece: &Piece,
+        mut visit: impl FnMut((u8, u8, u8), &betza::Atom),
+    ) {
+        let atoms = match self.variant.movement() {
+            Ok(movement) => movement.atoms(piece.piece_type),
+            // No position of the variant can be set up, see `from_variant_fen`
+            Err(_) => return,
+        };
+        let board = self.variant.board();
+        for atom in atoms {
+            for step in atom.steps() {
+                // Every side looks at the board from its own edge
+                let step = orient(piece.color, step);
+                let mut current = piece.square();
+                let mut taken = 0;
+                while atom.range == 0 || taken < atom.range {
+                    taken += 1;
+                    let to = match board.offset(current, step) {
+                        Some(to) => to,
+                        None => break,
+                    };
+                    current = to;
+                    visit(to, atom);
+                    if self.piece_at(to).is_some() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether the king of `color` is in check by the rules of the variant
+    pub fn is_in_check(&self, color: PieceColor) -> bool {
+        self.variant.is_in_check(self, color)
+    }
+
+    pub fn is_king_attacked(&self, color: PieceColor) -> bool {
+        self.king(color).map_or(false, |king| {
+            self.is_attacked(king.square(), color.opponent())
+        })
+    }
+
+    /// Castling side of a move, if it is one
+    pub fn castling_side(&self, mv: &Move) -> Option<CastlingSide> {
+        let piece = self.piece_at(mv.from)?;
+        let target = self.piece_at(mv.to)?;
+        if piece.piece_type != PieceType::King
+            || target.piece_type != PieceType::Rook
+            || target.color != piece.color
+        {
+            return None;
+        }
+        Some(if mv.to.1 > mv.from.1 {
+            CastlingSide::King
+        } else {
+            CastlingSide::Queen
+        })
+    }
+
+    /// Moves that follow the movement rules, without checking whether they leave the king in check
+    pub fn pseudo_legal_moves(&self) -> Vec<Move> {
+        let mut moves = self.variant.piece_moves(self);
+        self.variant.add_moves(self, &mut moves);
+        moves
+    }
+
+    /// Moves of the pieces by their Betza notation and of the pawns on a flat board, castling
+    /// included
+    pub fn standard_piece_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        for piece in self.pieces.iter().filter(|piece| piece.color == self.turn) {
+            if piece.piece_type == PieceType::Pawn {
+                self.push_pawn_moves(piece, &mut moves);
+                continue;
+            }
+            let from = piece.square();
+            self.visit_betza_targets(piece, |to, atom| {
+                let allowed = match self.piece_at(to) {
+                    Some(target) => target.color != piece.color && atom.captures,
+                    None => atom.moves,
+                };
+                if allowed {
+                    moves.push(Move::new(from, to));
+                }
+            });
+        }
+        self.push_castling_moves(&mut moves);
+        moves
+    }
+
+    /// Moves of a pawn, forward for the side it belongs to
+    pub fn push_pawn_moves(&self, pawn: &Piece, moves: &mut Vec<Move>) {
+        let board = self.variant.board();
+        let from = pawn.square();
+        let mut push = |to: (u8, u8, u8)| {
+            let must_promote = self.variant.is_promotion_square(pawn.color, to);
+            if must_promote || self.variant.is_optional_promotion_square(pawn.color, to) {
+                for &promotion in self.variant.promotions() {
+                    if self.variant.allows_promotion(self, pawn.color, promotion) {
+                        moves.push(Move {
+                            promotion: Some(promotion),
+                            ..Move::new(from, to)
+                        });
+                    }
+                }
+            }
+            if !must_promote {
+                moves.push(Move::new(from, to));
+            }
+        };
+
+        let forward = pawn.color.forward();
+        if let Some(ahead) = board
+            .offset(from, forward)
+            .filter(|&ahead| self.piece_at(ahead).is_none())
+        {
+            push(ahead);
+            // Counted along the direction the pawn moves in
+            let start = if forward.0 != 0 { pawn.x } else { pawn.y };
+            if start == self.variant.pawn_rank(pawn.color) {
+                if let Some(two) = board
+                    .offset(ahead, forward)
+                    .filter(|&two| self.piece_at(two).is_none())
+                {
+                    push(two);
+                }
+            }
+        }
+        for side in [-1, 1] {
+            let to = match board.offset(from, orient(pawn.color, (1, side))) {
+                Some(to) => to,
+                None => continue,
+            };
+            let takes = self
+                .piece_at(to)
+                .map_or(self.en_passant == Some(to), |target| {
+                    target.color != pawn.color
+                });
+            if takes {
+                push(to);
+            }
+        }
+    }
+
+    fn push_castling_moves(&self, moves: &mut Vec<Move>) {
+        let color = self.turn;
+        let sides = [CastlingSide::Queen, CastlingSide::King];
+        if sides
+            .iter()
+            .all(|&side| self.castling.get(color, side).is_none())
+        {
+            return;
+        }
+        let files = self.variant.board().files;
+        let rank = self.variant.board().back_rank(color);
+        let king = match self.king(color) {
+            Some(king) if king.x == rank && king.z == 0 => *king,
+            _ => return,
+        };
+        if self.is_in_check(color) {
+            return;
+        }
+        for side in sides {
+            let rook_file = match self.castling.get(color, side) {
+                Some(file) => file,
+                None => continue,
+            };
+            match self.piece_at((rank, rook_file, 0)) {
+                Some(rook) if rook.piece_type == PieceType::Rook && rook.color == color => {}
+                _ => continue,
+            }
+            let (king_to, rook_to) = side.destination_files(files);
+            // Every square either piece passes over or lands on has to be free, except for the
+            // castling king and rook themselves
+            let (king_from, rook_from) = (king.y, rook_file);
+            let span = |from: u8, to: u8| from.min(to)..=from.max(to);
+            let blocked = span(king_from, king_to)
+                .chain(span(rook_from, rook_to))
+                .any(|file| {
+                    file != king_from
+                        && file != rook_from
+                        && self.piece_at((rank, file, 0)).is_some()
+                });
+            if blocked {
+                continue;
+            }
+            // The king may not pass through or land on an attacked square
+            let attacked = span(king_from, king_to)
+                .any(|file| self.is_attacked((rank, file, 0), color.opponent()));
+            if attacked {
+                continue;
+            }
+            moves.push(Move::new((rank, king_from, 0), (rank, rook_file, 0)));
+        }
+    }
+
+    /// Moves the side to move may play, standard positions take the faster way over bitboards
+    pub fn legal_moves(&self) -> Vec<Move> {
+        if let Some(board) = Board::from_position(self) {
+            return board.legal_moves();
+        }
+        self.variant.legal_moves(self, self.pseudo_legal_moves())
+    }
+
+    /// Number of move sequences `depth` plies deep, to check and time the move generator
+    pub fn perft(&self, depth: u32) -> u64 {
+        if let Some(board) = Board::from_position(self) {
+            return board.perft(depth);
+        }
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.legal_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        moves
+            .iter()
+            .map(|mv| {
+                let mut after = self.clone();
+                after.make_move(mv);
+                after.perft(depth - 1)
+            })
+            .sum()
+    }
+
+    pub fn is_legal(&self, mv: &Move) -> bool {
+        self.legal_moves().contains(mv)
+    }
+
+    /// Find the legal move of the piece on `from` to `to`, clicking on the square the king ends up
+    /// on when castling also counts. Pawns promote to a queen, or to the first piece they may
+    /// when there is no queen to be had.
+    pub fn move_to(&self, from: (u8, u8, u8), to: (u8, u8, u8)) -> Option<Move> {
+        let moves = self.legal_moves();
+        let is_move_to = |mv: &&Move| mv.from == from && mv.to == to && mv.drop.is_none();
+        moves
+            .iter()
+            .filter(is_move_to)
+            .find(|mv| matches!(mv.promotion, None | Some(PieceType::Queen)))
+            .or_else(|| moves.iter().find(is_move_to))
+            .or_else(|| {
+                moves.iter().find(|mv| {
+                    mv.from == from
+                        && self.castling_destination(mv).map(|(king, _)| king) == Some(to)
+                })
+            })
+            .copied()
+    }
+
+    /// Where the king and the rook end up when castling
+    pub fn castling_destination(&self, mv: &Move) -> Option<((u8, u8, u8), (u8, u8, u8))> {
+        let side = self.castling_side(mv)?;
+        let (king, rook) = side.destination_files(self.variant.board().files);
+        Some(((mv.from.0, king, 0), (mv.from.0, rook, 0)))
+    }
+
+    /// Play a move without checking it, returns what changed on the board
+    pub fn make_move(&mut self, mv: &Move) -> MoveEffects {
+        let color = self.turn;
+        let mut effects = MoveEffects {
+            moved: Vec::with_capacity(2),
+            captured: None,
+            promotion: mv.promotion,
+            explosion: None,
+            exploded: Vec::new(),
+            dropped: None,
+        };
+
+        if let Some(piece_type) = mv.drop {
+            let piece = Piece {
+                color,
+                piece_type,
+                x: mv.to.0,
+                y: mv.to.1,
+                z: mv.to.2,
+            };
+            self.pieces.push(piece);
+            effects.dropped = Some(piece);
+            self.en_passant = None;
+            self.finish_move(color, piece_type == PieceType::Pawn);
+            let variant = self.variant;
+            variant.after_move(self, mv, &mut effects);
+            return effects;
+        }
+
+        let castling = self.castling_destination(mv);
+        let piece = *self.piece_at(mv.from).expect("there is a piece to move");
+
+        if let Some((king_to, rook_to)) = castling {
+            // Take both pieces off the board first, the king might land where the rook was
+            self.pieces
+                .retain(|piece| piece.square() != mv.from && piece.square() != mv.to);
+            self.pieces.push(Piece {
+                x: king_to.0,
+                y: king_to.1,
+                ..piece
+            });
+            self.pieces.push(Piece {
+                color,
+                piece_type: PieceType::Rook,
+                x: rook_to.0,
+                y: rook_to.1,
+                z: rook_to.2,
+            });
+            effects.moved.push((mv.from, king_to));
+            effects.moved.push((mv.to, rook_to));
+        } else {
+            // The pawn taken en passant is one step behind the square it passed over
+            let captured_square = if piece.piece_type == PieceType::Pawn
+                && Some(mv.to) == self.en_passant
+                && self.piece_at(mv.to).is_none()
+            {
+                let (dx, dy) = color.forward();
+                (
+                    (mv.to.0 as i8 - dx) as u8,
+                    (mv.to.1 as i8 - dy) as u8,
+                    mv.to.2,
+                )
+            } else {
+                mv.to
+            };
+            if let Some(index) = self
+                .pieces
+                .iter()
+                .position(|piece| piece.square() == captured_square)
+            {
+                effects.captured = Some(self.pieces.swap_remove(index));
+            }
+            let moving = self
+                .pieces
+                .iter_mut()
+                .find(|piece| piece.square() == mv.from)
+                .unwrap();
+            moving.x = mv.to.0;
+            moving.y = mv.to.1;
+            moving.z = mv.to.2;
+            if let Some(promotion) = mv.promotion {
+                moving.piece_type = promotion;
+            }
+            effects.moved.push((mv.from, mv.to));
+        }
+
+        // Update castling rights
+        let board = self.variant.board();
+        if piece.piece_type == PieceType::King {
+            self.castling.set(color, CastlingSide::Queen, None);
+            self.castling.set(color, CastlingSide::King, None);
+        } else if piece.piece_type == PieceType::Rook && mv.from.0 == board.back_rank(color) {
+            self.castling.remove_rook(color, mv.from.1);
+        }
+        if let Some(captured) = effects.captured {
+            if captured.piece_type == PieceType::Rook
+                && captured.x == board.back_rank(captured.color)
+            {
+                self.castling.remove_rook(captured.color, captured.y);
+            }
+        }
+
+        self.en_passant = (piece.piece_type == PieceType::Pawn && mv.from.0.abs_diff(mv.to.0) == 2)
+            .then(|| ((mv.from.0 + mv.to.0) / 2, mv.from.1, mv.from.2));
+
+        self.finish_move(
+            color,
+            piece.piece_type == PieceType::Pawn || effects.captured.is_some(),
+        );
+
+        let variant = self.variant;
+        variant.after_move(self, mv, &mut effects);
+        effects
+    }
+
+    /// Update the move counters and hand the turn to the opponent
+    fn finish_move(&mut self, color: PieceColor, reset_clock: bool) {
+        if reset_clock {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if self.variant.colors().last() == Some(&color) {
+            self.fullmove_number += 1;
+        }
+        self.turn = self.variant.next_turn(self, color);
+    }
+
+    /// The result if the game is over in this position
+    pub fn outcome(&self) -> Option<GameResult> {
+        self.variant.outcome(self, &self.legal_moves())
+    }
+}
+
+/// A step given as `(forward, sideways)` seen from the edge of the board `color` plays from,
+/// turned into a step in `(x, y)`
+pub fn orient(color: PieceColor, (forward, sideways): (i8, i8)) -> (i8, i8) {
+    let (dx, dy) = color.forward();
+    // To the right of the way forward
+    let (rx, ry) = (-dy, dx);
+    (forward * dx + sideways * rx, forward * dy + sideways * ry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STACKED: BoardShape = BoardShape {
+        files: 5,
+        ranks: 5,
+        levels: 5,
+        corners: 0,
+        hexagonal: false,
+    };
+
+    #[test]
+    fn square_names_round_trip() {
+        for board in [BoardShape::STANDARD, STACKED] {
+            for row in 0..board.rows() {
+                for square in board.row_squares(row) {
+                    let name = board.square_name(square);
+                    assert_eq!(board.parse_square(&name), Some(square), "{}", name);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn squares_off_the_board_are_rejected() {
+        for name in ["a0", "a9", "i1", "a255", "a256", "e"] {
+            assert_eq!(BoardShape::STANDARD.parse_square(name), None, "{}", name);
+        }
+        for name in ["Ea255", "Ea6", "Fa1", "Aa0", "a1"] {
+            assert_eq!(STACKED.parse_square(name), None, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,452 @@
+use std::{
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use bevy::{app::AppExit, ecs::system::SystemParam, prelude::*, window::WindowCloseRequested};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    board::ResetSelectedEvent,
+    clock::{Clock, TimeControl},
+    game::{Game, ResetBoardEvent},
+    menu::{spawn_button, spawn_label},
+    net::Connection,
+    notation::{parse_uci_moves, uci_moves},
+    offers::{ChatLog, PendingOffer},
+    pieces::PieceColor,
+    players::{PlayerKind, Players},
+    rules::{GameResult, Position},
+    variants::{find_variant, Standard, Variant},
+};
+
+/// Saving games to files, the games menu to resume them and saving the game on exit
+pub struct SavesPlugin;
+impl Plugin for SavesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(init_games_button)
+            .add_system(toggle_games_menu)
+            .add_system(press_save_buttons)
+            .add_system_to_stage(CoreStage::Last, autosave);
+    }
+}
+
+/// How many saved games the menu lists, newest first
+const LISTED_SAVES: usize = 10;
+
+const AUTOSAVE_NAME: &str = "autosave";
+
+/// Everything needed to go on with a game, stored as RON in the user data directory
+#[derive(Serialize, Deserialize)]
+pub struct SavedGame {
+    #[serde(default = "standard")]
+    pub variant: String,
+    pub start_fen: String,
+    /// FEN only tells Chess960 positions apart when castling is unusual
+    #[serde(default)]
+    pub chess960: bool,
+    /// Moves played from `start_fen`, in UCI notation
+    pub moves: Vec<String>,
+    pub white: PlayerKind,
+    pub black: PlayerKind,
+    /// The other two sides of four-player chess
+    #[serde(default = "human")]
+    pub blue: PlayerKind,
+    #[serde(default = "human")]
+    pub green: PlayerKind,
+    pub time_control: Option<TimeControl>,
+    /// Time left for white and black in milliseconds
+    pub clock_ms: Option<[u32; 2]>,
+    /// Time left for blue and green in four-player chess
+    #[serde(default)]
+    pub side_clock_ms: Option<[u32; 2]>,
+    /// How the game ended if the moves don't tell, like a resignation or a timeout
+    #[serde(default)]
+    pub result: Option<GameResult>,
+}
+
+impl SavedGame {
+    pub fn new(game: &Game, players: &Players, clock: &Clock) -> Self {
+        let remaining = |color| {
+            clock
+                .remaining(color)
+                .map(|seconds| (seconds * 1000.0) as u32)
+        };
+        Self {
+            variant: game.start.variant.name().to_string(),
+            start_fen: game.start.to_fen(),
+            chess960: game.start.chess960,
+            moves: uci_moves(&game.start, &game.move_list()),
+            white: players.get(PieceColor::Light).clone(),
+            black: players.get(PieceColor::Dark).clone(),
+            blue: players.get(PieceColor::Blue).clone(),
+            green: players.get(PieceColor::Green).clone(),
+            time_control: clock.time_control,
+            clock_ms: remaining(PieceColor::Light)
+                .zip(remaining(PieceColor::Dark))
+                .map(|(white, black)| [white, black]),
+            side_clock_ms: remaining(PieceColor::Blue)
+                .zip(remaining(PieceColor::Green))
+                .map(|(blue, green)| [blue, green]),
+            result: game.result.clone(),
+        }
+    }
+
+    pub fn dir() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("bevy-chess-3d").join("saves"))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let ron = std::fs::read_to_string(path)
+            .map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+        ron::from_str(&ron).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    /// Write the game to `name.ron` in the saves directory
+    pub fn save(&self, name: &str) -> Result<PathBuf, String> {
+        let dir = Self::dir().ok_or("no data directory")?;
+        std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+        let path = dir.join(format!("{}.ron", name));
+        let ron =
+            ron::ser::to_string_pretty(self, Default::default()).map_err(|err| err.to_string())?;
+        std::fs::write(&path, ron).map_err(|err| err.to_string())?;
+        Ok(path)
+    }
+
+    pub fn game(&self) -> Result<Game, String> {
+        let variant = find_variant(&self.variant)?;
+        let mut start = Position::from_variant_fen(variant, &self.start_fen)?;
+        start.chess960 |= self.chess960;
+        let moves = parse_uci_moves(&start, &self.moves)?;
+        let mut game = Game::from_moves(start, &moves);
+        if let Some(result) = &self.result {
+            game.end(result.clone());
+        }
+        Ok(game)
+    }
+
+    pub fn players(&self) -> Players {
+        Players::new(
+            self.white.clone(),
+            self.black.clone(),
+            self.blue.clone(),
+            self.green.clone(),
+        )
+    }
+
+    pub fn clock(&self) -> Clock {
+        let mut clock = Clock::new(self.time_control);
+        let sides = [
+            (PieceColor::Light, PieceColor::Dark, self.clock_ms),
+            (PieceColor::Blue, PieceColor::Green, self.side_clock_ms),
+        ];
+        for (first, second, clock_ms) in sides {
+            if let Some([first_ms, second_ms]) = clock_ms {
+                clock.set_remaining(first, first_ms as f32 / 1000.0);
+                clock.set_remaining(second, second_ms as f32 / 1000.0);
+            }
+        }
+        clock
+    }
+
+    /// Short description for the games menu
+    fn describe(&self, name: &str) -> String {
+        let title = if name == AUTOSAVE_NAME {
+            "Autosave".to_string()
+        } else {
+            name.replacen("game-", "Game ", 1)
+        };
+        let sides = find_variant(&self.variant).map_or(2, |variant| variant.colors().len());
+        let progress = match &self.result {
+            Some(result) => result.to_string(),
+            None => format!("move {}", self.moves.len() / sides + 1),
+        };
+        format!("{}: {} vs {}, {}", title, self.white, self.black, progress)
+    }
+}
+
+/// Games saved before four-player chess only had white and black players
+fn human() -> PlayerKind {
+    PlayerKind::Human
+}
+
+/// Games saved before there were variants are standard chess
+fn standard() -> String {
+    Standard.name().to_string()
+}
+
+/// Saved games with their file names, newest first
+fn saved_games() -> Vec<(String, SavedGame)> {
+    let entries = match SavedGame::dir().and_then(|dir| std::fs::read_dir(dir).ok()) {
+        Some(entries) => entries,
+        None => return Vec::new(),
+    };
+    let mut files: Vec<(SystemTime, PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .map_or(false, |extension| extension == "ron")
+        })
+        .filter_map(|path| Some((path.metadata().ok()?.modified().ok()?, path)))
+        .collect();
+    files.sort_by(|a, b| b.0.cmp(&a.0));
+    files
+        .into_iter()
+        .filter_map(|(_, path)| {
+            let name = path.file_stem()?.to_string_lossy().to_string();
+            match SavedGame::load(&path) {
+                Ok(saved) => Some((name, saved)),
+                Err(err) => {
+                    warn!("Invalid saved game {}", err);
+                    None
+                }
+            }
+        })
+        .take(LISTED_SAVES)
+        .collect()
+}
+
+// Component to mark the button opening the games menu
+#[derive(Component)]
+struct GamesButton;
+
+// Component to mark the root node of the games menu
+#[derive(Component)]
+struct GamesMenu;
+
+#[derive(Component)]
+enum SaveButton {
+    Save,
+    /// Resume the saved game with this file name
+    Load(String),
+}
+
+fn init_games_button(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(250.0),
+                    top: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .with_children(|parent| spawn_button(parent, font, "Games", GamesButton));
+}
+
+/// Open or close the games menu with its button or F2, the saved games are listed when it opens
+fn toggle_games_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    keys: Res<Input<KeyCode>>,
+    buttons: Query<&Interaction, (Changed<Interaction>, With<GamesButton>)>,
+    menus: Query<Entity, With<GamesMenu>>,
+) {
+    let clicked = buttons
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    if !clicked && !keys.just_pressed(KeyCode::F2) {
+        return;
+    }
+
+    let mut was_open = false;
+    for menu in menus.iter() {
+        commands.entity(menu).despawn_recursive();
+        was_open = true;
+    }
+    if was_open {
+        return;
+    }
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let saves = saved_games();
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(250.0),
+                    top: Val::Px(70.0),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                padding: Rect::all(Val::Px(10.0)),
+                ..Default::default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.7).into(),
+            ..Default::default()
+        })
+        .insert(GamesMenu)
+        .with_children(|parent| {
+            spawn_button(parent, font.clone(), "Save game", SaveButton::Save);
+            spawn_label(parent, font.clone(), "Resume");
+            if saves.is_empty() {
+                spawn_label(parent, font.clone(), "No saved games yet");
+            }
+            for (name, saved) in saves {
+                spawn_label(parent, font.clone(), &saved.describe(&name));
+                spawn_button(parent, font.clone(), "Resume", SaveButton::Load(name));
+            }
+        });
+}
+
+/// The resources a saved game replaces
+#[derive(SystemParam)]
+struct GameState<'w, 's> {
+    game: ResMut<'w, Game>,
+    players: ResMut<'w, Players>,
+    clock: ResMut<'w, Clock>,
+    pending: ResMut<'w, PendingOffer>,
+    log: ResMut<'w, ChatLog>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+fn press_save_buttons(
+    mut commands: Commands,
+    connection: Option<Res<Connection>>,
+    mut state: GameState,
+    buttons: Query<(&Interaction, &SaveButton), Changed<Interaction>>,
+    menus: Query<Entity, With<GamesMenu>>,
+    mut resets: EventWriter<ResetBoardEvent>,
+    mut reset_selected: EventWriter<ResetSelectedEvent>,
+) {
+    for (interaction, button) in buttons.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        // Both players of a network game would have to agree
+        if connection.is_some() {
+            state
+                .log
+                .add("Network games can't be saved or replaced".to_string());
+            continue;
+        }
+        match button {
+            SaveButton::Save => {
+                let saved = SavedGame::new(&state.game, &state.players, &state.clock);
+                match saved.save(&next_save_name()) {
+                    Ok(path) => state.log.add(format!("Saved to {}", path.display())),
+                    Err(err) => error!("Could not save the game: {}", err),
+                }
+            }
+            SaveButton::Load(name) => {
+                let loaded = SavedGame::dir()
+                    .ok_or_else(|| "no data directory".to_string())
+                    .and_then(|dir| SavedGame::load(&dir.join(format!("{}.ron", name))))
+                    .and_then(|saved| Ok((saved.game()?, saved)));
+                let (game, saved) = match loaded {
+                    Ok(loaded) => loaded,
+                    Err(err) => {
+                        error!("Could not resume the game: {}", err);
+                        continue;
+                    }
+                };
+                *state.game = game;
+                *state.players = saved.players();
+                *state.clock = saved.clock();
+                *state.pending = PendingOffer::default();
+                *state.log = ChatLog::default();
+                resets.send(ResetBoardEvent);
+                reset_selected.send(ResetSelectedEvent);
+            }
+        }
+        for menu in menus.iter() {
+            commands.entity(menu).despawn_recursive();
+        }
+    }
+}
+
+/// The first free `game-N` file name
+fn next_save_name() -> String {
+    let dir = SavedGame::dir();
+    (1..)
+        .map(|number| format!("game-{}", number))
+        .find(|name| {
+            dir.as_ref()
+                .map_or(true, |dir| !dir.join(format!("{}.ron", name)).exists())
+        })
+        .unwrap()
+}
+
+/// Save unfinished games when the window is closed, they can be resumed from the games menu
+fn autosave(
+    game: Res<Game>,
+    players: Res<Players>,
+    clock: Res<Clock>,
+    connection: Option<Res<Connection>>,
+    mut close_requests: EventReader<WindowCloseRequested>,
+    mut exits: EventReader<AppExit>,
+    mut saved: Local<bool>,
+) {
+    let exiting = close_requests.iter().count() > 0 || exits.iter().count() > 0;
+    if !exiting || *saved {
+        return;
+    }
+    *saved = true;
+    if connection.is_some() || game.is_over() || game.moves.is_empty() {
+        return;
+    }
+    match SavedGame::new(&game, &players, &clock).save(AUTOSAVE_NAME) {
+        Ok(path) => info!("Saved the game to {}", path.display()),
+        Err(err) => error!("Could not save the game: {}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variants::FourPlayer;
+
+    #[test]
+    fn four_player_clocks_are_saved_for_every_side() {
+        let game = Game::new(Position::starting(&FourPlayer::FREE_FOR_ALL));
+        let mut clock = Clock::new(Some(TimeControl {
+            minutes: 5,
+            increment_seconds: 0,
+        }));
+        for (color, seconds) in [
+            (PieceColor::Light, 10.0),
+            (PieceColor::Blue, 20.0),
+            (PieceColor::Dark, 30.0),
+            (PieceColor::Green, 40.0),
+        ] {
+            clock.set_remaining(color, seconds);
+        }
+        let saved = SavedGame::new(&game, &Players::default(), &clock);
+        let ron = ron::ser::to_string(&saved).unwrap();
+        let loaded: SavedGame = ron::from_str(&ron).unwrap();
+        let loaded = loaded.clock();
+        for color in FourPlayer::FREE_FOR_ALL.colors() {
+            assert_eq!(loaded.remaining(*color), clock.remaining(*color));
+        }
+    }
+
+    #[test]
+    fn results_and_move_numbers_are_saved() {
+        let mut game = Game::new(Position::starting(&FourPlayer::TEAMS));
+        let start = game.start.clone();
+        let uci = ["e2e4", "b7c7", "h13h12", "m8l8"].map(String::from);
+        for mv in parse_uci_moves(&start, &uci).unwrap() {
+            game.play(&mv);
+        }
+        let saved = SavedGame::new(&game, &Players::default(), &Clock::new(None));
+        assert_eq!(saved.describe("game-1"), "Game 1: Human vs Human, move 2");
+
+        let resigned = GameResult::win(PieceColor::Blue, "resignation of White");
+        game.end(resigned.clone());
+        let saved = SavedGame::new(&game, &Players::default(), &Clock::new(None));
+        let ron = ron::ser::to_string(&saved).unwrap();
+        let loaded: SavedGame = ron::from_str(&ron).unwrap();
+        assert_eq!(loaded.game().unwrap().result, Some(resigned));
+    }
+}
@@ -0,0 +1,897 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hasher},
+    io::BufReader,
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock::TimeControl,
+    game::Game,
+    net::{self, GameInfo, Message, SeekInfo, PROTOCOL_VERSION},
+    notation::uci_moves,
+    offers::{takeback_plies, Action, Offer},
+    pieces::PieceColor,
+    rules::GameResult,
+};
+
+/// How often the server checks clocks and abandoned games while no messages arrive
+const TICK: Duration = Duration::from_millis(100);
+
+pub struct ServerConfig {
+    pub address: String,
+    /// How long a player who lost their connection has to come back before they lose
+    pub reconnect_grace: Duration,
+}
+
+type ClientId = u32;
+type GameId = u32;
+
+/// What the network threads pass on to the server loop
+enum Event {
+    Connected(ClientId, TcpStream),
+    Message(ClientId, Message),
+    Disconnected(ClientId),
+}
+
+struct Client {
+    stream: TcpStream,
+    /// Set once the client said hello with the right protocol version
+    greeted: bool,
+    game: Option<GameId>,
+    /// The game this client follows as a spectator
+    watching: Option<GameId>,
+}
+
+struct Seek {
+    id: u32,
+    client: ClientId,
+    time_control: Option<TimeControl>,
+}
+
+/// A game the server is the referee of
+struct ServerGame {
+    game: Game,
+    time_control: Option<TimeControl>,
+    /// The clients playing white and black, `None` while they are disconnected
+    players: [Option<ClientId>; 2],
+    tokens: [String; 2],
+    /// Seconds left for white and black
+    remaining: [f32; 2],
+    turn_started: Instant,
+    left_at: [Option<Instant>; 2],
+    spectators: Vec<ClientId>,
+    /// Offer waiting for an answer, from the side that made it
+    pending: Option<(PieceColor, Offer)>,
+}
+
+impl ServerGame {
+    /// Seconds left for `color` right now, counting the running clock
+    fn time_left(&self, color: PieceColor, now: Instant) -> f32 {
+        let mut seconds = self.remaining[color.index()];
+        if self.clock_running() && self.game.position.turn == color {
+            seconds -= now.duration_since(self.turn_started).as_secs_f32();
+        }
+        seconds
+    }
+
+    /// Clocks start after the first move, like on the board
+    fn clock_running(&self) -> bool {
+        self.time_control.is_some() && !self.game.moves.is_empty()
+    }
+
+    fn clock_ms(&self, now: Instant) -> Option<[u32; 2]> {
+        self.time_control.map(|_| {
+            [PieceColor::Light, PieceColor::Dark]
+                .map(|color| (self.time_left(color, now).max(0.0) * 1000.0) as u32)
+        })
+    }
+
+    fn started_message(&self, color: PieceColor, now: Instant) -> Message {
+        Message::GameStarted {
+            your_color: color,
+            time_control: self.time_control,
+            variant: self.game.start.variant.name().to_string(),
+            start_fen: self.game.start.to_fen(),
+            chess960: self.game.start.chess960,
+            moves: uci_moves(&self.game.start, &self.game.move_list()),
+            clock_ms: self.clock_ms(now),
+            token: self.tokens[color.index()].clone(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Server {
+    grace: Duration,
+    clients: HashMap<ClientId, Client>,
+    seeks: Vec<Seek>,
+    games: HashMap<GameId, ServerGame>,
+    next_seek: u32,
+    next_game: GameId,
+}
+
+/// Run the server until the listener fails, clients are handled on their own threads and the
+/// games on this one
+pub fn run(config: ServerConfig) -> Result<(), String> {
+    let address = net::with_default_port(&config.address);
+    let listener = TcpListener::bind(&address)
+        .map_err(|err| format!("could not listen on {}: {}", address, err))?;
+    println!("Listening on {}", address);
+    serve(listener, config.reconnect_grace)
+}
+
+fn serve(listener: TcpListener, reconnect_grace: Duration) -> Result<(), String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || accept_clients(listener, sender));
+
+    let mut server = Server {
+        grace: reconnect_grace,
+        ..Default::default()
+    };
+    server.run(receiver)
+}
+
+fn accept_clients(listener: TcpListener, sender: Sender<Event>) {
+    let mut next_id: ClientId = 0;
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("Could not accept a connection: {}", err);
+                continue;
+            }
+        };
+        let reader = match stream.try_clone() {
+            Ok(reader) => BufReader::new(reader),
+            Err(err) => {
+                eprintln!("Could not use a connection: {}", err);
+                continue;
+            }
+        };
+        let _ = stream.set_nodelay(true);
+        next_id += 1;
+        let id = next_id;
+        if sender.send(Event::Connected(id, stream)).is_err() {
+            return;
+        }
+        let sender = sender.clone();
+        thread::spawn(move || read_client(id, reader, sender));
+    }
+}
+
+fn read_client(id: ClientId, mut reader: BufReader<TcpStream>, sender: Sender<Event>) {
+    while let Ok(message) = net::read_message(&mut reader) {
+        if sender.send(Event::Message(id, message)).is_err() {
+            return;
+        }
+    }
+    let _ = sender.send(Event::Disconnected(id));
+}
+
+impl Server {
+    fn run(&mut self, receiver: Receiver<Event>) -> Result<(), String> {
+        loop {
+            match receiver.recv_timeout(TICK) {
+                Ok(Event::Connected(id, stream)) => {
+                    self.clients.insert(
+                        id,
+                        Client {
+                            stream,
+                            greeted: false,
+                            game: None,
+                            watching: None,
+                        },
+                    );
+                }
+                Ok(Event::Message(id, message)) => self.handle(id, message),
+                Ok(Event::Disconnected(id)) => self.disconnect(id),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err("stopped accepting connections".to_string())
+                }
+            }
+            self.tick(Instant::now());
+        }
+    }
+
+    fn send(&mut self, id: ClientId, message: &Message) {
+        if let Some(client) = self.clients.get_mut(&id) {
+            // A client that can't be written to is dropped once its reader notices
+            if let Err(err) = net::write_message(&mut client.stream, message) {
+                eprintln!("Client {}: {}", id, err);
+            }
+        }
+    }
+
+    fn handle(&mut self, id: ClientId, message: Message) {
+        let greeted = match self.clients.get(&id) {
+            Some(client) => client.greeted,
+            None => return,
+        };
+        match message {
+            Message::Hello { version } if version == PROTOCOL_VERSION => {
+                self.clients.get_mut(&id).unwrap().greeted = true;
+                let lobby = self.lobby();
+                self.send(id, &lobby);
+            }
+            Message::Hello { version } => {
+                let reason = format!(
+                    "protocol version {} is not supported, the server speaks {}",
+                    version, PROTOCOL_VERSION
+                );
+                self.send(id, &Message::Reject { reason });
+                self.drop_client(id);
+            }
+            _ if !greeted => {
+                let reason = "expected Hello".to_string();
+                self.send(id, &Message::Reject { reason });
+                self.drop_client(id);
+            }
+            Message::Seek { time_control } => self.seek(id, time_control),
+            Message::AcceptSeek { id: seek } => self.accept_seek(id, seek),
+            Message::Resume { token } => self.resume(id, &token),
+            Message::Watch { id: game } => self.watch(id, game),
+            Message::Action { action, .. } => self.act(id, action),
+            Message::Move {
+                ply,
+                uci,
+                fen_after,
+                ..
+            } => self.play(id, ply, &uci, &fen_after),
+            Message::Desync { reason } => {
+                if let Some(game_id) = self.clients[&id].game {
+                    eprintln!("Game {} is out of sync: {}", game_id, reason);
+                    self.end_game(game_id, GameResult::aborted("the boards are out of sync"));
+                }
+            }
+            other => {
+                let reason = format!("unexpected message {:?}", other);
+                self.send(id, &Message::Reject { reason });
+            }
+        }
+    }
+
+    fn lobby(&self) -> Message {
+        Message::Lobby {
+            seeks: self
+                .seeks
+                .iter()
+                .map(|seek| SeekInfo {
+                    id: seek.id,
+                    time_control: seek.time_control,
+                })
+                .collect(),
+            games: self.game_list(),
+        }
+    }
+
+    fn game_list(&self) -> Vec<GameInfo> {
+        let mut games: Vec<GameInfo> = self
+            .games
+            .iter()
+            .map(|(id, game)| GameInfo {
+                id: *id,
+                time_control: game.time_control,
+                plies: game.game.moves.len(),
+            })
+            .collect();
+        games.sort_by_key(|game| game.id);
+        games
+    }
+
+    /// Tell everyone in the lobby about the open seeks and running games
+    fn broadcast_lobby(&mut self) {
+        let lobby = self.lobby();
+        let idle: Vec<ClientId> = self
+            .clients
+            .iter()
+            .filter(|(_, client)| {
+                client.greeted && client.game.is_none() && client.watching.is_none()
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        for id in idle {
+            self.send(id, &lobby);
+        }
+    }
+
+    fn seek(&mut self, client: ClientId, time_control: Option<TimeControl>) {
+        if self.clients[&client].game.is_some() {
+            let reason = "already playing".to_string();
+            self.send(client, &Message::Reject { reason });
+            return;
+        }
+        // One seek per client, a new one replaces the old
+        self.seeks.retain(|seek| seek.client != client);
+        self.next_seek += 1;
+        let id = self.next_seek;
+        self.seeks.push(Seek {
+            id,
+            client,
+            time_control,
+        });
+        println!(
+            "Client {} seeks a game ({})",
+            client,
+            describe(time_control)
+        );
+        self.send(client, &Message::Seeking { id });
+        self.broadcast_lobby();
+    }
+
+    fn accept_seek(&mut self, client: ClientId, seek_id: u32) {
+        let index = match self.seeks.iter().position(|seek| seek.id == seek_id) {
+            Some(index) => index,
+            None => {
+                let reason = format!("there is no seek {}", seek_id);
+                self.send(client, &Message::Reject { reason });
+                return;
+            }
+        };
+        if self.seeks[index].client == client || self.clients[&client].game.is_some() {
+            let reason = "can't take up this seek".to_string();
+            self.send(client, &Message::Reject { reason });
+            return;
+        }
+        let seek = self.seeks.remove(index);
+        self.seeks.retain(|other| other.client != client);
+
+        let white_first = random() % 2 == 0;
+        let players = if white_first {
+            [seek.client, client]
+        } else {
+            [client, seek.client]
+        };
+        let seconds = seek
+            .time_control
+            .map_or(0.0, |time_control| time_control.minutes as f32 * 60.0);
+        self.next_game += 1;
+        let game_id = self.next_game;
+        let game = ServerGame {
+            game: Game::default(),
+            time_control: seek.time_control,
+            players: players.map(Some),
+            tokens: [token(), token()],
+            remaining: [seconds; 2],
+            turn_started: Instant::now(),
+            left_at: [None; 2],
+            spectators: Vec::new(),
+            pending: None,
+        };
+        println!(
+            "Game {}: client {} plays white, client {} plays black ({})",
+            game_id,
+            players[0],
+            players[1],
+            describe(seek.time_control)
+        );
+
+        let now = Instant::now();
+        for color in [PieceColor::Light, PieceColor::Dark] {
+            let player = players[color.index()];
+            self.clients.get_mut(&player).unwrap().game = Some(game_id);
+            let started = game.started_message(color, now);
+            self.send(player, &started);
+        }
+        self.games.insert(game_id, game);
+        self.broadcast_lobby();
+    }
+
+    fn resume(&mut self, client: ClientId, token: &str) {
+        let found = self.games.iter().find_map(|(id, game)| {
+            game.tokens
+                .iter()
+                .position(|other| other == token)
+                .map(|index| (*id, index))
+        });
+        let (game_id, index) = match found {
+            Some(found) => found,
+            None => {
+                let reason = "there is no game to resume for this token".to_string();
+                self.send(client, &Message::Reject { reason });
+                return;
+            }
+        };
+        let color = if index == 0 {
+            PieceColor::Light
+        } else {
+            PieceColor::Dark
+        };
+
+        let game = self.games.get_mut(&game_id).unwrap();
+        // The old connection may not have been noticed as closed yet
+        let old = game.players[index].replace(client);
+        game.left_at[index] = None;
+        let opponent = game.players[color.opponent().index()];
+        let started = game.started_message(color, Instant::now());
+        if let Some(old) = old {
+            self.drop_client(old);
+        }
+        self.seeks.retain(|seek| seek.client != client);
+        self.clients.get_mut(&client).unwrap().game = Some(game_id);
+        println!("Game {}: client {} is back as {}", game_id, client, color);
+        self.send(client, &started);
+        if let Some(opponent) = opponent {
+            self.send(opponent, &Message::OpponentBack);
+        }
+    }
+
+    fn watch(&mut self, client: ClientId, game_id: GameId) {
+        let game = match self.games.get_mut(&game_id) {
+            Some(game) => game,
+            None => {
+                let reason = format!("there is no game {}", game_id);
+                self.send(client, &Message::Reject { reason });
+                return;
+            }
+        };
+        game.spectators.push(client);
+        let watching = Message::Watching {
+            time_control: game.time_control,
+            variant: game.game.start.variant.name().to_string(),
+            start_fen: game.game.start.to_fen(),
+            chess960: game.game.start.chess960,
+            moves: uci_moves(&game.game.start, &game.game.move_list()),
+            clock_ms: game.clock_ms(Instant::now()),
+        };
+        self.seeks.retain(|seek| seek.client != client);
+        self.clients.get_mut(&client).unwrap().watching = Some(game_id);
+        println!("Game {}: client {} is watching", game_id, client);
+        self.send(client, &watching);
+    }
+
+    fn play(&mut self, client: ClientId, ply: usize, uci: &str, fen_after: &str) {
+        let game_id = match self.clients[&client].game {
+            Some(game_id) => game_id,
+            None => return,
+        };
+        let now = Instant::now();
+        let game = self.games.get_mut(&game_id).unwrap();
+        let color = game.game.position.turn;
+        if game.players[color.index()] != Some(client) {
+            // Moves sent again after a reconnect may already have been played
+            if ply >= game.game.moves.len() {
+                let reason = format!("got move {} while it is not your turn", uci);
+                self.send(client, &Message::Reject { reason });
+            }
+            return;
+        }
+        if ply < game.game.moves.len() {
+            return;
+        }
+        let mv = match net::check_move(&game.game, ply, uci, fen_after) {
+            Ok(mv) => mv,
+            Err(err) => {
+                println!(
+                    "Game {}: {} played an illegal move: {}",
+                    game_id, color, err
+                );
+                self.end_game(game_id, GameResult::win(color.opponent(), "illegal move"));
+                return;
+            }
+        };
+
+        if game.time_control.is_some() {
+            let left = game.time_left(color, now);
+            if left <= 0.0 {
+                self.end_game(game_id, GameResult::win(color.opponent(), "timeout"));
+                return;
+            }
+            game.remaining[color.index()] = left;
+        }
+        let clock_ms = game
+            .time_control
+            .map(|_| (game.remaining[color.index()] * 1000.0) as u32);
+        if let Some(time_control) = game.time_control {
+            game.remaining[color.index()] += time_control.increment_seconds as f32;
+        }
+        game.game.play(&mv);
+        // Playing on declines the opponent's offer
+        if game.pending.map_or(false, |(from, _)| from != color) {
+            game.pending = None;
+        }
+        game.turn_started = now;
+
+        let relayed = Message::Move {
+            ply,
+            uci: uci.to_string(),
+            fen_after: fen_after.to_string(),
+            clock_ms,
+        };
+        // Spectators get the move like the opponent, both add the increment themselves
+        let receivers: Vec<ClientId> = game.players[color.opponent().index()]
+            .iter()
+            .chain(&game.spectators)
+            .copied()
+            .collect();
+        let clocks = game.clock_ms(now);
+        let result = game.game.result.clone();
+        for receiver in receivers {
+            self.send(receiver, &relayed);
+        }
+        if let Some([white_ms, black_ms]) = clocks {
+            self.send(client, &Message::Clock { white_ms, black_ms });
+        }
+        if let Some(result) = result {
+            self.end_game(game_id, result);
+        }
+    }
+
+    /// Relay resigning, offers and chat to everyone in the game, and apply accepted offers
+    fn act(&mut self, client: ClientId, action: Action) {
+        let game_id = match self.clients[&client].game {
+            Some(game_id) => game_id,
+            None => return,
+        };
+        let now = Instant::now();
+        let game = self.games.get_mut(&game_id).unwrap();
+        let color = match game
+            .players
+            .iter()
+            .position(|player| *player == Some(client))
+        {
+            Some(0) => PieceColor::Light,
+            Some(_) => PieceColor::Dark,
+            None => return,
+        };
+
+        let mut result = None;
+        match &action {
+            Action::Chat(_) => {}
+            Action::Resign => result = Some(GameResult::win(color.opponent(), "resignation")),
+            Action::Offer(offer) => {
+                if *offer == Offer::Takeback && takeback_plies(&game.game, color).is_none() {
+                    return;
+                }
+                game.pending = Some((color, *offer));
+            }
+            Action::Accept | Action::Decline => {
+                let offer = match game.pending.take() {
+                    Some((from, offer)) if from != color => offer,
+                    other => {
+                        game.pending = other;
+                        return;
+                    }
+                };
+                if action == Action::Accept {
+                    match offer {
+                        Offer::Draw => result = Some(GameResult::draw("agreement")),
+                        Offer::Takeback => {
+                            if let Some(plies) = takeback_plies(&game.game, color.opponent()) {
+                                // Charge the running clock before the side to move changes
+                                let turn = game.game.position.turn;
+                                if game.clock_running() {
+                                    game.remaining[turn.index()] = game.time_left(turn, now);
+                                }
+                                game.game.take_back(plies);
+                                game.turn_started = now;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let message = Message::Action { color, action };
+        let receivers: Vec<ClientId> = game.players[color.opponent().index()]
+            .iter()
+            .chain(&game.spectators)
+            .copied()
+            .collect();
+        for receiver in receivers {
+            self.send(receiver, &message);
+        }
+        if let Some(result) = result {
+            self.end_game(game_id, result);
+        }
+    }
+
+    fn disconnect(&mut self, client: ClientId) {
+        let had_seek = self.seeks.iter().any(|seek| seek.client == client);
+        self.seeks.retain(|seek| seek.client != client);
+        match self.clients.remove(&client) {
+            Some(Client {
+                game: Some(game_id),
+                ..
+            }) => self.leave_game(game_id, client),
+            Some(Client {
+                watching: Some(game_id),
+                ..
+            }) => {
+                if let Some(game) = self.games.get_mut(&game_id) {
+                    game.spectators.retain(|spectator| *spectator != client);
+                }
+            }
+            _ => {}
+        }
+        if had_seek {
+            self.broadcast_lobby();
+        }
+    }
+
+    /// The player keeps their seat for a while, the game is only aborted when both are gone
+    fn leave_game(&mut self, game_id: GameId, client: ClientId) {
+        let grace_seconds = self.grace.as_secs() as u32;
+        let game = match self.games.get_mut(&game_id) {
+            Some(game) => game,
+            None => return,
+        };
+        let index = match game
+            .players
+            .iter()
+            .position(|player| *player == Some(client))
+        {
+            Some(index) => index,
+            None => return,
+        };
+        game.players[index] = None;
+        game.left_at[index] = Some(Instant::now());
+        println!("Game {}: client {} left", game_id, client);
+        match game.players[1 - index] {
+            Some(opponent) => self.send(opponent, &Message::OpponentLeft { grace_seconds }),
+            None => self.end_game(game_id, GameResult::aborted("both players left")),
+        }
+    }
+
+    fn drop_client(&mut self, client: ClientId) {
+        if let Some(client) = self.clients.get(&client) {
+            let _ = client.stream.shutdown(std::net::Shutdown::Both);
+        }
+    }
+
+    /// Flag players who ran out of time and those who didn't come back
+    fn tick(&mut self, now: Instant) {
+        let mut ended = Vec::new();
+        for (id, game) in &self.games {
+            let turn = game.game.position.turn;
+            if game.clock_running() && game.time_left(turn, now) <= 0.0 {
+                ended.push((*id, GameResult::win(turn.opponent(), "timeout")));
+                continue;
+            }
+            for color in [PieceColor::Light, PieceColor::Dark] {
+                let gone = game.left_at[color.index()]
+                    .map_or(false, |left_at| now.duration_since(left_at) >= self.grace);
+                if gone {
+                    ended.push((*id, GameResult::win(color.opponent(), "abandonment")));
+                    break;
+                }
+            }
+        }
+        for (id, result) in ended {
+            self.end_game(id, result);
+        }
+    }
+
+    fn end_game(&mut self, game_id: GameId, result: GameResult) {
+        let game = match self.games.remove(&game_id) {
+            Some(game) => game,
+            None => return,
+        };
+        println!("Game {}: {}", game_id, result);
+        let game_over = Message::GameOver { result };
+        for player in game.players.iter().flatten().chain(&game.spectators) {
+            self.send(*player, &game_over);
+            if let Some(client) = self.clients.get_mut(player) {
+                client.game = None;
+                client.watching = None;
+            }
+        }
+        self.broadcast_lobby();
+    }
+}
+
+/// Every `RandomState` is seeded differently, which is random enough for colors and tokens
+fn random() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+/// A hard to guess token that lets a player back into their game
+fn token() -> String {
+    format!("{:016x}{:016x}", random(), random())
+}
+
+fn describe(time_control: Option<TimeControl>) -> String {
+    match time_control {
+        Some(time_control) => time_control.to_string(),
+        None => "untimed".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        net::{Connection, LobbyRequest},
+        notation::parse_uci,
+        rules::Position,
+    };
+
+    /// A client speaking the protocol by hand
+    struct TestClient {
+        stream: TcpStream,
+        reader: BufReader<TcpStream>,
+    }
+
+    impl TestClient {
+        fn connect(address: &str) -> Self {
+            let stream = TcpStream::connect(address).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            let reader = BufReader::new(stream.try_clone().unwrap());
+            let mut client = Self { stream, reader };
+            client.send(Message::Hello {
+                version: PROTOCOL_VERSION,
+            });
+            client
+        }
+
+        fn send(&mut self, message: Message) {
+            net::write_message(&mut self.stream, &message).unwrap();
+        }
+
+        /// The next message that isn't a lobby update
+        fn receive(&mut self) -> Message {
+            loop {
+                match net::read_message(&mut self.reader).unwrap() {
+                    Message::Lobby { .. } => {}
+                    message => return message,
+                }
+            }
+        }
+
+        fn game_started(&mut self) -> PieceColor {
+            match self.receive() {
+                Message::GameStarted {
+                    your_color,
+                    variant,
+                    moves,
+                    ..
+                } => {
+                    assert_eq!(variant, "Standard");
+                    assert!(moves.is_empty());
+                    your_color
+                }
+                other => panic!("expected GameStarted, got {:?}", other),
+            }
+        }
+    }
+
+    fn start_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || serve(listener, Duration::from_secs(60)));
+        address
+    }
+
+    #[test]
+    fn pairs_players_relays_moves_and_referees() {
+        let address = start_server();
+        let mut seeker = TestClient::connect(&address);
+        seeker.send(Message::Seek { time_control: None });
+        let seek = match seeker.receive() {
+            Message::Seeking { id } => id,
+            other => panic!("expected Seeking, got {:?}", other),
+        };
+        let mut taker = TestClient::connect(&address);
+        taker.send(Message::AcceptSeek { id: seek });
+        let seeker_color = seeker.game_started();
+        let taker_color = taker.game_started();
+        assert_eq!(seeker_color, taker_color.opponent());
+        let (mut white, mut black) = if seeker_color == PieceColor::Light {
+            (seeker, taker)
+        } else {
+            (taker, seeker)
+        };
+
+        let mut spectator = TestClient::connect(&address);
+        spectator.send(Message::Watch { id: 1 });
+        match spectator.receive() {
+            Message::Watching { variant, moves, .. } => {
+                assert_eq!(variant, "Standard");
+                assert!(moves.is_empty());
+            }
+            other => panic!("expected Watching, got {:?}", other),
+        }
+
+        let mut position = Position::default();
+        let mv = parse_uci(&position, "e2e4").unwrap();
+        position.make_move(&mv);
+        white.send(Message::Move {
+            ply: 0,
+            uci: "e2e4".to_string(),
+            fen_after: position.to_fen(),
+            clock_ms: None,
+        });
+        for receiver in [&mut black, &mut spectator] {
+            match receiver.receive() {
+                Message::Move {
+                    ply,
+                    uci,
+                    fen_after,
+                    ..
+                } => {
+                    assert_eq!((ply, uci.as_str()), (0, "e2e4"));
+                    assert_eq!(fen_after, position.to_fen());
+                }
+                other => panic!("expected the move, got {:?}", other),
+            }
+        }
+
+        // The server is the referee, an illegal move loses
+        black.send(Message::Move {
+            ply: 1,
+            uci: "e7e4".to_string(),
+            fen_after: position.to_fen(),
+            clock_ms: None,
+        });
+        for receiver in [&mut white, &mut black, &mut spectator] {
+            match receiver.receive() {
+                Message::GameOver { result } => {
+                    assert_eq!(result.winner, Some(PieceColor::Light));
+                    assert_eq!(result.reason, "illegal move");
+                }
+                other => panic!("expected the result, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn matching_only_takes_up_seeks_with_the_same_time_control() {
+        let address = start_server();
+        let mut untimed = TestClient::connect(&address);
+        untimed.send(Message::Seek { time_control: None });
+        assert!(matches!(untimed.receive(), Message::Seeking { .. }));
+
+        let blitz = TimeControl {
+            minutes: 5,
+            increment_seconds: 0,
+        };
+        let joining = address.clone();
+        let player = thread::spawn(move || {
+            Connection::join_server(&joining, LobbyRequest::Any(Some(blitz))).unwrap()
+        });
+
+        // The untimed seek stays open next to a new one for 5+0
+        let mut taker = TestClient::connect(&address);
+        let seek = loop {
+            match net::read_message(&mut taker.reader).unwrap() {
+                Message::Lobby { seeks, .. } if seeks.len() == 2 => {
+                    assert_eq!(seeks[0].time_control, None);
+                    assert_eq!(seeks[1].time_control, Some(blitz));
+                    break seeks[1].id;
+                }
+                Message::Lobby { .. } => {}
+                other => panic!("expected the lobby, got {:?}", other),
+            }
+        };
+        taker.send(Message::AcceptSeek { id: seek });
+        taker.game_started();
+        let (_, agreement) = player.join().unwrap();
+        assert_eq!(agreement.time_control, Some(blitz));
+    }
+
+    #[test]
+    fn rejects_other_protocol_versions() {
+        let address = start_server();
+        let mut client = TestClient::connect(&address);
+        client.send(Message::Seek { time_control: None });
+        // The greeting above was accepted, a second client with an old version is not
+        assert!(matches!(client.receive(), Message::Seeking { .. }));
+        let stream = TcpStream::connect(&address).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut stream = stream;
+        net::write_message(&mut stream, &Message::Hello { version: 1 }).unwrap();
+        assert!(matches!(
+            net::read_message(&mut reader),
+            Ok(Message::Reject { .. })
+        ));
+    }
+}
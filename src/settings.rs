@@ -0,0 +1,313 @@
+use std::path::PathBuf;
+
+use bevy::{
+    app::AppExit,
+    prelude::*,
+    window::{WindowCloseRequested, WindowMode},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    animation::{CaptureAnimation, CaptureEffect, MoveAnimation},
+    clock::TimeControl,
+    theme::Themes,
+};
+
+pub struct SettingsPlugin;
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Settings>()
+            .init_resource::<SettingsOverrides>()
+            .init_resource::<SavedSettings>()
+            .add_system(apply_settings)
+            .add_system_to_stage(CoreStage::Last, save_settings);
+    }
+}
+
+/// Everything the player can configure, stored in `settings.ron` in the user config directory
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub display: DisplaySettings,
+    pub camera: CameraSettings,
+    pub input: InputSettings,
+    pub theme: String,
+    pub piece_set: String,
+    pub animation: AnimationSettings,
+    pub sound: SoundSettings,
+    pub time_control: TimeControlSettings,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplaySettings {
+    pub width: f32,
+    pub height: f32,
+    pub fullscreen: bool,
+    pub vsync: bool,
+    pub msaa_samples: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraSettings {
+    pub position: [f32; 3],
+    pub look_at: [f32; 3],
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputSettings {
+    /// Show the picking debug cursor where the mouse ray hits the board
+    pub debug_cursor: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnimationSettings {
+    /// Multiplier for how fast pieces move, 2.0 makes every animation take half as long
+    pub speed: f32,
+    pub capture_effect: CaptureEffect,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoundSettings {
+    pub enabled: bool,
+}
+
+/// Time control new games start with, `None` minutes means untimed
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeControlSettings {
+    pub minutes: Option<u32>,
+    pub increment_seconds: u32,
+}
+
+impl TimeControlSettings {
+    pub fn get(&self) -> Option<TimeControl> {
+        self.minutes.map(|minutes| TimeControl {
+            minutes,
+            increment_seconds: self.increment_seconds,
+        })
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            display: Default::default(),
+            camera: Default::default(),
+            input: Default::default(),
+            theme: "Classic wood".to_string(),
+            piece_set: crate::piece_set::DEFAULT_PIECE_SET.to_string(),
+            animation: Default::default(),
+            sound: Default::default(),
+            time_control: Default::default(),
+        }
+    }
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            width: 1600.,
+            height: 1600.,
+            fullscreen: false,
+            vsync: true,
+            msaa_samples: 4,
+        }
+    }
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            position: [-7.0, 20.0, 4.0],
+            look_at: [3.7, 0.0, 4.0],
+        }
+    }
+}
+
+impl Default for InputSettings {
+    fn default() -> Self {
+        Self { debug_cursor: true }
+    }
+}
+
+impl Default for AnimationSettings {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            capture_effect: CaptureEffect::Topple,
+        }
+    }
+}
+
+impl Default for SoundSettings {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+impl Settings {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("bevy-chess-3d").join("settings.ron"))
+    }
+
+    /// Load the settings file, falling back to the defaults if there is none yet
+    pub fn load() -> Self {
+        let path = if let Some(path) = Self::path() {
+            path
+        } else {
+            return Self::default();
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(ron) => ron::from_str(&ron).unwrap_or_else(|err| {
+                warn!("Invalid settings file {}: {}", path.display(), err);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::path().ok_or("no config directory")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+        }
+        let ron =
+            ron::ser::to_string_pretty(self, Default::default()).map_err(|err| err.to_string())?;
+        std::fs::write(&path, ron).map_err(|err| err.to_string())
+    }
+
+    pub fn window_descriptor(&self) -> WindowDescriptor {
+        WindowDescriptor {
+            title: "Chess!".to_string(),
+            width: self.display.width,
+            height: self.display.height,
+            vsync: self.display.vsync,
+            mode: self.window_mode(),
+            ..Default::default()
+        }
+    }
+
+    fn window_mode(&self) -> WindowMode {
+        if self.display.fullscreen {
+            WindowMode::BorderlessFullscreen
+        } else {
+            WindowMode::Windowed
+        }
+    }
+
+    pub fn camera_transform(&self) -> Transform {
+        Transform::from_translation(self.camera.position.into())
+            .looking_at(self.camera.look_at.into(), Vec3::Y)
+    }
+}
+
+/// Push changed settings to the resources and the window they configure
+fn apply_settings(
+    settings: Res<Settings>,
+    mut themes: ResMut<Themes>,
+    mut move_animation: ResMut<MoveAnimation>,
+    mut capture_animation: ResMut<CaptureAnimation>,
+    mut msaa: ResMut<Msaa>,
+    mut windows: ResMut<Windows>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    if themes.active().name != settings.theme && !themes.select(&settings.theme) {
+        warn!("Unknown theme {}", settings.theme);
+    }
+
+    let speed = settings.animation.speed.max(0.1);
+    let default_move = MoveAnimation::default();
+    move_animation.duration = default_move.duration / speed;
+    let default_capture = CaptureAnimation::default();
+    capture_animation.duration = default_capture.duration / speed;
+    capture_animation.effect = settings.animation.capture_effect;
+
+    if msaa.samples != settings.display.msaa_samples {
+        msaa.samples = settings.display.msaa_samples;
+    }
+
+    if let Some(window) = windows.get_primary_mut() {
+        let mode = settings.window_mode();
+        if window.mode() != mode {
+            window.set_mode(mode);
+        }
+        if window.vsync() != settings.display.vsync {
+            window.set_vsync(settings.display.vsync);
+        }
+    }
+}
+
+/// Settings given on the command line, they are used for this run but never written to the file
+#[derive(Clone, Default, Debug)]
+pub struct SettingsOverrides {
+    pub theme: Option<String>,
+    pub fullscreen: Option<bool>,
+}
+
+impl SettingsOverrides {
+    pub fn apply(&self, settings: &mut Settings) {
+        if let Some(theme) = &self.theme {
+            settings.theme = theme.clone();
+        }
+        if let Some(fullscreen) = self.fullscreen {
+            settings.display.fullscreen = fullscreen;
+        }
+    }
+
+    /// `settings` as they go into the file: values that still are the overrides are put back to
+    /// the ones in `saved`, values changed in game are kept
+    fn restore(&self, settings: &Settings, saved: &Settings) -> Settings {
+        let mut restored = settings.clone();
+        if self.theme.as_ref() == Some(&settings.theme) {
+            restored.theme = saved.theme.clone();
+        }
+        if self.fullscreen == Some(settings.display.fullscreen) {
+            restored.display.fullscreen = saved.display.fullscreen;
+        }
+        restored
+    }
+}
+
+/// The settings as they are in the file, without the overrides
+#[derive(Default)]
+pub struct SavedSettings(pub Settings);
+
+/// Seconds the settings have to stay unchanged before they are written, so clicking through the
+/// menu writes the file once
+const SAVE_DELAY: f64 = 1.0;
+
+/// Write the settings back to disk once they stop changing in game or the window is closed,
+/// without the overrides
+fn save_settings(
+    settings: Res<Settings>,
+    overrides: Res<SettingsOverrides>,
+    mut saved: ResMut<SavedSettings>,
+    time: Res<Time>,
+    mut close_requests: EventReader<WindowCloseRequested>,
+    mut exits: EventReader<AppExit>,
+    mut changed_at: Local<Option<f64>>,
+) {
+    let now = time.seconds_since_startup();
+    if settings.is_changed() && !settings.is_added() {
+        *changed_at = Some(now);
+    }
+    let exiting = close_requests.iter().count() > 0 || exits.iter().count() > 0;
+    match *changed_at {
+        Some(changed) if exiting || now - changed >= SAVE_DELAY => {}
+        _ => return,
+    }
+    *changed_at = None;
+    let settings = overrides.restore(&settings, &saved.0);
+    match settings.save() {
+        Ok(()) => saved.0 = settings,
+        Err(err) => warn!("Could not save settings: {}", err),
+    }
+}
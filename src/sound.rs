@@ -0,0 +1,38 @@
+use bevy::{asset::LoadState, prelude::*};
+
+use crate::{game::PlayerTurn, settings::Settings};
+
+pub struct SoundPlugin;
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MoveSound>().add_system(play_move_sound);
+    }
+}
+
+/// The clip played after a move, the game stays silent when the file isn't there
+struct MoveSound(Handle<AudioSource>);
+
+impl FromWorld for MoveSound {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+        Self(asset_server.load("sounds/move.ogg"))
+    }
+}
+
+/// Play a sound after every move, unless sound is turned off in the settings
+fn play_move_sound(
+    settings: Res<Settings>,
+    turn: Res<PlayerTurn>,
+    sound: Res<MoveSound>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+) {
+    if !turn.is_changed() || turn.is_added() || !settings.sound.enabled {
+        return;
+    }
+    // The asset server already logged why the file could not be loaded
+    if asset_server.get_load_state(&sound.0) == LoadState::Failed {
+        return;
+    }
+    audio.play(sound.0.clone());
+}
@@ -0,0 +1,150 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{assets::load_ron, board::SquareMaterials, pieces::PieceMaterials, settings::Settings};
+
+const BUILT_IN_THEMES: &str = include_str!("../assets/themes.ron");
+
+pub struct ThemePlugin;
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Themes>().add_system(apply_theme);
+    }
+}
+
+#[derive(Clone, Copy, Deserialize)]
+pub struct Rgb(pub f32, pub f32, pub f32);
+
+impl From<Rgb> for Color {
+    fn from(Rgb(red, green, blue): Rgb) -> Self {
+        Color::rgb(red, green, blue)
+    }
+}
+
+/// Look of the pieces of one color
+#[derive(Clone, Deserialize)]
+pub struct PieceStyle {
+    pub color: Rgb,
+    pub metallic: f32,
+    pub roughness: f32,
+}
+
+impl From<&PieceStyle> for StandardMaterial {
+    fn from(style: &PieceStyle) -> Self {
+        StandardMaterial {
+            base_color: style.color.into(),
+            metallic: style.metallic,
+            perceptual_roughness: style.roughness,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct Theme {
+    pub name: String,
+    pub light_square: Rgb,
+    pub dark_square: Rgb,
+    pub highlight: Rgb,
+    pub selected: Rgb,
+    pub cursor: Rgb,
+    pub light_piece: PieceStyle,
+    pub dark_piece: PieceStyle,
+    /// The side armies of four-player chess
+    #[serde(default = "default_blue_piece")]
+    pub blue_piece: PieceStyle,
+    #[serde(default = "default_green_piece")]
+    pub green_piece: PieceStyle,
+    /// Pieces of a player who is out of a four-player game
+    #[serde(default = "default_out_piece")]
+    pub out_piece: PieceStyle,
+}
+
+fn default_blue_piece() -> PieceStyle {
+    PieceStyle {
+        color: Rgb(0.20, 0.35, 0.80),
+        metallic: 0.0,
+        roughness: 0.5,
+    }
+}
+
+fn default_green_piece() -> PieceStyle {
+    PieceStyle {
+        color: Rgb(0.20, 0.60, 0.30),
+        metallic: 0.0,
+        roughness: 0.5,
+    }
+}
+
+fn default_out_piece() -> PieceStyle {
+    PieceStyle {
+        color: Rgb(0.45, 0.45, 0.45),
+        metallic: 0.0,
+        roughness: 0.9,
+    }
+}
+
+/// All known themes, loaded from `assets/themes.ron`
+pub struct Themes {
+    pub themes: Vec<Theme>,
+    active: usize,
+}
+
+impl Themes {
+    pub fn active(&self) -> &Theme {
+        &self.themes[self.active]
+    }
+
+    pub fn active_index(&self) -> usize {
+        self.active
+    }
+
+    /// Switch to the theme called `name`, returns false if there is none
+    pub fn select(&mut self, name: &str) -> bool {
+        match self
+            .themes
+            .iter()
+            .position(|theme| theme.name.eq_ignore_ascii_case(name))
+        {
+            Some(index) => {
+                self.active = index;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Themes {
+    pub fn load() -> Self {
+        let themes = load_ron::<Vec<Theme>>("themes.ron")
+            .filter(|themes| !themes.is_empty())
+            .unwrap_or_else(|| ron::from_str(BUILT_IN_THEMES).expect("built-in themes are valid"));
+        Self { themes, active: 0 }
+    }
+}
+
+impl FromWorld for Themes {
+    fn from_world(world: &mut World) -> Self {
+        let mut themes = Themes::load();
+        if let Some(settings) = world.get_resource::<Settings>() {
+            themes.select(&settings.theme);
+        }
+        themes
+    }
+}
+
+/// Update the square and piece materials in place whenever the theme changes
+fn apply_theme(
+    themes: Res<Themes>,
+    square_materials: Res<SquareMaterials>,
+    piece_materials: Res<PieceMaterials>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if !themes.is_changed() {
+        return;
+    }
+    let theme = themes.active();
+    square_materials.apply_theme(theme, &mut materials);
+    piece_materials.apply_theme(theme, &mut materials);
+}
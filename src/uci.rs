@@ -0,0 +1,174 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+use crate::{
+    notation::{parse_uci, uci},
+    rules::{Move, Position},
+    variants::{Standard, Variant},
+};
+
+/// How long an engine may think about a move
+#[derive(Clone, Copy, Debug)]
+pub enum SearchLimit {
+    MoveTime {
+        milliseconds: u32,
+    },
+    /// Remaining time and increment for both sides, in milliseconds
+    Clock {
+        white: u32,
+        black: u32,
+        white_increment: u32,
+        black_increment: u32,
+    },
+}
+
+/// An external engine speaking the Universal Chess Interface over stdin and stdout
+pub struct UciEngine {
+    name: String,
+    process: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    /// Whether the engine was told to play Chess960
+    chess960: bool,
+    /// The `UCI_Variant` the engine was told to play
+    variant: &'static str,
+}
+
+impl UciEngine {
+    /// Start the engine and wait until it is ready
+    pub fn start(path: &Path) -> Result<Self, String> {
+        let mut process = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|err| format!("could not start engine {}: {}", path.display(), err))?;
+        let stdin = process.stdin.take().unwrap();
+        let stdout = BufReader::new(process.stdout.take().unwrap());
+        let mut engine = Self {
+            name: path.display().to_string(),
+            process,
+            stdin,
+            stdout,
+            chess960: false,
+            variant: Standard.uci_name(),
+        };
+
+        engine.send("uci")?;
+        let mut name = None;
+        engine.wait_for(|line| {
+            if let Some(id) = line.strip_prefix("id name ") {
+                name = Some(id.to_string());
+            }
+            (line == "uciok").then(|| ())
+        })?;
+        if let Some(name) = name {
+            engine.name = name;
+        }
+        engine.send("ucinewgame")?;
+        engine.sync()?;
+        Ok(engine)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Ask for the best move after `moves` were played from `start`
+    pub fn best_move(
+        &mut self,
+        start: &Position,
+        moves: &[Move],
+        limit: SearchLimit,
+    ) -> Result<Move, String> {
+        if start.chess960 != self.chess960 {
+            self.send(&format!(
+                "setoption name UCI_Chess960 value {}",
+                start.chess960
+            ))?;
+            self.chess960 = start.chess960;
+        }
+        if start.variant.uci_name() != self.variant {
+            self.send(&format!(
+                "setoption name UCI_Variant value {}",
+                start.variant.uci_name()
+            ))?;
+            self.variant = start.variant.uci_name();
+        }
+        // Shredder-FEN names the rooks that can castle without any guessing
+        let fen = if start.chess960 {
+            start.to_shredder_fen()
+        } else {
+            start.to_fen()
+        };
+        let mut command = format!("position fen {}", fen);
+        let mut position = start.clone();
+        if !moves.is_empty() {
+            command.push_str(" moves");
+            for mv in moves {
+                command.push(' ');
+                command.push_str(&uci(&position, mv));
+                position.make_move(mv);
+            }
+        }
+        self.send(&command)?;
+        self.sync()?;
+
+        self.send(&match limit {
+            SearchLimit::MoveTime { milliseconds } => format!("go movetime {}", milliseconds),
+            SearchLimit::Clock {
+                white,
+                black,
+                white_increment,
+                black_increment,
+            } => format!(
+                "go wtime {} btime {} winc {} binc {}",
+                white, black, white_increment, black_increment
+            ),
+        })?;
+        let best = self.wait_for(|line| {
+            line.strip_prefix("bestmove ")
+                .map(|rest| rest.split_whitespace().next().unwrap_or("").to_string())
+        })?;
+        parse_uci(&position, &best).map_err(|err| format!("{} played {}", self.name, err))
+    }
+
+    /// Wait until the engine has processed everything sent so far
+    fn sync(&mut self) -> Result<(), String> {
+        self.send("isready")?;
+        self.wait_for(|line| (line == "readyok").then(|| ()))
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), String> {
+        writeln!(self.stdin, "{}", command)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|err| format!("could not talk to {}: {}", self.name, err))
+    }
+
+    /// Read lines until `parse` accepts one
+    fn wait_for<T>(&mut self, mut parse: impl FnMut(&str) -> Option<T>) -> Result<T, String> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = self
+                .stdout
+                .read_line(&mut line)
+                .map_err(|err| format!("could not read from {}: {}", self.name, err))?;
+            if read == 0 {
+                return Err(format!("{} quit", self.name));
+            }
+            if let Some(value) = parse(line.trim()) {
+                return Ok(value);
+            }
+        }
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let _ = self.process.wait();
+    }
+}
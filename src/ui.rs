@@ -1,9 +1,22 @@
 use bevy::prelude::*;
 
+use crate::{
+    clock::{format_time, Clock},
+    game::{Game, PlayerTurn},
+};
+
 // Component to mark the Text entity
 #[derive(Component)]
 struct NextMoveText;
 
+// Component to mark the text showing the time left for both players
+#[derive(Component)]
+struct ClockText;
+
+// Component to mark the text showing the state of the variant, like the checks given
+#[derive(Component)]
+struct VariantText;
+
 /// Initialize UiCamera and text
 fn init_next_move_text(mut commands: Commands, asset_server: ResMut<AssetServer>) {
     let font = asset_server.load("fonts/FiraSans-Bold.ttf");
@@ -18,6 +31,7 @@ fn init_next_move_text(mut commands: Commands, asset_server: ResMut<AssetServer>
                     top: Val::Px(10.0),
                     ..Default::default()
                 },
+                flex_direction: FlexDirection::ColumnReverse,
                 ..Default::default()
             },
             color: Color::NONE.into(),
@@ -28,7 +42,7 @@ fn init_next_move_text(mut commands: Commands, asset_server: ResMut<AssetServer>
                 text: Text::with_section(
                     "Next move: White",
                     TextStyle {
-                        font,
+                        font: font.clone(),
                         font_size: 40.0,
                         color: Color::rgb(0.8, 0.8, 0.8),
                     },
@@ -39,30 +53,97 @@ fn init_next_move_text(mut commands: Commands, asset_server: ResMut<AssetServer>
                 ),
                 ..Default::default()
             }).insert(NextMoveText);
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 32.0,
+                            color: Color::rgb(0.8, 0.8, 0.8),
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(ClockText);
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font,
+                            font_size: 32.0,
+                            color: Color::rgb(0.8, 0.8, 0.8),
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(VariantText);
         });
 }
 
-/// Update text with the correct turn
+/// Update text with the correct turn, or how the game ended
 fn next_move_text_update(
-    turn: Res<crate::board::PlayerTurn>,
+    turn: Res<PlayerTurn>,
+    game: Res<Game>,
     mut query: Query<&mut Text, With<NextMoveText>>,
 ) {
-    if !turn.is_changed() {
+    if !turn.is_changed() && !game.is_changed() {
         return;
     }
 
     for mut text in query.iter_mut() {
         for section in text.sections.iter_mut() {
-            println!("  found section");
-            section.value = format!("Next move: {}", turn.0);
+            section.value = match &game.result {
+                Some(result) => result.to_string(),
+                None => format!("Next move: {}", turn.0),
+            };
         }
     }
 }
 
+fn clock_text_update(
+    clock: Res<Clock>,
+    game: Res<Game>,
+    mut query: Query<&mut Text, With<ClockText>>,
+) {
+    let times: Option<Vec<String>> = game
+        .position
+        .variant
+        .colors()
+        .iter()
+        .map(|&color| Some(format!("{} {}", color, format_time(clock.remaining(color)?))))
+        .collect();
+    let times = match times {
+        Some(times) => times,
+        None => return,
+    };
+    for mut text in query.iter_mut() {
+        text.sections[0].value = times.join("  ");
+    }
+}
+
+fn variant_text_update(game: Res<Game>, mut query: Query<&mut Text, With<VariantText>>) {
+    if !game.is_changed() {
+        return;
+    }
+    for mut text in query.iter_mut() {
+        text.sections[0].value = game
+            .position
+            .variant
+            .status(&game.position)
+            .unwrap_or_default();
+    }
+}
+
 pub struct UiPlugin;
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
         app.add_startup_system(init_next_move_text)
-            .add_system(next_move_text_update);
+            .add_system(next_move_text_update)
+            .add_system(clock_text_update)
+            .add_system(variant_text_update);
     }
 }
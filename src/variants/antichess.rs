@@ -0,0 +1,138 @@
+use crate::{
+    ai::piece_value,
+    pieces::{PieceColor, PieceType},
+    rules::{GameResult, Move, Position},
+};
+
+use super::Variant;
+
+/// Losing chess: captures are forced, the king is a piece like any other and the side that loses
+/// all its pieces or can't move wins
+#[derive(Debug)]
+pub struct Antichess;
+
+fn is_capture(position: &Position, mv: &Move) -> bool {
+    let pawn_moves_sideways = position
+        .piece_at(mv.from)
+        .map_or(false, |piece| piece.piece_type == PieceType::Pawn)
+        && mv.from.1 != mv.to.1;
+    position.piece_at(mv.to).is_some() || pawn_moves_sideways
+}
+
+impl Variant for Antichess {
+    fn name(&self) -> &'static str {
+        "Antichess"
+    }
+
+    fn uci_name(&self) -> &'static str {
+        "antichess"
+    }
+
+    fn starting_fen(&self) -> &'static str {
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1"
+    }
+
+    fn has_royal_king(&self, _color: PieceColor) -> bool {
+        false
+    }
+
+    fn is_in_check(&self, _position: &Position, _color: PieceColor) -> bool {
+        false
+    }
+
+    /// Pawns may also promote to a king
+    fn add_moves(&self, _position: &Position, moves: &mut Vec<Move>) {
+        let to_king: Vec<Move> = moves
+            .iter()
+            .filter(|mv| mv.promotion == Some(PieceType::Queen))
+            .map(|mv| Move {
+                promotion: Some(PieceType::King),
+                ..*mv
+            })
+            .collect();
+        moves.extend(to_king);
+    }
+
+    /// There is no castling, and when a capture is possible one has to be made
+    fn legal_moves(&self, position: &Position, moves: Vec<Move>) -> Vec<Move> {
+        let moves: Vec<Move> = moves
+            .into_iter()
+            .filter(|mv| position.castling_side(mv).is_none())
+            .collect();
+        if moves.iter().any(|mv| is_capture(position, mv)) {
+            moves
+                .into_iter()
+                .filter(|mv| is_capture(position, mv))
+                .collect()
+        } else {
+            moves
+        }
+    }
+
+    fn outcome(&self, position: &Position, legal_moves: &[Move]) -> Option<GameResult> {
+        let has_pieces = position
+            .pieces
+            .iter()
+            .any(|piece| piece.color == position.turn);
+        if !has_pieces {
+            return Some(GameResult::win(position.turn, "losing all pieces"));
+        }
+        if legal_moves.is_empty() {
+            return Some(GameResult::win(position.turn, "being stalemated"));
+        }
+        if position.halfmove_clock >= 100 {
+            return Some(GameResult::draw("the fifty-move rule"));
+        }
+        None
+    }
+
+    /// Material counts against the side that has it, the usual evaluation is turned around
+    fn evaluate(&self, position: &Position) -> i32 {
+        let material: i32 = position
+            .pieces
+            .iter()
+            .map(|piece| {
+                let value = piece_value(piece.piece_type);
+                if piece.color == position.turn {
+                    value
+                } else {
+                    -value
+                }
+            })
+            .sum();
+        -2 * material
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{notation::parse_uci, variants::after_moves};
+
+    #[test]
+    fn captures_are_forced() {
+        assert_eq!(Position::starting(&Antichess).perft(3), 8067);
+        let start = Antichess.starting_fen();
+        let position = after_moves(&Antichess, start, &["e2e3", "b7b5"]);
+        let moves: Vec<Move> = position.legal_moves();
+        assert_eq!(moves, [parse_uci(&position, "f1b5").unwrap()]);
+    }
+
+    #[test]
+    fn losing_all_pieces_wins() {
+        let position = after_moves(&Antichess, "8/8/8/8/8/8/1p6/R7 b - - 0 1", &["b2a1q"]);
+        assert_eq!(
+            position.outcome(),
+            Some(GameResult::win(PieceColor::Light, "losing all pieces"))
+        );
+    }
+
+    #[test]
+    fn kings_are_not_royal() {
+        // Kings may be left in check and pawns may promote to one
+        let position =
+            Position::from_variant_fen(&Antichess, "8/P7/8/8/8/8/8/r6K w - - 0 1").unwrap();
+        assert!(parse_uci(&position, "a7a8k").is_ok());
+        assert!(parse_uci(&position, "h1h2").is_ok());
+    }
+}
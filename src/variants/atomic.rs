@@ -0,0 +1,143 @@
+use crate::{
+    pieces::{Piece, PieceColor, PieceType},
+    rules::{GameResult, Move, MoveEffects, Position},
+};
+
+use super::{mate_or_draw, Variant};
+
+/// Every capture explodes, taking the capturing piece and all pieces but pawns next to the
+/// capture square with it. Blowing up the opponent's king wins.
+#[derive(Debug)]
+pub struct Atomic;
+
+fn kings_touch(position: &Position) -> bool {
+    match (
+        position.king(PieceColor::Light),
+        position.king(PieceColor::Dark),
+    ) {
+        (Some(white), Some(black)) => {
+            white.x.abs_diff(black.x) <= 1 && white.y.abs_diff(black.y) <= 1
+        }
+        _ => false,
+    }
+}
+
+impl Variant for Atomic {
+    fn name(&self) -> &'static str {
+        "Atomic"
+    }
+
+    fn uci_name(&self) -> &'static str {
+        "atomic"
+    }
+
+    /// Taking a king next to your own would blow both up, so touching kings are never in check
+    fn is_in_check(&self, position: &Position, color: PieceColor) -> bool {
+        !kings_touch(position) && position.is_king_attacked(color)
+    }
+
+    fn legal_moves(&self, position: &Position, moves: Vec<Move>) -> Vec<Move> {
+        let color = position.turn;
+        moves
+            .into_iter()
+            .filter(|mv| {
+                // Kings can't capture, they would blow themselves up
+                let piece = position.piece_at(mv.from);
+                let target = position.piece_at(mv.to);
+                if piece.map(|piece| piece.piece_type) == Some(PieceType::King)
+                    && target.map_or(false, |target| target.color != color)
+                {
+                    return false;
+                }
+                let mut after = position.clone();
+                after.make_move(mv);
+                if after.king(color).is_none() {
+                    return false;
+                }
+                after.king(color.opponent()).is_none() || !after.is_in_check(color)
+            })
+            .collect()
+    }
+
+    fn after_move(&self, position: &mut Position, mv: &Move, effects: &mut MoveEffects) {
+        if effects.captured.is_none() {
+            return;
+        }
+        let center @ (x, y, _) = mv.to;
+        let blown_up = |piece: &Piece| {
+            piece.square() == center
+                || piece.x.abs_diff(x) <= 1
+                    && piece.y.abs_diff(y) <= 1
+                    && piece.piece_type != PieceType::Pawn
+        };
+        let (exploded, left): (Vec<_>, Vec<_>) =
+            position.pieces.drain(..).partition(blown_up);
+        position.pieces = left;
+        let board = position.variant.board();
+        for piece in &exploded {
+            if piece.piece_type == PieceType::Rook && piece.x == board.back_rank(piece.color) {
+                position.castling.remove_rook(piece.color, piece.y);
+            }
+        }
+        effects.explosion = Some(center);
+        effects.exploded = exploded
+            .into_iter()
+            .filter(|piece| piece.square() != center)
+            .collect();
+    }
+
+    fn outcome(&self, position: &Position, legal_moves: &[Move]) -> Option<GameResult> {
+        for color in [PieceColor::Light, PieceColor::Dark] {
+            if position.king(color).is_none() {
+                return Some(GameResult::win(color.opponent(), "explosion"));
+            }
+        }
+        mate_or_draw(position, legal_moves)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{notation::parse_uci, variants::after_moves};
+
+    fn legal(fen: &str, uci: &str) -> bool {
+        let position = Position::from_variant_fen(&Atomic, fen).unwrap();
+        parse_uci(&position, uci).is_ok()
+    }
+
+    #[test]
+    fn a_capture_blows_up_the_pieces_around_it_but_pawns() {
+        let mut position =
+            Position::from_variant_fen(&Atomic, "4k3/8/2p5/3nb3/8/8/8/3QK3 w - - 0 1").unwrap();
+        let effects = position.make_move(&Move::new((0, 3, 0), (4, 3, 0)));
+        assert_eq!(effects.explosion, Some((4, 3, 0)));
+        let exploded: Vec<PieceType> = effects
+            .exploded
+            .iter()
+            .map(|piece| piece.piece_type)
+            .collect();
+        assert_eq!(exploded, [PieceType::Bishop]);
+        // The knight, the queen that took it and the bishop are gone, the pawn is left
+        assert_eq!(position.to_fen(), "4k3/8/2p5/8/8/8/8/4K3 b - - 0 1");
+    }
+
+    #[test]
+    fn kings_can_not_capture_or_blow_up_their_own_king() {
+        // The pawn gives check, the king may not take it
+        assert!(!legal("4k3/8/8/8/8/8/3p4/4K3 w - - 0 1", "e1d2"));
+        assert!(legal("4k3/8/8/8/8/8/3p4/4K3 w - - 0 1", "e1e2"));
+        // Taking the knight would blow up the king next to it
+        assert!(!legal("4k3/8/8/8/8/8/3n4/3RK3 w - - 0 1", "d1d2"));
+    }
+
+    #[test]
+    fn blowing_up_the_king_wins() {
+        let position = after_moves(&Atomic, "3k4/3p4/8/8/8/8/8/3QK3 w - - 0 1", &["d1d7"]);
+        assert!(position.king(PieceColor::Dark).is_none());
+        assert_eq!(
+            position.outcome(),
+            Some(GameResult::win(PieceColor::Light, "explosion"))
+        );
+    }
+}
@@ -0,0 +1,58 @@
+use crate::{pieces::PieceType, rules::BoardShape};
+
+use super::Variant;
+
+/// Chess on a 10×8 board with an archbishop and a chancellor added to each side
+#[derive(Debug)]
+pub struct Capablanca;
+
+const PROMOTIONS: [PieceType; 6] = [
+    PieceType::Queen,
+    PieceType::Chancellor,
+    PieceType::Archbishop,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+];
+
+impl Variant for Capablanca {
+    fn name(&self) -> &'static str {
+        "Capablanca"
+    }
+
+    fn uci_name(&self) -> &'static str {
+        "capablanca"
+    }
+
+    fn starting_fen(&self) -> &'static str {
+        "rnabqkbcnr/pppppppppp/10/10/10/10/PPPPPPPPPP/RNABQKBCNR w KQkq - 0 1"
+    }
+
+    fn board(&self) -> BoardShape {
+        BoardShape {
+            files: 10,
+            ranks: 8,
+            levels: 1,
+            corners: 0,
+            hexagonal: false,
+        }
+    }
+
+    fn promotions(&self) -> &'static [PieceType] {
+        &PROMOTIONS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Position;
+
+    #[test]
+    fn moves_from_the_start() {
+        let start = Position::starting(&Capablanca);
+        assert_eq!(start.perft(1), 28);
+        assert_eq!(start.perft(2), 784);
+        assert_eq!(start.perft(3), 25228);
+    }
+}
@@ -0,0 +1,246 @@
+use crate::{
+    ai::piece_value,
+    fen::piece_letter,
+    pieces::{PieceColor, PieceType},
+    rules::{GameResult, Move, MoveEffects, Position},
+};
+
+use super::{mate_or_draw, Variant, VariantState};
+
+/// Captured pieces change sides and join the capturer's reserve, from where they can be dropped
+/// onto any empty square instead of making a move
+#[derive(Debug)]
+pub struct Crazyhouse;
+
+/// Order pieces are listed in, in the FEN holdings and beside the board
+pub const RESERVE_ORDER: [PieceType; 5] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::Pawn,
+];
+
+impl Variant for Crazyhouse {
+    fn name(&self) -> &'static str {
+        "Crazyhouse"
+    }
+
+    fn uci_name(&self) -> &'static str {
+        "crazyhouse"
+    }
+
+    fn starting_fen(&self) -> &'static str {
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[] w KQkq - 0 1"
+    }
+
+    /// The reserves are written after the placement as `[Qn]` or as a ninth rank, promoted
+    /// pieces are followed by `~` because they go back to the reserve as pawns
+    fn read_fen(&self, fen: &str, state: &mut VariantState) -> Result<String, String> {
+        let (placement, rest) = fen.split_once(' ').unwrap_or((fen, ""));
+        let (placement, holdings) = if let Some((placement, holdings)) = placement.split_once('[') {
+            let holdings = holdings
+                .strip_suffix(']')
+                .ok_or_else(|| format!("unclosed reserve '[{}'", holdings))?;
+            (placement, holdings)
+        } else if placement.matches('/').count() == 8 {
+            placement.rsplit_once('/').unwrap()
+        } else {
+            (placement, "")
+        };
+
+        for c in holdings.chars().filter(|&c| c != '-') {
+            let piece_type = PieceType::from_letter(c)
+                .filter(|&piece_type| piece_type != PieceType::King)
+                .ok_or_else(|| format!("invalid reserve piece '{}'", c))?;
+            let color = if c.is_ascii_uppercase() {
+                PieceColor::Light
+            } else {
+                PieceColor::Dark
+            };
+            state.reserves[color.index()].push(piece_type);
+        }
+
+        for (index, rank) in placement.split('/').enumerate() {
+            let x = 7u8.saturating_sub(index as u8);
+            let mut y: u8 = 0;
+            for c in rank.chars() {
+                match c {
+                    '~' => state.promoted.push((x, y.wrapping_sub(1), 0)),
+                    _ => match c.to_digit(10) {
+                        Some(empty) => y = y.saturating_add(empty as u8),
+                        None => y += 1,
+                    },
+                }
+            }
+        }
+
+        Ok(format!("{} {}", placement.replace('~', ""), rest))
+    }
+
+    fn write_fen(&self, position: &Position, fen: &mut String) {
+        let (placement, rest) = fen.split_once(' ').unwrap_or((fen, ""));
+        let mut marked = String::new();
+        let (mut x, mut y) = (7u8, 0u8);
+        for c in placement.chars() {
+            marked.push(c);
+            match c {
+                '/' => {
+                    x = x.saturating_sub(1);
+                    y = 0;
+                }
+                _ => match c.to_digit(10) {
+                    Some(empty) => y += empty as u8,
+                    None => {
+                        if position.state.promoted.contains(&(x, y, 0)) {
+                            marked.push('~');
+                        }
+                        y += 1;
+                    }
+                },
+            }
+        }
+
+        marked.push('[');
+        for color in [PieceColor::Light, PieceColor::Dark] {
+            for (piece_type, count) in reserve_counts(position, color) {
+                let letter = piece_letter(piece_type, color);
+                marked.extend(std::iter::repeat(letter).take(count));
+            }
+        }
+        marked.push(']');
+        *fen = format!("{} {}", marked, rest);
+    }
+
+    fn has_reserves(&self) -> bool {
+        true
+    }
+
+    fn add_moves(&self, position: &Position, moves: &mut Vec<Move>) {
+        for (piece_type, _) in reserve_counts(position, position.turn) {
+            for x in 0..8 {
+                // Pawns can't be dropped on the first or last rank
+                if piece_type == PieceType::Pawn && (x == 0 || x == 7) {
+                    continue;
+                }
+                for y in 0..8 {
+                    if position.piece_at((x, y, 0)).is_none() {
+                        moves.push(Move::drop(piece_type, (x, y, 0)));
+                    }
+                }
+            }
+        }
+    }
+
+    fn after_move(&self, position: &mut Position, mv: &Move, effects: &mut MoveEffects) {
+        let mover = position.turn.opponent();
+        let state = &mut position.state;
+
+        if let Some(piece_type) = mv.drop {
+            let reserve = &mut state.reserves[mover.index()];
+            if let Some(index) = reserve.iter().position(|&held| held == piece_type) {
+                reserve.remove(index);
+            }
+            return;
+        }
+
+        if let Some(captured) = effects.captured {
+            let square = captured.square();
+            let piece_type = match state.promoted.iter().position(|&held| held == square) {
+                Some(index) => {
+                    state.promoted.remove(index);
+                    PieceType::Pawn
+                }
+                None => captured.piece_type,
+            };
+            state.reserves[mover.index()].push(piece_type);
+        }
+        for &(from, to) in &effects.moved {
+            if let Some(square) = state.promoted.iter_mut().find(|square| **square == from) {
+                *square = to;
+            }
+        }
+        if mv.promotion.is_some() {
+            state.promoted.push(mv.to);
+        }
+    }
+
+    /// Every piece stays in play, so there is no draw by insufficient material
+    fn outcome(&self, position: &Position, legal_moves: &[Move]) -> Option<GameResult> {
+        mate_or_draw(position, legal_moves)
+    }
+
+    fn evaluate(&self, position: &Position) -> i32 {
+        let held = |color: PieceColor| -> i32 {
+            position.state.reserves[color.index()]
+                .iter()
+                .map(|&piece_type| piece_value(piece_type))
+                .sum()
+        };
+        held(position.turn) - held(position.turn.opponent())
+    }
+}
+
+/// The pieces `color` holds in reserve and how many of each, in `RESERVE_ORDER`
+pub fn reserve_counts(position: &Position, color: PieceColor) -> Vec<(PieceType, usize)> {
+    let reserve = &position.state.reserves[color.index()];
+    RESERVE_ORDER
+        .iter()
+        .map(|&piece_type| {
+            let count = reserve.iter().filter(|&&held| held == piece_type).count();
+            (piece_type, count)
+        })
+        .filter(|&(_, count)| count > 0)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{notation::parse_uci, variants::after_moves};
+
+    #[test]
+    fn captured_pieces_can_be_dropped() {
+        let start = Crazyhouse.starting_fen();
+        let position = after_moves(&Crazyhouse, start, &["e2e4", "d7d5", "e4d5", "d8d5"]);
+        assert_eq!(
+            position.state.reserves,
+            [vec![PieceType::Pawn], vec![PieceType::Pawn]]
+        );
+        let dropped = after_moves(&Crazyhouse, &position.to_fen(), &["P@e6"]);
+        assert!(dropped.state.reserves[0].is_empty());
+        assert_eq!(
+            dropped.piece_at((5, 4, 0)).map(|piece| piece.piece_type),
+            Some(PieceType::Pawn)
+        );
+
+        // Pawns can't be dropped on the first or last rank, other pieces can
+        let fen = "4k3/8/8/8/8/8/8/4K3[PN] w - - 0 1";
+        let position = Position::from_variant_fen(&Crazyhouse, fen).unwrap();
+        assert!(parse_uci(&position, "P@a8").is_err());
+        assert!(parse_uci(&position, "N@a8").is_ok());
+    }
+
+    #[test]
+    fn promoted_pieces_go_back_to_the_reserve_as_pawns() {
+        let promoted = after_moves(&Crazyhouse, "4k3/P7/8/8/8/8/8/4K3[] w - - 0 1", &["a7a8q"]);
+        assert_eq!(promoted.to_fen(), "Q~3k3/8/8/8/8/8/8/4K3[] b - - 0 1");
+
+        let taken = after_moves(&Crazyhouse, "3Q~k3/8/8/8/8/8/8/4K3[] b - - 0 1", &["e8d8"]);
+        assert_eq!(taken.state.reserves[1], [PieceType::Pawn]);
+        assert!(taken.state.promoted.is_empty());
+    }
+
+    #[test]
+    fn holdings_in_the_fen_round_trip() {
+        let fen = "rnbqkb1r/pppppppp/8/8/8/8/PPPPPPPP/RNB~QKBNR[QNnp] w KQkq - 0 1";
+        let position = Position::from_variant_fen(&Crazyhouse, fen).unwrap();
+        assert_eq!(position.to_fen(), fen);
+        assert_eq!(position.state.promoted, [(0, 2, 0)]);
+
+        // The holdings may also be written as a ninth rank
+        let ninth = "rnbqkb1r/pppppppp/8/8/8/8/PPPPPPPP/RNB~QKBNR/nQpN w KQkq - 0 1";
+        let position = Position::from_variant_fen(&Crazyhouse, ninth).unwrap();
+        assert_eq!(position.to_fen(), fen);
+    }
+}
@@ -0,0 +1,544 @@
+use crate::{
+    ai::piece_value,
+    pieces::{PieceColor, PieceType},
+    rules::{BoardShape, GameResult, Move, MoveEffects, Position},
+};
+
+use super::{Variant, VariantState};
+
+/// Four armies on the 14×14 cross-shaped board, every player for themselves or two teams of
+/// the players sitting across from each other
+#[derive(Debug)]
+pub struct FourPlayer {
+    pub teams: bool,
+    /// Put players who can't move out after every move, turned off to look for legal moves
+    eliminates: bool,
+}
+
+impl FourPlayer {
+    pub const FREE_FOR_ALL: FourPlayer = FourPlayer {
+        teams: false,
+        eliminates: true,
+    };
+
+    pub const TEAMS: FourPlayer = FourPlayer {
+        teams: true,
+        eliminates: true,
+    };
+}
+
+static FREE_FOR_ALL_MOVES: FourPlayer = FourPlayer {
+    teams: false,
+    eliminates: false,
+};
+
+static TEAMS_MOVES: FourPlayer = FourPlayer {
+    teams: true,
+    eliminates: false,
+};
+
+const BOARD: BoardShape = BoardShape {
+    files: 14,
+    ranks: 14,
+    levels: 1,
+    corners: 3,
+    hexagonal: false,
+};
+
+/// Clockwise round the board, starting at the bottom
+const COLORS: [PieceColor; 4] = [
+    PieceColor::Light,
+    PieceColor::Blue,
+    PieceColor::Dark,
+    PieceColor::Green,
+];
+
+/// Points for checkmating a player or taking their king, and for being stalemated
+const KING_POINTS: u32 = 20;
+
+/// Rank pawns promote on, counted from their own side of the board
+const PROMOTION_RANK: u8 = 7;
+
+fn capture_points(piece_type: PieceType) -> u32 {
+    match piece_type {
+        PieceType::King => KING_POINTS,
+        PieceType::Queen => 9,
+        PieceType::Rook | PieceType::Bishop => 5,
+        PieceType::Knight => 3,
+        PieceType::Pawn => 1,
+        // Pieces of other variants that can only get here through a FEN
+        other => (piece_value(other) / 100) as u32,
+    }
+}
+
+/// Teammates sit across the board from each other
+fn partner(color: PieceColor) -> PieceColor {
+    color.opponent()
+}
+
+impl FourPlayer {
+    /// The same rules without putting anyone out after a move
+    fn movement(&self) -> &'static FourPlayer {
+        if self.teams {
+            &TEAMS_MOVES
+        } else {
+            &FREE_FOR_ALL_MOVES
+        }
+    }
+
+    fn is_enemy(&self, color: PieceColor, other: PieceColor) -> bool {
+        other != color && !(self.teams && other == partner(color))
+    }
+
+    /// Players that are still in, in the order they move
+    fn remaining(&self, position: &Position) -> Vec<PieceColor> {
+        COLORS
+            .into_iter()
+            .filter(|&color| !self.is_out(position, color))
+            .collect()
+    }
+
+    fn is_over(&self, position: &Position) -> bool {
+        if self.teams {
+            !position.state.out.is_empty()
+        } else {
+            self.remaining(position).len() <= 1
+        }
+    }
+
+    /// Whether the side to move has any legal move, stops at the first one
+    fn can_move(&self, position: &Position) -> bool {
+        let mut probe = position.clone();
+        probe.variant = self.movement();
+        self.allowed_moves(&probe, probe.pseudo_legal_moves())
+            .into_iter()
+            .any(|mv| {
+                let mut after = probe.clone();
+                after.make_move(&mv);
+                !after.is_in_check(probe.turn)
+            })
+    }
+
+    /// Pseudo-legal moves without the ones taking a teammate's piece
+    fn allowed_moves(&self, position: &Position, moves: Vec<Move>) -> Vec<Move> {
+        moves
+            .into_iter()
+            .filter(|mv| {
+                position
+                    .piece_at(mv.to)
+                    .map_or(true, |target| self.is_enemy(position.turn, target.color))
+            })
+            .collect()
+    }
+
+    /// The player with the most points wins, `forfeited` doesn't count
+    fn most_points(
+        &self,
+        position: &Position,
+        forfeited: Option<PieceColor>,
+        reason: &str,
+    ) -> GameResult {
+        let points = |color: PieceColor| position.state.points[color.index()];
+        let players: Vec<PieceColor> = COLORS
+            .into_iter()
+            .filter(|&color| Some(color) != forfeited)
+            .collect();
+        let best = players
+            .iter()
+            .map(|&color| points(color))
+            .max()
+            .unwrap_or(0);
+        let leaders: Vec<PieceColor> = players
+            .into_iter()
+            .filter(|&color| points(color) == best)
+            .collect();
+        match leaders[..] {
+            [winner] => GameResult::win(winner, &format!("most {}", reason)),
+            _ => GameResult::draw(&format!("equal {}", reason)),
+        }
+    }
+
+    fn put_out(&self, state: &mut VariantState, color: PieceColor) {
+        if !state.out.contains(&color) {
+            state.out.push(color);
+        }
+    }
+}
+
+impl Variant for FourPlayer {
+    fn name(&self) -> &'static str {
+        if self.teams {
+            "Four-player teams"
+        } else {
+            "Four-player"
+        }
+    }
+
+    fn uci_name(&self) -> &'static str {
+        if self.teams {
+            "fourplayerteams"
+        } else {
+            "fourplayer"
+        }
+    }
+
+    /// White at the bottom, Blue on the left, Black at the top and Green on the right. Pieces of
+    /// Blue and Green are written in parentheses.
+    fn starting_fen(&self) -> &'static str {
+        "3rnbkqbnr3/3pppppppp3/14/\
+         (R)(P)10(p)(r)/(N)(P)10(p)(n)/(B)(P)10(p)(b)/(Q)(P)10(p)(k)/\
+         (K)(P)10(p)(q)/(B)(P)10(p)(b)/(N)(P)10(p)(n)/(R)(P)10(p)(r)/\
+         14/3PPPPPPPP3/3RNBQKBNR3 w - - 0 1"
+    }
+
+    fn board(&self) -> BoardShape {
+        BOARD
+    }
+
+    fn colors(&self) -> &'static [PieceColor] {
+        &COLORS
+    }
+
+    fn is_out(&self, position: &Position, color: PieceColor) -> bool {
+        position.state.out.contains(&color)
+    }
+
+    /// Points of the players in the order they move, an `x` marks a player who is out, like
+    /// `3,0,x20,0`
+    fn read_fen(&self, fen: &str, state: &mut VariantState) -> Result<String, String> {
+        let mut fields: Vec<&str> = fen.split_whitespace().collect();
+        if let Some(index) = fields.iter().position(|field| field.contains(',')) {
+            let field = fields.remove(index);
+            let scores: Vec<&str> = field.split(',').collect();
+            if scores.len() != COLORS.len() {
+                return Err(format!(
+                    "expected points of four players, found '{}'",
+                    field
+                ));
+            }
+            for (color, score) in COLORS.into_iter().zip(scores) {
+                let points = match score.strip_prefix('x') {
+                    Some(points) => {
+                        self.put_out(state, color);
+                        points
+                    }
+                    None => score,
+                };
+                state.points[color.index()] = points
+                    .parse()
+                    .map_err(|_| format!("invalid points '{}'", score))?;
+            }
+        }
+        Ok(fields.join(" "))
+    }
+
+    fn write_fen(&self, position: &Position, fen: &mut String) {
+        let scores: Vec<String> = COLORS
+            .into_iter()
+            .map(|color| {
+                let out = if self.is_out(position, color) {
+                    "x"
+                } else {
+                    ""
+                };
+                format!("{}{}", out, position.state.points[color.index()])
+            })
+            .collect();
+        fen.push(' ');
+        fen.push_str(&scores.join(","));
+    }
+
+    fn is_promotion_square(&self, color: PieceColor, (x, y, _): (u8, u8, u8)) -> bool {
+        match color {
+            PieceColor::Light => x == PROMOTION_RANK,
+            PieceColor::Dark => x == BOARD.ranks - 1 - PROMOTION_RANK,
+            PieceColor::Blue => y == PROMOTION_RANK,
+            PieceColor::Green => y == BOARD.files - 1 - PROMOTION_RANK,
+        }
+    }
+
+    fn promotions(&self) -> &'static [PieceType] {
+        &[PieceType::Queen]
+    }
+
+    /// In check from any piece of a player that is still in, teammates don't count
+    fn is_in_check(&self, position: &Position, color: PieceColor) -> bool {
+        let king = match position.king(color) {
+            Some(king) => king.square(),
+            None => return false,
+        };
+        self.remaining(position)
+            .into_iter()
+            .filter(|&other| self.is_enemy(color, other))
+            .any(|other| position.is_attacked(king, other))
+    }
+
+    fn legal_moves(&self, position: &Position, moves: Vec<Move>) -> Vec<Move> {
+        self.allowed_moves(position, moves)
+            .into_iter()
+            .filter(|mv| {
+                let mut after = position.clone();
+                after.make_move(mv);
+                !after.is_in_check(position.turn)
+            })
+            .collect()
+    }
+
+    /// Score the capture, then put out every player whose king was taken or who can't move
+    /// when their turn comes
+    fn after_move(&self, position: &mut Position, mv: &Move, effects: &mut MoveEffects) {
+        // Pawns only go two squares on their first move, nobody takes en passant
+        position.en_passant = None;
+        if !self.eliminates {
+            return;
+        }
+        let mover = match position.piece_at(mv.to) {
+            Some(piece) => piece.color,
+            None => return,
+        };
+        if let Some(captured) = effects.captured {
+            if !self.is_out(position, captured.color) {
+                position.state.points[mover.index()] += capture_points(captured.piece_type);
+                if captured.piece_type == PieceType::King {
+                    self.put_out(&mut position.state, captured.color);
+                }
+            }
+        }
+        if self.is_out(position, position.turn) {
+            position.turn = self.next_turn(position, position.turn);
+        }
+
+        while !self.is_over(position) && !self.can_move(position) {
+            let stuck = position.turn;
+            if position.is_in_check(stuck) {
+                position.state.points[mover.index()] += KING_POINTS;
+            } else {
+                position.state.points[stuck.index()] += KING_POINTS;
+            }
+            self.put_out(&mut position.state, stuck);
+            position.turn = self.next_turn(position, stuck);
+        }
+    }
+
+    /// Teams lose as soon as one of them is out, otherwise the last player standing ends the
+    /// game and the most points win
+    fn outcome(&self, position: &Position, legal_moves: &[Move]) -> Option<GameResult> {
+        if !self.is_over(position) && !legal_moves.is_empty() {
+            return None;
+        }
+        if self.teams {
+            let loser = position.state.out.first().copied().unwrap_or(position.turn);
+            let winner = COLORS
+                .into_iter()
+                .find(|&color| self.is_enemy(loser, color))
+                .unwrap_or(loser);
+            return Some(GameResult::win(
+                winner,
+                &format!("putting {} out, together with {}", loser, partner(winner)),
+            ));
+        }
+        Some(self.most_points(position, None, "points"))
+    }
+
+    /// The other team wins, or whoever else has the most points
+    fn forfeit(&self, position: &Position, color: PieceColor, reason: &str) -> GameResult {
+        if self.teams {
+            let winner = COLORS
+                .into_iter()
+                .find(|&other| self.is_enemy(color, other))
+                .unwrap_or(color);
+            return GameResult::win(winner, &format!("{} of {}", reason, color));
+        }
+        self.most_points(
+            position,
+            Some(color),
+            &format!("points after the {} of {}", reason, color),
+        )
+    }
+
+    fn status(&self, position: &Position) -> Option<String> {
+        if self.teams {
+            return Some("White and Black against Blue and Green".to_string());
+        }
+        let scores: Vec<String> = COLORS
+            .into_iter()
+            .map(|color| {
+                let out = if self.is_out(position, color) {
+                    " (out)"
+                } else {
+                    ""
+                };
+                format!("{} {}{}", color, position.state.points[color.index()], out)
+            })
+            .collect();
+        Some(format!("Points: {}", scores.join("  ")))
+    }
+
+    /// The usual evaluation counts every other piece against the side to move, pieces of a
+    /// teammate or of a player who is out are taken out of that again
+    fn evaluate(&self, position: &Position) -> i32 {
+        let turn = position.turn;
+        let pieces: i32 = position
+            .pieces
+            .iter()
+            .map(|piece| {
+                let value = piece_value(piece.piece_type);
+                if piece.color == turn {
+                    0
+                } else if self.teams && piece.color == partner(turn) {
+                    2 * value
+                } else if self.is_out(position, piece.color) {
+                    value
+                } else {
+                    0
+                }
+            })
+            .sum();
+        if self.teams {
+            return pieces;
+        }
+        let points = |color: PieceColor| position.state.points[color.index()] as i32;
+        let best_other = COLORS
+            .into_iter()
+            .filter(|&color| color != turn)
+            .map(points)
+            .max()
+            .unwrap_or(0);
+        pieces + 100 * (points(turn) - best_other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{notation::parse_uci, pieces::Piece};
+
+    type Placed = (PieceColor, PieceType, (u8, u8));
+
+    /// The kings of every player but `without` on their starting squares
+    fn kings(without: Option<PieceColor>) -> Vec<Placed> {
+        [(0, 7), (7, 0), (13, 6), (6, 13)]
+            .into_iter()
+            .zip(COLORS)
+            .filter(|&(_, color)| Some(color) != without)
+            .map(|(square, color)| (color, PieceType::King, square))
+            .collect()
+    }
+
+    /// A position with only `pieces` on the board and `turn` to move
+    fn position_with(
+        variant: &'static FourPlayer,
+        turn: PieceColor,
+        pieces: &[Placed],
+    ) -> Position {
+        let mut position = Position::starting(variant);
+        position.pieces = pieces
+            .iter()
+            .map(|&(color, piece_type, (x, y))| Piece {
+                color,
+                piece_type,
+                x,
+                y,
+                z: 0,
+            })
+            .collect();
+        position.turn = turn;
+        position.castling = Default::default();
+        position
+    }
+
+    fn play(mut position: Position, uci: &str) -> Position {
+        let mv = parse_uci(&position, uci).unwrap();
+        position.make_move(&mv);
+        position
+    }
+
+    #[test]
+    fn captures_score_points() {
+        let mut pieces = kings(None);
+        pieces.push((PieceColor::Blue, PieceType::Rook, (5, 8)));
+        pieces.push((PieceColor::Light, PieceType::Queen, (5, 5)));
+        let start = position_with(&FourPlayer::FREE_FOR_ALL, PieceColor::Light, &pieces);
+        let position = play(start, "f6i6");
+        assert_eq!(position.state.points, [5, 0, 0, 0]);
+        assert_eq!(position.turn, PieceColor::Blue);
+        assert!(position.to_fen().ends_with(" 5,0,0,0"));
+    }
+
+    #[test]
+    fn taking_a_king_puts_its_player_out() {
+        let mut pieces = kings(None);
+        pieces.push((PieceColor::Blue, PieceType::Rook, (0, 10)));
+        let start = position_with(&FourPlayer::FREE_FOR_ALL, PieceColor::Blue, &pieces);
+        let position = play(start, "k1h1");
+        assert_eq!(position.state.out, [PieceColor::Light]);
+        assert_eq!(position.state.points[PieceColor::Blue.index()], KING_POINTS);
+        assert_eq!(position.turn, PieceColor::Dark);
+        assert!(position.to_fen().ends_with(" x0,20,0,0"));
+        assert_eq!(position.outcome(), None);
+
+        // Players who are out are skipped
+        let position = play(play(position, "g14g13"), "n7n8");
+        assert_eq!(position.turn, PieceColor::Blue);
+    }
+
+    #[test]
+    fn players_who_cant_move_are_out() {
+        let mut pieces = kings(Some(PieceColor::Blue));
+        pieces.push((PieceColor::Blue, PieceType::King, (3, 0)));
+
+        // Stalemate scores for the player who is stuck
+        let queen = (PieceColor::Light, PieceType::Queen, (9, 2));
+        let start = position_with(
+            &FourPlayer::FREE_FOR_ALL,
+            PieceColor::Light,
+            &[pieces.as_slice(), &[queen]].concat(),
+        );
+        let position = play(start, "c10c5");
+        assert_eq!(position.state.out, [PieceColor::Blue]);
+        assert_eq!(position.state.points, [0, 0, KING_POINTS, 0]);
+        assert_eq!(position.turn, PieceColor::Dark);
+
+        // Checkmate scores for the player giving it
+        pieces.push((PieceColor::Light, PieceType::Queen, (4, 5)));
+        pieces.push((PieceColor::Light, PieceType::Rook, (8, 9)));
+        let start = position_with(&FourPlayer::FREE_FOR_ALL, PieceColor::Light, &pieces);
+        let position = play(start, "j9j4");
+        assert_eq!(position.state.out, [PieceColor::Blue]);
+        assert_eq!(position.state.points, [KING_POINTS, 0, 0, 0]);
+    }
+
+    #[test]
+    fn teams_lose_when_one_of_them_is_out() {
+        let mut pieces = kings(None);
+        pieces.push((PieceColor::Blue, PieceType::Rook, (0, 10)));
+        let start = position_with(&FourPlayer::TEAMS, PieceColor::Blue, &pieces);
+        let position = play(start, "k1h1");
+        assert_eq!(
+            position.outcome(),
+            Some(GameResult::win(
+                PieceColor::Blue,
+                "putting White out, together with Green"
+            ))
+        );
+    }
+
+    #[test]
+    fn forfeits_go_to_the_other_team_or_the_most_points() {
+        let start = Position::starting(&FourPlayer::TEAMS);
+        assert_eq!(
+            FourPlayer::TEAMS.forfeit(&start, PieceColor::Dark, "timeout"),
+            GameResult::win(PieceColor::Blue, "timeout of Black")
+        );
+        let mut start = Position::starting(&FourPlayer::FREE_FOR_ALL);
+        assert_eq!(
+            FourPlayer::FREE_FOR_ALL.forfeit(&start, PieceColor::Light, "timeout"),
+            GameResult::draw("equal points after the timeout of White")
+        );
+        start.state.points = [30, 0, 0, 3];
+        assert_eq!(
+            FourPlayer::FREE_FOR_ALL.forfeit(&start, PieceColor::Light, "timeout"),
+            GameResult::win(PieceColor::Green, "most points after the timeout of White")
+        );
+    }
+}
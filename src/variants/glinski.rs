@@ -0,0 +1,199 @@
+use crate::{
+    pieces::{Piece, PieceColor, PieceType},
+    rules::{BoardShape, Move, Position},
+};
+
+use super::{is_king_attacked, moves_of, Variant};
+
+/// Gliński's hexagonal chess on 91 cells in three colors, with three bishops on each side
+#[derive(Debug)]
+pub struct Glinski;
+
+const BOARD: BoardShape = BoardShape {
+    files: 11,
+    ranks: 11,
+    levels: 1,
+    corners: 0,
+    hexagonal: true,
+};
+
+/// A cell in axial coordinates, see `BoardShape::hex_point`
+type Point = (i8, i8);
+
+/// Steps to the six cells sharing an edge, counterclockwise from straight up
+const ORTHOGONAL: [Point; 6] = [(0, 1), (-1, 0), (-1, -1), (0, -1), (1, 0), (1, 1)];
+
+fn add((file, up): Point, (file_step, up_step): Point) -> Point {
+    (file + file_step, up + up_step)
+}
+
+/// Steps to the six cells across a corner, each between two orthogonal steps
+fn diagonals() -> impl Iterator<Item = Point> {
+    (0..6).map(|index| add(ORTHOGONAL[index], ORTHOGONAL[(index + 1) % 6]))
+}
+
+/// Two steps in one orthogonal direction and one in a direction next to it
+fn knight_leaps() -> impl Iterator<Item = Point> {
+    (0..6).flat_map(|index| {
+        let (file, up) = ORTHOGONAL[index];
+        [5, 1].map(move |turn| add((2 * file, 2 * up), ORTHOGONAL[(index + turn) % 6]))
+    })
+}
+
+/// Directions a piece slides or steps in, and whether it slides
+fn directions(piece_type: PieceType) -> (Vec<Point>, bool) {
+    match piece_type {
+        PieceType::Rook => (ORTHOGONAL.to_vec(), true),
+        PieceType::Bishop => (diagonals().collect(), true),
+        PieceType::Queen => (ORTHOGONAL.into_iter().chain(diagonals()).collect(), true),
+        PieceType::King => (ORTHOGONAL.into_iter().chain(diagonals()).collect(), false),
+        PieceType::Knight => (knight_leaps().collect(), false),
+        // Pawns move their own way, the pieces of other variants don't take part
+        _ => (Vec::new(), false),
+    }
+}
+
+/// Index of the orthogonal step straight forward for `color`
+fn forward(color: PieceColor) -> usize {
+    match color {
+        PieceColor::Dark => 3,
+        _ => 0,
+    }
+}
+
+/// The cells the pawns start on, a pawn standing on one of its side's may move two cells
+fn is_pawn_start(color: PieceColor, (x, y, _): (u8, u8, u8)) -> bool {
+    let from_middle = y.abs_diff(BOARD.files / 2);
+    match color {
+        PieceColor::Dark => x == 6,
+        _ => x + from_middle == 4,
+    }
+}
+
+/// Moves of one piece, pawns move straight forward and take on the two cells beside that
+fn push_piece_moves(position: &Position, piece: &Piece, moves: &mut Vec<Move>) {
+    let from = piece.square();
+    let point = BOARD.hex_point(from);
+    let color_at = |to| position.piece_at(to).map(|piece| piece.color);
+
+    if piece.piece_type == PieceType::Pawn {
+        let mut push = |to: (u8, u8, u8)| {
+            if Glinski.is_promotion_square(piece.color, to) {
+                for &promotion in Glinski.promotions() {
+                    moves.push(Move {
+                        promotion: Some(promotion),
+                        ..Move::new(from, to)
+                    });
+                }
+            } else {
+                moves.push(Move::new(from, to));
+            }
+        };
+        let forward = forward(piece.color);
+        let step = ORTHOGONAL[forward];
+        let ahead = BOARD
+            .hex_square(add(point, step))
+            .filter(|&to| color_at(to).is_none());
+        if let Some(ahead) = ahead {
+            push(ahead);
+            let two = BOARD
+                .hex_square(add(BOARD.hex_point(ahead), step))
+                .filter(|&to| color_at(to).is_none());
+            if let Some(two) = two.filter(|_| is_pawn_start(piece.color, from)) {
+                push(two);
+            }
+        }
+        for turn in [5, 1] {
+            let to = match BOARD.hex_square(add(point, ORTHOGONAL[(forward + turn) % 6])) {
+                Some(to) => to,
+                None => continue,
+            };
+            let takes = color_at(to).map_or(position.en_passant == Some(to), |color| {
+                color != piece.color
+            });
+            if takes {
+                push(to);
+            }
+        }
+        return;
+    }
+
+    let (directions, slides) = directions(piece.piece_type);
+    for step in directions {
+        let mut current = point;
+        loop {
+            current = add(current, step);
+            let to = match BOARD.hex_square(current) {
+                Some(to) => to,
+                None => break,
+            };
+            match color_at(to) {
+                Some(color) if color == piece.color => break,
+                Some(_) => {
+                    moves.push(Move::new(from, to));
+                    break;
+                }
+                None => moves.push(Move::new(from, to)),
+            }
+            if !slides {
+                break;
+            }
+        }
+    }
+}
+
+impl Variant for Glinski {
+    fn name(&self) -> &'static str {
+        "Glinski"
+    }
+
+    fn uci_name(&self) -> &'static str {
+        "glinski"
+    }
+
+    /// Ranks 11 down to 1, each with only the cells it has, so rank 11 is just f11
+    fn starting_fen(&self) -> &'static str {
+        "b/qbk/n1b1n/r5r/ppppppppp/11/5P5/4P1P4/3P1B1P3/2P2B2P2/1PRNQBKNRP1 w - - 0 1"
+    }
+
+    fn board(&self) -> BoardShape {
+        BOARD
+    }
+
+    /// White promotes on the last cell of each file, black on the first
+    fn is_promotion_square(&self, color: PieceColor, (x, y, _): (u8, u8, u8)) -> bool {
+        match color {
+            PieceColor::Dark => x == 0,
+            _ => x == BOARD.ranks - 1 - y.abs_diff(BOARD.files / 2),
+        }
+    }
+
+    fn piece_moves(&self, position: &Position) -> Vec<Move> {
+        moves_of(position, position.turn, push_piece_moves)
+    }
+
+    fn is_in_check(&self, position: &Position, color: PieceColor) -> bool {
+        is_king_attacked(position, color, push_piece_moves)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_from_the_start() {
+        let start = Position::starting(&Glinski);
+        assert_eq!(start.perft(1), 51);
+        assert_eq!(start.perft(2), 2586);
+    }
+
+    #[test]
+    fn pawns_promote_on_the_last_cell_of_their_file() {
+        for (file, rank) in [(0, 5), (3, 8), (5, 10), (10, 5)] {
+            assert!(Glinski.is_promotion_square(PieceColor::Light, (rank, file, 0)));
+            assert!(!Glinski.is_promotion_square(PieceColor::Light, (rank - 1, file, 0)));
+            assert!(Glinski.is_promotion_square(PieceColor::Dark, (0, file, 0)));
+        }
+    }
+}
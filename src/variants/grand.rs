@@ -0,0 +1,143 @@
+use crate::{
+    pieces::{PieceColor, PieceType},
+    rules::{BoardShape, Position},
+};
+
+use super::Variant;
+
+/// Grand Chess on a 10×10 board, the pieces stand on the second rank behind pawns on the third,
+/// with only the rooks left in the corners. There is no castling. Pawns may promote on the last
+/// three ranks and have to on the last one, but only to a piece their side has lost.
+#[derive(Debug)]
+pub struct Grand;
+
+const BOARD: BoardShape = BoardShape {
+    files: 10,
+    ranks: 10,
+    levels: 1,
+    corners: 0,
+    hexagonal: false,
+};
+
+const PROMOTIONS: [PieceType; 6] = [
+    PieceType::Queen,
+    PieceType::Chancellor,
+    PieceType::Archbishop,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+];
+
+/// How many pieces of `piece_type` each side starts with
+fn starting_count(piece_type: PieceType) -> usize {
+    match piece_type {
+        PieceType::Rook | PieceType::Bishop | PieceType::Knight => 2,
+        _ => 1,
+    }
+}
+
+impl Variant for Grand {
+    fn name(&self) -> &'static str {
+        "Grand"
+    }
+
+    fn uci_name(&self) -> &'static str {
+        "grand"
+    }
+
+    fn starting_fen(&self) -> &'static str {
+        "r8r/1nbqkcabn1/pppppppppp/10/10/10/10/PPPPPPPPPP/1NBQKCABN1/R8R w - - 0 1"
+    }
+
+    fn board(&self) -> BoardShape {
+        BOARD
+    }
+
+    fn pawn_rank(&self, color: PieceColor) -> u8 {
+        match color {
+            PieceColor::Light | PieceColor::Blue => 2,
+            PieceColor::Dark | PieceColor::Green => BOARD.ranks - 3,
+        }
+    }
+
+    /// The eighth and ninth ranks counted from the pawn's own side
+    fn is_optional_promotion_square(&self, color: PieceColor, (x, _, _): (u8, u8, u8)) -> bool {
+        match color {
+            PieceColor::Dark | PieceColor::Green => (1..=2).contains(&x),
+            PieceColor::Light | PieceColor::Blue => {
+                (BOARD.ranks - 3..=BOARD.ranks - 2).contains(&x)
+            }
+        }
+    }
+
+    fn promotions(&self) -> &'static [PieceType] {
+        &PROMOTIONS
+    }
+
+    /// Only to a piece that was captured, so there are fewer on the board than at the start
+    fn allows_promotion(
+        &self,
+        position: &Position,
+        color: PieceColor,
+        piece_type: PieceType,
+    ) -> bool {
+        let on_board = position
+            .pieces
+            .iter()
+            .filter(|piece| piece.color == color && piece.piece_type == piece_type)
+            .count();
+        on_board < starting_count(piece_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::{parse_uci, uci};
+
+    /// UCI of the legal moves of the pawn on `from` in `fen`
+    fn pawn_moves(fen: &str, from: &str) -> Vec<String> {
+        let position = Position::from_variant_fen(&Grand, fen).unwrap();
+        let from = BOARD.parse_square(from).unwrap();
+        position
+            .legal_moves()
+            .into_iter()
+            .filter(|mv| mv.from == from)
+            .map(|mv| uci(&position, &mv))
+            .collect()
+    }
+
+    #[test]
+    fn moves_from_the_start() {
+        let start = Position::starting(&Grand);
+        assert_eq!(start.perft(1), 65);
+        assert_eq!(start.perft(2), 4225);
+    }
+
+    #[test]
+    fn pawns_only_promote_to_pieces_that_were_captured() {
+        let army = "k9/5P4/10/10/10/10/10/10/10/RNBQKCABNR w - - 0 1";
+        assert!(pawn_moves(army, "f9").is_empty());
+        let no_queen = "k9/5P4/10/10/10/10/10/10/10/RNB1KCABNR w - - 0 1";
+        assert_eq!(pawn_moves(no_queen, "f9"), ["f9f10q"]);
+        let no_pieces = "k9/5P4/10/10/10/10/10/10/10/4K5 w - - 0 1";
+        assert_eq!(pawn_moves(no_pieces, "f9").len(), PROMOTIONS.len());
+    }
+
+    #[test]
+    fn pawns_may_promote_before_the_last_rank() {
+        let no_queen = "k9/10/10/5P4/10/10/10/10/10/RNB1KCABNR w - - 0 1";
+        assert_eq!(pawn_moves(no_queen, "f7"), ["f7f8q", "f7f8"]);
+        let no_queen = "rnb1kcabnr/10/10/10/10/10/5p4/10/10/K9 b - - 0 1";
+        assert_eq!(pawn_moves(no_queen, "f4"), ["f4f3q", "f4f3"]);
+    }
+
+    #[test]
+    fn clicking_promotes_to_a_queen_or_what_there_is() {
+        let no_rook = "k9/5P4/10/10/10/10/10/10/10/1NBQKCABNR w - - 0 1";
+        let position = Position::from_variant_fen(&Grand, no_rook).unwrap();
+        let (from, to) = (BOARD.parse_square("f9"), BOARD.parse_square("f10"));
+        let mv = position.move_to(from.unwrap(), to.unwrap());
+        assert_eq!(mv, parse_uci(&position, "f9f10r").ok());
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{
+    pieces::{PieceColor, PieceType},
+    rules::{GameResult, Move, MoveEffects, Position},
+};
+
+use super::{mate_or_draw, Variant};
+
+/// White's 36 pawns and no king against the usual black army, black wins by taking every white
+/// piece
+#[derive(Debug)]
+pub struct Horde;
+
+impl Variant for Horde {
+    fn name(&self) -> &'static str {
+        "Horde"
+    }
+
+    fn uci_name(&self) -> &'static str {
+        "horde"
+    }
+
+    fn starting_fen(&self) -> &'static str {
+        "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1"
+    }
+
+    fn has_royal_king(&self, color: PieceColor) -> bool {
+        color == PieceColor::Dark
+    }
+
+    /// White pawns on the first rank can move two squares as well
+    fn add_moves(&self, position: &Position, moves: &mut Vec<Move>) {
+        if position.turn != PieceColor::Light {
+            return;
+        }
+        for pawn in position.pieces.iter().filter(|piece| {
+            piece.color == PieceColor::Light && piece.piece_type == PieceType::Pawn && piece.x == 0
+        }) {
+            let is_free = |x| position.piece_at((x, pawn.y, 0)).is_none();
+            if is_free(1) && is_free(2) {
+                moves.push(Move::new((0, pawn.y, 0), (2, pawn.y, 0)));
+            }
+        }
+    }
+
+    /// A pawn that moved two squares from the first rank can't be taken en passant
+    fn after_move(&self, position: &mut Position, mv: &Move, _effects: &mut MoveEffects) {
+        if mv.from.0 == 0 && position.en_passant == Some((1, mv.from.1, 0)) {
+            position.en_passant = None;
+        }
+    }
+
+    fn outcome(&self, position: &Position, legal_moves: &[Move]) -> Option<GameResult> {
+        let horde_left = position
+            .pieces
+            .iter()
+            .any(|piece| piece.color == PieceColor::Light);
+        if !horde_left {
+            return Some(GameResult::win(PieceColor::Dark, "destroying the horde"));
+        }
+        mate_or_draw(position, legal_moves)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{notation::parse_uci, variants::after_moves};
+
+    #[test]
+    fn first_rank_pawns_move_two_squares() {
+        assert_eq!(Position::starting(&Horde).perft(3), 1274);
+        let fen = "4k3/8/8/8/8/1p6/8/P7 w - - 0 1";
+        let position = after_moves(&Horde, fen, &["a1a3"]);
+        assert_eq!(position.en_passant, None);
+        assert!(parse_uci(&position, "b3a2").is_err());
+    }
+
+    #[test]
+    fn second_rank_pawns_can_be_taken_en_passant() {
+        let fen = "4k3/8/8/8/1p6/8/P7/8 w - - 0 1";
+        let position = after_moves(&Horde, fen, &["a2a4"]);
+        assert!(parse_uci(&position, "b4a3").is_ok());
+    }
+
+    #[test]
+    fn black_wins_by_destroying_the_horde() {
+        let position = after_moves(&Horde, "r3k3/8/8/8/8/8/8/P7 b - - 0 1", &["a8a1"]);
+        assert_eq!(
+            position.outcome(),
+            Some(GameResult::win(PieceColor::Dark, "destroying the horde"))
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use crate::{
+    pieces::{PieceColor, PieceType},
+    rules::{GameResult, Move, Position},
+};
+
+use super::{mate_or_draw, Variant};
+
+/// The four center squares
+const HILL: [(u8, u8, u8); 4] = [(3, 3, 0), (3, 4, 0), (4, 3, 0), (4, 4, 0)];
+
+/// Standard chess, but a king that reaches the center wins
+#[derive(Debug)]
+pub struct KingOfTheHill;
+
+/// How many king moves `color` needs to reach the hill
+fn distance_to_hill(position: &Position, color: PieceColor) -> i32 {
+    position.king(color).map_or(0, |king| {
+        HILL.iter()
+            .map(|&(x, y, _)| king.x.abs_diff(x).max(king.y.abs_diff(y)) as i32)
+            .min()
+            .unwrap_or(0)
+    })
+}
+
+impl Variant for KingOfTheHill {
+    fn name(&self) -> &'static str {
+        "King of the Hill"
+    }
+
+    fn uci_name(&self) -> &'static str {
+        "kingofthehill"
+    }
+
+    fn outcome(&self, position: &Position, legal_moves: &[Move]) -> Option<GameResult> {
+        let mover = position.turn.opponent();
+        let on_hill = position.pieces.iter().any(|piece| {
+            piece.color == mover
+                && piece.piece_type == PieceType::King
+                && HILL.contains(&piece.square())
+        });
+        if on_hill {
+            return Some(GameResult::win(mover, "reaching the hill"));
+        }
+        // Bare kings can still race to the center
+        mate_or_draw(position, legal_moves)
+    }
+
+    fn goal_squares(&self) -> Vec<(u8, u8, u8)> {
+        HILL.to_vec()
+    }
+
+    fn evaluate(&self, position: &Position) -> i32 {
+        let turn = position.turn;
+        30 * (distance_to_hill(position, turn.opponent()) - distance_to_hill(position, turn))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variants::after_moves;
+
+    #[test]
+    fn a_king_reaching_the_center_wins() {
+        let fen = "4k3/8/8/8/8/4K3/8/8 w - - 0 1";
+        let beside = after_moves(&KingOfTheHill, fen, &["e3f3"]);
+        assert_eq!(beside.outcome(), None);
+        let on_hill = after_moves(&KingOfTheHill, fen, &["e3e4"]);
+        assert_eq!(
+            on_hill.outcome(),
+            Some(GameResult::win(PieceColor::Light, "reaching the hill"))
+        );
+    }
+}
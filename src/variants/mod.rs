@@ -0,0 +1,466 @@
+//! Rule sets, most of them played on the usual board. Standard chess is one of them, the others
+//! only override the parts of the rules they change.
+
+mod antichess;
+mod atomic;
+mod capablanca;
+mod crazyhouse;
+mod four_player;
+mod glinski;
+mod grand;
+mod horde;
+mod king_of_the_hill;
+mod racing_kings;
+mod raumschach;
+mod three_check;
+
+pub use antichess::Antichess;
+pub use atomic::Atomic;
+pub use capablanca::Capablanca;
+pub use crazyhouse::{reserve_counts, Crazyhouse, RESERVE_ORDER};
+pub use four_player::FourPlayer;
+pub use glinski::Glinski;
+pub use grand::Grand;
+pub use horde::Horde;
+pub use king_of_the_hill::KingOfTheHill;
+pub use racing_kings::RacingKings;
+pub use raumschach::Raumschach;
+pub use three_check::ThreeCheck;
+
+use once_cell::sync::Lazy;
+
+use crate::{
+    betza::Movement,
+    fen::STARTING_FEN,
+    pieces::{Piece, PieceColor, PieceType},
+    rules::{BoardShape, GameResult, Move, MoveEffects, Position, PROMOTIONS},
+};
+
+/// Pieces moving as `PieceType::betza` writes it
+static STANDARD_MOVEMENT: Lazy<Result<Movement, String>> =
+    Lazy::new(|| Movement::new(PieceType::betza));
+
+/// The rules of a game, every hook defaults to the standard rules
+pub trait Variant: std::fmt::Debug + Sync {
+    /// Name used on the command line and in the PGN `Variant` tag
+    fn name(&self) -> &'static str;
+
+    /// Value of the `UCI_Variant` option for engines that play it
+    fn uci_name(&self) -> &'static str;
+
+    fn starting_fen(&self) -> &'static str {
+        STARTING_FEN
+    }
+
+    fn board(&self) -> BoardShape {
+        BoardShape::STANDARD
+    }
+
+    /// The sides in the order they move
+    fn colors(&self) -> &'static [PieceColor] {
+        &[PieceColor::Light, PieceColor::Dark]
+    }
+
+    /// Whose turn it is after `color` moved, the turn goes round the sides that are still in
+    fn next_turn(&self, position: &Position, color: PieceColor) -> PieceColor {
+        let colors = self.colors();
+        let index = colors.iter().position(|&c| c == color).unwrap_or(0);
+        (1..=colors.len())
+            .map(|offset| colors[(index + offset) % colors.len()])
+            .find(|&next| !self.is_out(position, next))
+            .unwrap_or(color)
+    }
+
+    /// Whether `color` is out of a game with more than two sides, its pieces stay on the board
+    fn is_out(&self, _position: &Position, _color: PieceColor) -> bool {
+        false
+    }
+
+    /// Read the variant's own FEN fields into `state`, returns the FEN without them
+    fn read_fen(&self, fen: &str, _state: &mut VariantState) -> Result<String, String> {
+        Ok(fen.to_string())
+    }
+
+    /// Add the variant's own fields to the FEN of `position`
+    fn write_fen(&self, _position: &Position, _fen: &mut String) {}
+
+    /// Moves of the pieces on the board, variants on other boards bring their own movement
+    fn piece_moves(&self, position: &Position) -> Vec<Move> {
+        position.standard_piece_moves()
+    }
+
+    /// How the pieces move, variants can give a piece other moves in a `Movement` of their own.
+    /// Positions of a variant whose Betza notation doesn't parse can't be set up.
+    fn movement(&self) -> Result<&'static Movement, &'static str> {
+        STANDARD_MOVEMENT.as_ref().map_err(String::as_str)
+    }
+
+    /// Rank the pawns of `color` start on, they may move two squares from there
+    fn pawn_rank(&self, color: PieceColor) -> u8 {
+        match color {
+            PieceColor::Light | PieceColor::Blue => 1,
+            PieceColor::Dark => self.board().ranks - 2,
+            PieceColor::Green => self.board().files - 2,
+        }
+    }
+
+    /// Whether a pawn of `color` promotes when it reaches `square`
+    fn is_promotion_square(&self, color: PieceColor, (x, _, _): (u8, u8, u8)) -> bool {
+        x == self.board().back_rank(color.opponent())
+    }
+
+    /// Whether a pawn of `color` may promote on `square` before reaching the squares where it
+    /// has to
+    fn is_optional_promotion_square(&self, _color: PieceColor, _square: (u8, u8, u8)) -> bool {
+        false
+    }
+
+    /// Pieces a pawn promotes to
+    fn promotions(&self) -> &'static [PieceType] {
+        &PROMOTIONS
+    }
+
+    /// Whether a pawn of `color` may promote to `piece_type` in `position`, for variants that
+    /// limit promotions to the pieces on the board
+    fn allows_promotion(
+        &self,
+        _position: &Position,
+        _color: PieceColor,
+        _piece_type: PieceType,
+    ) -> bool {
+        true
+    }
+
+    /// Add moves the usual piece movement doesn't cover to the pseudo-legal `moves`
+    fn add_moves(&self, _position: &Position, _moves: &mut Vec<Move>) {}
+
+    /// Whether `color` needs a king that can be checked, otherwise the king is a usual piece
+    fn has_royal_king(&self, _color: PieceColor) -> bool {
+        true
+    }
+
+    fn is_in_check(&self, position: &Position, color: PieceColor) -> bool {
+        position.is_king_attacked(color)
+    }
+
+    /// Keep the pseudo-legal `moves` that may be played, by default those that don't leave the
+    /// mover's king in check
+    fn legal_moves(&self, position: &Position, moves: Vec<Move>) -> Vec<Move> {
+        moves
+            .into_iter()
+            .filter(|mv| {
+                let mut after = position.clone();
+                after.make_move(mv);
+                !after.is_in_check(position.turn)
+            })
+            .collect()
+    }
+
+    /// Change the position after `mv` was played the usual way, with the side to move already
+    /// switched. Changes to the pieces have to be added to `effects`.
+    fn after_move(&self, _position: &mut Position, _mv: &Move, _effects: &mut MoveEffects) {}
+
+    /// The result if the game is over, `legal_moves` are the moves of the side to move
+    fn outcome(&self, position: &Position, legal_moves: &[Move]) -> Option<GameResult> {
+        if let Some(result) = mate_or_draw(position, legal_moves) {
+            return Some(result);
+        }
+        if position
+            .pieces
+            .iter()
+            .all(|piece| piece.piece_type == PieceType::King)
+        {
+            return Some(GameResult::draw("insufficient material"));
+        }
+        None
+    }
+
+    /// The result when `color` loses off the board, by resigning, running out of time or an
+    /// engine failing, for `reason`
+    fn forfeit(&self, _position: &Position, color: PieceColor, reason: &str) -> GameResult {
+        GameResult::win(color.opponent(), reason)
+    }
+
+    /// Whether captured pieces go to a reserve they can be dropped from, shown beside the board
+    fn has_reserves(&self) -> bool {
+        false
+    }
+
+    /// Squares a king wins the game on, they are highlighted on the board
+    fn goal_squares(&self) -> Vec<(u8, u8, u8)> {
+        Vec::new()
+    }
+
+    /// A line about the variant's own state for the UI, like how many checks were given
+    fn status(&self, _position: &Position) -> Option<String> {
+        None
+    }
+
+    /// Score for the side to move on top of the material, to steer the built-in engine towards
+    /// the variant's goal
+    fn evaluate(&self, _position: &Position) -> i32 {
+        0
+    }
+
+    /// Whether the moves can be generated on bitboards, only for the standard rules on the usual
+    /// board
+    fn uses_bitboards(&self) -> bool {
+        false
+    }
+}
+
+/// Adds the moves of one piece to a list, for variants that move pieces on a board of their own
+pub type PushPieceMoves = fn(&Position, &Piece, &mut Vec<Move>);
+
+/// Pseudo-legal moves of every piece of `color`
+pub fn moves_of(position: &Position, color: PieceColor, push: PushPieceMoves) -> Vec<Move> {
+    let mut moves = Vec::new();
+    for piece in position.pieces.iter().filter(|piece| piece.color == color) {
+        push(position, piece, &mut moves);
+    }
+    moves
+}
+
+/// Whether a piece of the opponent of `color` could take its king
+pub fn is_king_attacked(position: &Position, color: PieceColor, push: PushPieceMoves) -> bool {
+    let king = match position.king(color) {
+        Some(king) => king.square(),
+        None => return false,
+    };
+    moves_of(position, color.opponent(), push)
+        .iter()
+        .any(|mv| mv.to == king)
+}
+
+/// Checkmate, stalemate and the fifty-move rule
+pub fn mate_or_draw(position: &Position, legal_moves: &[Move]) -> Option<GameResult> {
+    if legal_moves.is_empty() {
+        return Some(if position.is_in_check(position.turn) {
+            GameResult::win(position.turn.opponent(), "checkmate")
+        } else {
+            GameResult::draw("stalemate")
+        });
+    }
+    if position.halfmove_clock >= 100 {
+        return Some(GameResult::draw("the fifty-move rule"));
+    }
+    None
+}
+
+/// Anything a variant keeps track of besides the pieces and the usual counters, variants add
+/// their fields here
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VariantState {
+    /// Checks given by white and black, for Three-check
+    pub checks: [u8; 2],
+    /// Pieces white and black can drop, for Crazyhouse
+    pub reserves: [Vec<PieceType>; 2],
+    /// Squares of promoted pieces, they go back to the reserve as pawns when captured
+    pub promoted: Vec<(u8, u8, u8)>,
+    /// Points of each player in four-player chess
+    pub points: [u32; 4],
+    /// Players who are out of a four-player game, in the order they went out
+    pub out: Vec<PieceColor>,
+}
+
+#[derive(Debug)]
+pub struct Standard;
+
+impl Variant for Standard {
+    fn name(&self) -> &'static str {
+        "Standard"
+    }
+
+    fn uci_name(&self) -> &'static str {
+        "chess"
+    }
+
+    fn uses_bitboards(&self) -> bool {
+        true
+    }
+}
+
+/// Every variant that can be played
+pub static VARIANTS: &[&dyn Variant] = &[
+    &Standard,
+    &KingOfTheHill,
+    &ThreeCheck,
+    &RacingKings,
+    &Atomic,
+    &Crazyhouse,
+    &Antichess,
+    &Horde,
+    &Raumschach,
+    &Capablanca,
+    &Grand,
+    &FourPlayer::FREE_FOR_ALL,
+    &FourPlayer::TEAMS,
+    &Glinski,
+];
+
+/// Look up a variant by name, case, spaces and dashes don't matter
+pub fn find_variant(name: &str) -> Result<&'static dyn Variant, String> {
+    let simplify = |name: &str| -> String {
+        name.chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_lowercase())
+            .collect()
+    };
+    let wanted = simplify(name);
+    VARIANTS
+        .iter()
+        .copied()
+        .find(|variant| simplify(variant.name()) == wanted || variant.uci_name() == wanted)
+        .ok_or_else(|| {
+            let names: Vec<&str> = VARIANTS.iter().map(|variant| variant.name()).collect();
+            format!(
+                "unknown variant '{}', expected one of {}",
+                name,
+                names.join(", ")
+            )
+        })
+}
+
+impl Position {
+    /// The starting position of `variant`
+    pub fn starting(variant: &'static dyn Variant) -> Self {
+        Position::from_variant_fen(variant, variant.starting_fen())
+            .expect("variants start from a valid FEN")
+    }
+
+    /// Parse a position of `variant` in Forsyth-Edwards Notation
+    pub fn from_variant_fen(variant: &'static dyn Variant, fen: &str) -> Result<Self, String> {
+        variant.movement()?;
+        let mut state = VariantState::default();
+        let fen = variant.read_fen(fen, &mut state)?;
+        let mut position = Position::parse_fen(&fen, variant.board())?;
+        for color in [PieceColor::Light, PieceColor::Dark] {
+            if variant.has_royal_king(color) {
+                position.check_king(color)?;
+            }
+        }
+        position.variant = variant;
+        position.state = state;
+        position.check_pawns()?;
+        Ok(position)
+    }
+
+    pub fn is_standard(&self) -> bool {
+        self.variant.name() == Standard.name()
+    }
+}
+
+/// `fen` of `variant` after `moves` in UCI notation, for the tests of the variants
+#[cfg(test)]
+fn after_moves(variant: &'static dyn Variant, fen: &str, moves: &[&str]) -> Position {
+    let mut position = Position::from_variant_fen(variant, fen).unwrap();
+    for text in moves {
+        let mv = crate::notation::parse_uci(&position, text)
+            .unwrap_or_else(|err| panic!("{}: {}", position.to_fen(), err));
+        position.make_move(&mv);
+    }
+    position
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_variant_starts_from_a_fen_that_reads_back() {
+        for &variant in VARIANTS {
+            let start = Position::starting(variant);
+            let fen = start.to_fen();
+            let read = Position::from_variant_fen(variant, &fen).unwrap();
+            assert_eq!(read.to_fen(), fen, "{}", variant.name());
+            assert!(!start.legal_moves().is_empty(), "{}", variant.name());
+            assert_eq!(start.outcome(), None, "{}", variant.name());
+        }
+    }
+
+    #[test]
+    fn variants_are_found_by_name_or_uci_name() {
+        for (name, found) in [
+            ("King of the Hill", "King of the Hill"),
+            ("king-of-the-hill", "King of the Hill"),
+            ("3check", "Three-check"),
+        ] {
+            assert_eq!(find_variant(name).unwrap().name(), found);
+        }
+        assert!(find_variant("bughouse").is_err());
+    }
+
+    #[test]
+    fn standard_rules_are_the_defaults() {
+        let position = Position::starting(&Standard);
+        assert!(position.is_standard());
+        assert_eq!(position.perft(3), 8902);
+        let mate = after_moves(&Standard, STARTING_FEN, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+        assert_eq!(
+            mate.outcome(),
+            Some(GameResult::win(PieceColor::Dark, "checkmate"))
+        );
+        let resigned = Standard.forfeit(&mate, PieceColor::Light, "resignation");
+        assert_eq!(resigned.winner, Some(PieceColor::Dark));
+    }
+
+    /// Standard chess with knights that keep leaping in the same direction
+    #[derive(Debug)]
+    struct Nightriders;
+
+    static NIGHTRIDERS: Lazy<Result<Movement, String>> = Lazy::new(|| {
+        Movement::new(|piece_type| match piece_type {
+            PieceType::Knight => Some("NN"),
+            other => other.betza(),
+        })
+    });
+
+    static MISPRINTED: Lazy<Result<Movement, String>> = Lazy::new(|| {
+        Movement::new(|piece_type| match piece_type {
+            PieceType::King => Some("KX"),
+            other => other.betza(),
+        })
+    });
+
+    impl Variant for Nightriders {
+        fn name(&self) -> &'static str {
+            "Nightriders"
+        }
+
+        fn uci_name(&self) -> &'static str {
+            "nightriders"
+        }
+
+        fn movement(&self) -> Result<&'static Movement, &'static str> {
+            NIGHTRIDERS.as_ref().map_err(String::as_str)
+        }
+    }
+
+    /// A king written in Betza notation that doesn't parse
+    #[derive(Debug)]
+    struct Misprinted;
+
+    impl Variant for Misprinted {
+        fn name(&self) -> &'static str {
+            "Misprinted"
+        }
+
+        fn uci_name(&self) -> &'static str {
+            "misprinted"
+        }
+
+        fn movement(&self) -> Result<&'static Movement, &'static str> {
+            MISPRINTED.as_ref().map_err(String::as_str)
+        }
+    }
+
+    #[test]
+    fn variants_give_pieces_their_own_betza() {
+        let position = Position::starting(&Nightriders);
+        assert!(crate::notation::parse_uci(&position, "b1e7").is_ok());
+        assert_eq!(
+            Position::from_variant_fen(&Misprinted, STARTING_FEN).err(),
+            Some("King: unknown atom 'X' in KX".to_string())
+        );
+    }
+}
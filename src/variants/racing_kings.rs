@@ -0,0 +1,110 @@
+use crate::{
+    pieces::{PieceColor, PieceType},
+    rules::{GameResult, Move, Position},
+};
+
+use super::{mate_or_draw, Variant};
+
+/// Rank the kings race to
+const LAST_RANK: u8 = 7;
+
+/// Both kings race to the eighth rank, nobody may give or be in check
+#[derive(Debug)]
+pub struct RacingKings;
+
+fn king_rank(position: &Position, color: PieceColor) -> Option<u8> {
+    position.king(color).map(|king| king.x)
+}
+
+impl Variant for RacingKings {
+    fn name(&self) -> &'static str {
+        "Racing Kings"
+    }
+
+    fn uci_name(&self) -> &'static str {
+        "racingkings"
+    }
+
+    fn starting_fen(&self) -> &'static str {
+        "8/8/8/8/8/8/krbnNBRK/qrbnNBRQ w - - 0 1"
+    }
+
+    fn legal_moves(&self, position: &Position, moves: Vec<Move>) -> Vec<Move> {
+        moves
+            .into_iter()
+            .filter(|mv| {
+                let mut after = position.clone();
+                after.make_move(mv);
+                !after.is_in_check(PieceColor::Light) && !after.is_in_check(PieceColor::Dark)
+            })
+            .collect()
+    }
+
+    fn outcome(&self, position: &Position, legal_moves: &[Move]) -> Option<GameResult> {
+        let white_home = king_rank(position, PieceColor::Light) == Some(LAST_RANK);
+        let black_home = king_rank(position, PieceColor::Dark) == Some(LAST_RANK);
+        match (white_home, black_home) {
+            (true, true) => return Some(GameResult::draw("both kings reaching the goal")),
+            (false, true) => return Some(GameResult::win(PieceColor::Dark, "reaching the goal")),
+            (true, false) => {
+                // Black gets one more move to draw by reaching the goal too
+                let black_can_follow = position.turn == PieceColor::Dark
+                    && legal_moves.iter().any(|mv| {
+                        mv.to.0 == LAST_RANK
+                            && position.piece_at(mv.from).map(|piece| piece.piece_type)
+                                == Some(PieceType::King)
+                    });
+                if !black_can_follow {
+                    return Some(GameResult::win(PieceColor::Light, "reaching the goal"));
+                }
+            }
+            (false, false) => {}
+        }
+        // Nobody is ever in check, so running out of moves is a stalemate
+        mate_or_draw(position, legal_moves)
+    }
+
+    fn goal_squares(&self) -> Vec<(u8, u8, u8)> {
+        (0..8).map(|y| (LAST_RANK, y, 0)).collect()
+    }
+
+    fn evaluate(&self, position: &Position) -> i32 {
+        let rank = |color| king_rank(position, color).unwrap_or(0) as i32;
+        100 * (rank(position.turn) - rank(position.turn.opponent()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variants::after_moves;
+
+    #[test]
+    fn moves_that_give_check_are_not_allowed() {
+        let start = Position::starting(&RacingKings);
+        assert_eq!(start.perft(3), 11264);
+        // The rook could check the king on a8 from the a-file
+        let position =
+            Position::from_variant_fen(&RacingKings, "k7/8/8/8/8/8/8/1R4K1 w - - 0 1").unwrap();
+        assert!(!position.is_legal(&Move::new((0, 1, 0), (0, 0, 0))));
+        assert!(position.is_legal(&Move::new((0, 1, 0), (1, 1, 0))));
+    }
+
+    #[test]
+    fn reaching_the_last_rank_wins_unless_black_follows() {
+        let far = after_moves(&RacingKings, "8/6K1/8/8/8/8/k7/8 w - - 0 1", &["g7g8"]);
+        assert_eq!(
+            far.outcome(),
+            Some(GameResult::win(PieceColor::Light, "reaching the goal"))
+        );
+
+        let fen = "8/k5K1/8/8/8/8/8/8 w - - 0 1";
+        let close = after_moves(&RacingKings, fen, &["g7g8"]);
+        assert_eq!(close.outcome(), None);
+        let both = after_moves(&RacingKings, fen, &["g7g8", "a7a8"]);
+        assert_eq!(
+            both.outcome(),
+            Some(GameResult::draw("both kings reaching the goal"))
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use crate::{
+    pieces::{Piece, PieceColor, PieceType},
+    rules::{BoardShape, Move, Position},
+};
+
+use super::{is_king_attacked, moves_of, Variant};
+
+/// Chess in three dimensions on five stacked 5×5 levels, with a unicorn that moves through the
+/// corners of the cubes
+#[derive(Debug)]
+pub struct Raumschach;
+
+const BOARD: BoardShape = BoardShape {
+    files: 5,
+    ranks: 5,
+    levels: 5,
+    corners: 0,
+    hexagonal: false,
+};
+
+const PROMOTIONS: [PieceType; 5] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::Unicorn,
+];
+
+/// A square as `(rank, file, level)`, signed to take steps
+type Point = (i8, i8, i8);
+
+fn point((x, y, z): (u8, u8, u8)) -> Point {
+    (x as i8, y as i8, z as i8)
+}
+
+fn square((rank, file, level): Point) -> Option<(u8, u8, u8)> {
+    let size = BOARD.ranks as i8;
+    if [rank, file, level].iter().any(|&c| c < 0 || c >= size) {
+        return None;
+    }
+    Some((rank as u8, file as u8, level as u8))
+}
+
+/// Steps that change exactly `changed` of the three coordinates by one
+fn steps(changed: usize) -> impl Iterator<Item = Point> {
+    (-1..=1).flat_map(move |rank| {
+        (-1..=1).flat_map(move |file| {
+            (-1..=1)
+                .map(move |level| (rank, file, level))
+                .filter(move |&(rank, file, level)| {
+                    [rank, file, level].iter().filter(|&&c| c != 0).count() == changed
+                })
+        })
+    })
+}
+
+/// Every way to move one square along one axis and two along another
+fn knight_leaps() -> impl Iterator<Item = Point> {
+    (-2..=2).flat_map(|rank| {
+        (-2..=2).flat_map(move |file| {
+            (-2..=2).map(move |level| (rank, file, level)).filter(
+                |&(rank, file, level): &(i8, i8, i8)| {
+                    let mut sizes = [rank.abs(), file.abs(), level.abs()];
+                    sizes.sort_unstable();
+                    sizes == [0, 1, 2]
+                },
+            )
+        })
+    })
+}
+
+/// Directions a piece slides or steps in, and whether it slides
+fn directions(piece_type: PieceType) -> (Vec<Point>, bool) {
+    match piece_type {
+        PieceType::Rook => (steps(1).collect(), true),
+        PieceType::Bishop => (steps(2).collect(), true),
+        PieceType::Unicorn => (steps(3).collect(), true),
+        PieceType::Queen => ((1..=3).flat_map(steps).collect(), true),
+        PieceType::King => ((1..=3).flat_map(steps).collect(), false),
+        PieceType::Knight => (knight_leaps().collect(), false),
+        // Pawns move their own way, the pieces of wider boards don't take part
+        PieceType::Pawn | PieceType::Archbishop | PieceType::Chancellor | PieceType::Amazon => {
+            (Vec::new(), false)
+        }
+    }
+}
+
+/// Moves of one piece, pawns move forward or up and take forward or up to another file
+fn push_piece_moves(position: &Position, piece: &Piece, moves: &mut Vec<Move>) {
+    let from = piece.square();
+    let (rank, file, level) = point(from);
+    let color_at = |to| position.piece_at(to).map(|piece| piece.color);
+
+    if piece.piece_type == PieceType::Pawn {
+        let forward = piece.color.forward().0;
+        let mut push = |to: (u8, u8, u8)| {
+            if Raumschach.is_promotion_square(piece.color, to) {
+                for &promotion in Raumschach.promotions() {
+                    moves.push(Move {
+                        promotion: Some(promotion),
+                        ..Move::new(from, to)
+                    });
+                }
+            } else {
+                moves.push(Move::new(from, to));
+            }
+        };
+        for (rank_step, level_step) in [(forward, 0), (0, forward)] {
+            let ahead = square((rank + rank_step, file, level + level_step));
+            if let Some(to) = ahead.filter(|&to| color_at(to).is_none()) {
+                push(to);
+            }
+            for side in [-1, 1] {
+                let diagonal = square((rank + rank_step, file + side, level + level_step));
+                if let Some(to) =
+                    diagonal.filter(|&to| color_at(to) == Some(piece.color.opponent()))
+                {
+                    push(to);
+                }
+            }
+        }
+        return;
+    }
+
+    let (directions, slides) = directions(piece.piece_type);
+    for (rank_step, file_step, level_step) in directions {
+        let mut current = (rank, file, level);
+        loop {
+            current = (
+                current.0 + rank_step,
+                current.1 + file_step,
+                current.2 + level_step,
+            );
+            let to = match square(current) {
+                Some(to) => to,
+                None => break,
+            };
+            match color_at(to) {
+                Some(color) if color == piece.color => break,
+                Some(_) => {
+                    moves.push(Move::new(from, to));
+                    break;
+                }
+                None => moves.push(Move::new(from, to)),
+            }
+            if !slides {
+                break;
+            }
+        }
+    }
+}
+
+impl Variant for Raumschach {
+    fn name(&self) -> &'static str {
+        "Raumschach"
+    }
+
+    fn uci_name(&self) -> &'static str {
+        "raumschach"
+    }
+
+    /// Levels E down to A, white starts on A and B and black on D and E
+    fn starting_fen(&self) -> &'static str {
+        "rnknr/ppppp/5/5/5/buqbu/ppppp/5/5/5/5/5/5/5/5/5/5/5/PPPPP/BUQBU/5/5/5/PPPPP/RNKNR w - - 0 1"
+    }
+
+    fn board(&self) -> BoardShape {
+        BOARD
+    }
+
+    /// White promotes on the last rank of the top level, black on the first rank of the bottom one
+    fn is_promotion_square(&self, color: PieceColor, (x, _, z): (u8, u8, u8)) -> bool {
+        match color {
+            PieceColor::Dark => (x, z) == (0, 0),
+            _ => (x, z) == (BOARD.ranks - 1, BOARD.levels - 1),
+        }
+    }
+
+    fn promotions(&self) -> &'static [PieceType] {
+        &PROMOTIONS
+    }
+
+    fn piece_moves(&self, position: &Position) -> Vec<Move> {
+        moves_of(position, position.turn, push_piece_moves)
+    }
+
+    fn is_in_check(&self, position: &Position, color: PieceColor) -> bool {
+        is_king_attacked(position, color, push_piece_moves)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_from_the_start() {
+        let start = Position::starting(&Raumschach);
+        assert_eq!(start.perft(1), 61);
+        assert_eq!(start.perft(2), 3608);
+    }
+
+    #[test]
+    fn unicorns_move_through_the_corners_of_the_cubes() {
+        let empty = ["5"; 5].join("/");
+        let fen = format!(
+            "1k3/5/5/5/5/{empty}/5/5/2U2/5/5/{empty}/5/5/5/5/3K1 w - - 0 1",
+            empty = empty
+        );
+        let position = Position::from_variant_fen(&Raumschach, &fen).unwrap();
+        let unicorn_moves: Vec<Move> = position
+            .legal_moves()
+            .into_iter()
+            .filter(|mv| mv.from == (2, 2, 2))
+            .collect();
+        assert_eq!(unicorn_moves.len(), 16);
+        // Every move changes the rank, the file and the level alike
+        assert!(unicorn_moves.iter().all(|mv| {
+            let (from, to) = (point(mv.from), point(mv.to));
+            let sizes = [to.0 - from.0, to.1 - from.1, to.2 - from.2].map(i8::abs);
+            sizes[0] > 0 && sizes == [sizes[0]; 3]
+        }));
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{
+    pieces::PieceColor,
+    rules::{GameResult, Move, MoveEffects, Position},
+};
+
+use super::{Standard, Variant, VariantState};
+
+/// Checks that win the game
+const CHECKS_TO_WIN: u8 = 3;
+
+/// Standard chess, but giving check three times wins
+#[derive(Debug)]
+pub struct ThreeCheck;
+
+impl Variant for ThreeCheck {
+    fn name(&self) -> &'static str {
+        "Three-check"
+    }
+
+    fn uci_name(&self) -> &'static str {
+        "3check"
+    }
+
+    /// Checks are written after the move counters as given by white and black, like `+1+0`, or
+    /// after the en passant square as the checks both sides have left, like `2+3`
+    fn read_fen(&self, fen: &str, state: &mut VariantState) -> Result<String, String> {
+        let mut fields: Vec<&str> = fen.split_whitespace().collect();
+        let checks = |text: &str| -> Option<[u8; 2]> {
+            let (white, black) = text.trim_start_matches('+').split_once('+')?;
+            Some([white.parse().ok()?, black.parse().ok()?])
+        };
+        if let Some(index) = fields.iter().position(|field| field.contains('+')) {
+            let field = fields.remove(index);
+            let counted = checks(field).ok_or_else(|| format!("invalid checks '{}'", field))?;
+            state.checks = if field.starts_with('+') {
+                counted
+            } else {
+                counted.map(|left| CHECKS_TO_WIN.saturating_sub(left))
+            };
+        }
+        Ok(fields.join(" "))
+    }
+
+    fn write_fen(&self, position: &Position, fen: &mut String) {
+        let [white, black] = position.state.checks;
+        fen.push_str(&format!(" +{}+{}", white, black));
+    }
+
+    fn after_move(&self, position: &mut Position, _mv: &Move, _effects: &mut MoveEffects) {
+        if position.is_in_check(position.turn) {
+            position.state.checks[position.turn.opponent().index()] += 1;
+        }
+    }
+
+    fn outcome(&self, position: &Position, legal_moves: &[Move]) -> Option<GameResult> {
+        let mover = position.turn.opponent();
+        if position.state.checks[mover.index()] >= CHECKS_TO_WIN {
+            return Some(GameResult::win(mover, "three checks"));
+        }
+        Standard.outcome(position, legal_moves)
+    }
+
+    fn status(&self, position: &Position) -> Option<String> {
+        let [white, black] = position.state.checks;
+        Some(format!("Checks: White {}  Black {}", white, black))
+    }
+
+    fn evaluate(&self, position: &Position) -> i32 {
+        let given = |color: PieceColor| position.state.checks[color.index()] as i32;
+        200 * (given(position.turn) - given(position.turn.opponent()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fen::STARTING_FEN, variants::after_moves};
+
+    #[test]
+    fn checks_are_counted_and_written_in_the_fen() {
+        // Bb5+ and Bxc6+ are two checks by white
+        let position = after_moves(
+            &ThreeCheck,
+            STARTING_FEN,
+            &["e2e4", "d7d5", "f1b5", "c7c6", "b5c6"],
+        );
+        assert_eq!(position.state.checks, [2, 0]);
+        assert!(position.to_fen().ends_with(" +2+0"));
+        assert_eq!(position.outcome(), None);
+
+        // Also read as the checks each side has left
+        let fen = "4k3/8/8/8/8/8/8/3QK3 w - - 1+3 0 1";
+        let read = Position::from_variant_fen(&ThreeCheck, fen).unwrap();
+        assert_eq!(read.state.checks, [2, 0]);
+    }
+
+    #[test]
+    fn the_third_check_wins() {
+        let position = after_moves(
+            &ThreeCheck,
+            "4k3/8/8/8/8/8/8/3QK3 w - - 0 1 +2+0",
+            &["d1d7"],
+        );
+        assert_eq!(position.state.checks, [3, 0]);
+        assert_eq!(
+            position.outcome(),
+            Some(GameResult::win(PieceColor::Light, "three checks"))
+        );
+    }
+}